@@ -1,16 +1,26 @@
-use chrono::TimeDelta;
+use std::collections::HashMap;
+
+use chrono::{TimeDelta, Utc};
 use itertools::Itertools;
 use poise::{
     serenity_prelude::{
         self as serenity, CreateActionRow, CreateAllowedMentions, CreateMessage, CreateSelectMenu,
-        CreateSelectMenuOption, EditMember, Mentionable, UserId,
+        CreateSelectMenuOption, EditMessage, Mentionable, PermissionOverwrite,
+        PermissionOverwriteType, Permissions, UserId,
     },
     CreateReply,
 };
+use rand::seq::SliceRandom;
 
 use crate::{
-    apply_match_results, log_match_results, update_bans, BanData, ButtonData, Context,
-    DerivedPlayerData, Error, MatchResult, QueueMessageType, QueueState,
+    audit::{audit, AuditAction, AuditTarget},
+    await_result_proof, blacklisted_pair,
+    configure_command::get_queue_uuid,
+    create_map_vote_message, hold_result_for_proof, is_fake_player, is_not_found,
+    prune_historical_matches, record_game_result, resolve_match, result_lock_in_time,
+    tally_map_votes, tally_result_votes, try_matchmaking, update_bans, update_leaver_decay,
+    BanData, ButtonData, Context, DerivedPlayerData, Error, MatchResult, MatchmakingOutcome,
+    PreferenceDimension, QueueMessageType, RoleConfiguration,
 };
 
 #[poise::command(prefix_command, required_permissions = "MANAGE_CHANNELS")]
@@ -38,6 +48,8 @@ async fn ban_player(
         .unwrap()
         .queues
         .clone();
+    let mut notify_player = false;
+    let mut player_notice = None;
     for queue in queues {
         update_bans(ctx.data().clone(), &queue);
         let ban_seconds = 60 * 60 * (24 * days.unwrap_or(0) as i64 + hours.unwrap_or(0) as i64);
@@ -49,6 +61,9 @@ async fn ban_player(
             shadow_ban: is_shadow_ban.unwrap_or(false),
         };
         let ban_text = get_ban_text(&player, &ban_data);
+        if !ban_data.shadow_ban {
+            player_notice = Some(get_ban_notice(&ctx, &ban_data));
+        }
         let was_previously_banned = ctx
             .data()
             .player_bans
@@ -62,20 +77,26 @@ async fn ban_player(
         } else {
             ban_text.clone()
         };
-        let audit_channel = ctx.data().configuration.get(&queue).unwrap().audit_channel;
-        if let Some(audit_log) = audit_channel {
-            audit_log
-                .send_message(
-                    ctx.http(),
-                    CreateMessage::new()
-                        .content(format!("{}: {}", ctx.author().mention(), ban_text))
-                        .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
-                )
-                .await?;
-        }
+        notify_player |= ctx
+            .data()
+            .configuration
+            .get(&queue)
+            .unwrap()
+            .dm_ban_notifications;
+        audit(
+            &ctx,
+            &queue,
+            AuditAction::Ban,
+            AuditTarget::Player(player),
+            ban_text.clone(),
+        )
+        .await?;
         ctx.send(CreateReply::default().content(response).ephemeral(true))
             .await?;
     }
+    if let (true, Some(player_notice)) = (notify_player, player_notice) {
+        send_ban_notice(&ctx, player, player_notice).await?;
+    }
     Ok(())
 }
 
@@ -94,32 +115,33 @@ async fn unban_player(
         .unwrap()
         .queues
         .clone();
+    let mut notify_player = false;
     for queue in queues {
         update_bans(ctx.data().clone(), &queue);
-        let was_banned = ctx
+        let removed_ban = ctx
             .data()
             .player_bans
             .get_mut(&queue)
             .unwrap()
-            .remove(&player)
-            .is_some();
+            .remove(&player);
 
-        let response = if was_banned {
-            let audit_channel = ctx.data().configuration.get(&queue).unwrap().audit_channel;
-            if let Some(audit_log) = audit_channel {
-                audit_log
-                    .send_message(
-                        ctx.http(),
-                        CreateMessage::new()
-                            .content(format!(
-                                "{} unbanned {}.",
-                                ctx.author().mention(),
-                                player.mention()
-                            ))
-                            .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
-                    )
-                    .await?;
-            }
+        let response = if let Some(removed_ban) = removed_ban {
+            let dm_ban_notifications = ctx
+                .data()
+                .configuration
+                .get(&queue)
+                .unwrap()
+                .dm_ban_notifications;
+            // Shadow banned players were never told about the ban
+            notify_player |= dm_ban_notifications && !removed_ban.shadow_ban;
+            audit(
+                &ctx,
+                &queue,
+                AuditAction::Unban,
+                AuditTarget::Player(player),
+                format!("Removed: {}", get_ban_text(&player, &removed_ban)),
+            )
+            .await?;
             format!("Unbanned {}.", player.mention())
         } else {
             format!("{} was not banned.", player.mention())
@@ -127,6 +149,55 @@ async fn unban_player(
         ctx.send(CreateReply::default().content(response).ephemeral(true))
             .await?;
     }
+    if notify_player {
+        let notice = format!(
+            "You have been unbanned from queueing in {}.",
+            get_guild_name(&ctx)
+        );
+        send_ban_notice(&ctx, player, notice).await?;
+    }
+    Ok(())
+}
+
+fn get_guild_name(ctx: &Context<'_>) -> String {
+    ctx.guild_id()
+        .and_then(|guild_id| guild_id.name(ctx))
+        .unwrap_or("this server".to_string())
+}
+
+fn get_ban_notice(ctx: &Context<'_>, ban_data: &BanData) -> String {
+    format!(
+        "You have been banned from queueing in {}{}{}.",
+        get_guild_name(ctx),
+        ban_data
+            .reason
+            .clone()
+            .map(|reason| format!(" for {}", reason))
+            .unwrap_or_default(),
+        ban_data
+            .end_time
+            .map(|end_time| format!(" until <t:{}:f>", end_time.timestamp()))
+            .unwrap_or_default(),
+    )
+}
+
+/// DMs a player about their ban, telling the moderator if their DMs are closed
+async fn send_ban_notice(ctx: &Context<'_>, player: UserId, notice: String) -> Result<(), Error> {
+    if player
+        .direct_message(ctx, CreateMessage::new().content(notice))
+        .await
+        .is_err()
+    {
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "Could not message {}. Maybe they don't have dms open?",
+                    player.mention()
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+    }
     Ok(())
 }
 
@@ -217,6 +288,122 @@ async fn get_player(
     Ok(())
 }
 
+const SEARCH_PAGE_SIZE: usize = 5;
+
+/// Searches server members by name, showing their queue data
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "BAN_MEMBERS"
+)]
+pub async fn search_player(
+    ctx: Context<'_>,
+    #[description = "Part of the player's name"] name: String,
+    #[description = "Result page"]
+    #[min = 1]
+    page: Option<u32>,
+) -> Result<(), Error> {
+    let search = name.to_lowercase();
+    let Some(matches) = ctx.guild().map(|guild| {
+        guild
+            .members
+            .values()
+            .filter(|member| {
+                [
+                    Some(&member.user.name),
+                    member.user.global_name.as_ref(),
+                    member.nick.as_ref(),
+                ]
+                .into_iter()
+                .flatten()
+                .any(|name| name.to_lowercase().contains(&search))
+            })
+            .map(|member| (member.user.id, member.display_name().to_string()))
+            .sorted_by_key(|(_, name)| name.to_lowercase())
+            .collect_vec()
+    }) else {
+        ctx.send(
+            CreateReply::default()
+                .content("Server members aren't cached yet")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let page_count = matches.len().div_ceil(SEARCH_PAGE_SIZE).max(1);
+    let page = (page.unwrap_or(1) as usize).min(page_count);
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&ctx.guild_id().unwrap())
+        .unwrap()
+        .queues
+        .clone();
+    for queue in queues.iter() {
+        update_bans(ctx.data().clone(), queue);
+    }
+    let mut response = format!(
+        "# Players matching \"{}\"\n{} found, page {}/{}\n",
+        name,
+        matches.len(),
+        page,
+        page_count
+    );
+    for (player, display_name) in matches
+        .iter()
+        .skip((page - 1) * SEARCH_PAGE_SIZE)
+        .take(SEARCH_PAGE_SIZE)
+    {
+        response += format!("## {} ({})\n", display_name, player.mention()).as_str();
+        for (queue_idx, queue) in queues.iter().enumerate() {
+            let player_data = ctx
+                .data()
+                .player_data
+                .get(queue)
+                .unwrap()
+                .get(player)
+                .cloned();
+            let ban = ctx
+                .data()
+                .player_bans
+                .get(queue)
+                .unwrap()
+                .get(player)
+                .map(|ban_data| get_ban_text(player, ban_data));
+            let stats = match player_data {
+                Some(player_data) => format!(
+                    "rating {}, {}W/{}L/{}D",
+                    player_data
+                        .rating
+                        .map(|rating| rating.rating.to_string())
+                        .unwrap_or("unrated".to_string()),
+                    player_data.stats.wins,
+                    player_data.stats.losses,
+                    player_data.stats.draws
+                ),
+                None => "no data".to_string(),
+            };
+            response += format!(
+                "Queue {}: {}{}\n",
+                queue_idx,
+                stats,
+                ban.map(|ban| format!("\n{}", ban)).unwrap_or_default()
+            )
+            .as_str();
+        }
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
+    )
+    .await?;
+    Ok(())
+}
+
 /// Manage a user
 #[poise::command(
     slash_command,
@@ -245,13 +432,14 @@ pub async fn list_leavers(ctx: Context<'_>) -> Result<(), Error> {
         .queues
         .clone();
     for queue in queues {
+        update_leaver_decay(ctx.data(), &queue);
         let leave_data = ctx
             .data()
             .leaver_data
             .get(&queue)
             .unwrap()
             .iter()
-            .map(|(id, count)| format!("{} left {} times", id.mention(), count))
+            .map(|(id, record)| format!("{} left {} times", id.mention(), record.count))
             .join("\n");
 
         let response = format!("# Player Leave Counts\n{}", leave_data);
@@ -261,36 +449,262 @@ pub async fn list_leavers(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-/// Forces the outcome of a game
-#[poise::command(slash_command, prefix_command, rename = "cancel")]
-async fn force_outcome_cancel(ctx: Context<'_>) -> Result<(), Error> {
-    force_result(ctx, MatchResult::Cancel).await
-}
-
-/// Forces the outcome of a game
-#[poise::command(slash_command, prefix_command, rename = "draw")]
-async fn force_outcome_draw(ctx: Context<'_>) -> Result<(), Error> {
-    force_result(ctx, MatchResult::Tie).await
+/// Shows a player's leave history
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "BAN_MEMBERS"
+)]
+pub async fn leaver_stats(
+    ctx: Context<'_>,
+    #[description = "Player"] player: UserId,
+) -> Result<(), Error> {
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&ctx.guild_id().unwrap())
+        .unwrap()
+        .queues
+        .clone();
+    let mut response = format!("# Leaver Stats for {}\n", player.mention());
+    for (queue_idx, queue) in queues.iter().enumerate() {
+        update_leaver_decay(ctx.data(), queue);
+        let record = ctx
+            .data()
+            .leaver_data
+            .get(queue)
+            .unwrap()
+            .get(&player)
+            .cloned()
+            .unwrap_or_default();
+        let banned = ctx
+            .data()
+            .player_bans
+            .get(queue)
+            .unwrap()
+            .get(&player)
+            .filter(|ban| !ban.shadow_ban)
+            .map(|ban| {
+                ban.end_time
+                    .map(|end_time| format!("until <t:{}:f>", end_time.timestamp()))
+                    .unwrap_or("permanently".to_string())
+            })
+            .unwrap_or("no".to_string());
+        let recent_leaves = record
+            .leaves
+            .iter()
+            .rev()
+            .take(5)
+            .map(|(match_id, time)| {
+                let match_name = ctx
+                    .data()
+                    .historical_match_data
+                    .lock()
+                    .unwrap()
+                    .get(match_id)
+                    .map(|match_data| match_data.name.clone());
+                match match_name {
+                    Some(match_name) => format!("<t:{}:R> in {}", time.timestamp(), match_name),
+                    None => format!("<t:{}:R>", time.timestamp()),
+                }
+            })
+            .join(", ");
+        response += format!(
+            "### Queue {}\nLeave count: {}\nRecent leaves: {}\nBanned: {}\n",
+            queue_idx,
+            record.count,
+            if recent_leaves.is_empty() {
+                "none recorded"
+            } else {
+                recent_leaves.as_str()
+            },
+            banned
+        )
+        .as_str();
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
+    )
+    .await?;
+    Ok(())
 }
 
-/// Forces the outcome of a game
-#[poise::command(slash_command, prefix_command, rename = "team")]
-async fn force_outcome_team(ctx: Context<'_>, #[min = 1] team_idx: u32) -> Result<(), Error> {
-    force_result(ctx, MatchResult::Team(team_idx - 1)).await
+/// Removes leaves from a player's leaver count
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "BAN_MEMBERS"
+)]
+pub async fn approve_leaver_appeal(
+    ctx: Context<'_>,
+    #[description = "Player"] player: UserId,
+    #[description = "Leaves to remove, clears all if unset"]
+    #[min = 1]
+    count: Option<u32>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let remaining = {
+        let mut leaver_data = ctx.data().leaver_data.get_mut(&queue_uuid).unwrap();
+        let remaining = leaver_data.get_mut(&player).map(|record| {
+            record.count = record.count.saturating_sub(count.unwrap_or(u32::MAX));
+            // Appeals are usually for the latest leaves
+            record.leaves.truncate(record.count as usize);
+            record.count
+        });
+        if remaining == Some(0) {
+            leaver_data.remove(&player);
+        }
+        remaining
+    };
+    let Some(remaining) = remaining else {
+        ctx.send(
+            CreateReply::default()
+                .content(format!("{} has no recorded leaves.", player.mention()))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let response = format!(
+        "{} now has {} recorded leaves.",
+        player.mention(),
+        remaining
+    );
+    audit(
+        &ctx,
+        &queue_uuid,
+        AuditAction::ApproveLeaverAppeal,
+        AuditTarget::Player(player),
+        response.clone(),
+    )
+    .await?;
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
 }
 
-/// Forces the outcome of a game
+/// Hides or restores a match's channels for its players
 #[poise::command(
     slash_command,
     prefix_command,
-    default_member_permissions = "BAN_MEMBERS",
-    subcommands("force_outcome_cancel", "force_outcome_draw", "force_outcome_team")
+    default_member_permissions = "MANAGE_CHANNELS"
 )]
-pub async fn force_outcome(_ctx: Context<'_>) -> Result<(), Error> {
+pub async fn set_team_channels_private(
+    ctx: Context<'_>,
+    #[description = "Whether players are locked out of the match channels"] private: bool,
+) -> Result<(), Error> {
+    let match_number = {
+        let match_channels = ctx.data().match_channels.lock().unwrap();
+        match_channels.get(&ctx.channel_id()).cloned()
+    };
+    let Some(match_number) = match_number else {
+        ctx.send(
+            CreateReply::default()
+                .content("This command must be done in a match channel!")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let match_info = {
+        let mut match_data = ctx.data().match_data.lock().unwrap();
+        let match_data = match_data
+            .get_mut(&match_number)
+            .ok_or("Could not get match data")?;
+        (match_data.locked != private).then(|| {
+            match_data.locked = private;
+            (
+                match_data.channels.clone(),
+                match_data.members.clone(),
+                match_data.queue,
+                match_data.name.clone(),
+            )
+        })
+    };
+    let Some((channels, members, queue_id, name)) = match_info else {
+        ctx.send(
+            CreateReply::default()
+                .content(if private {
+                    "Match is already private."
+                } else {
+                    "Match is not private."
+                })
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    ctx.defer_ephemeral().await?;
+    let (allow, deny) = if private {
+        (
+            Permissions::empty(),
+            Permissions::VIEW_CHANNEL | Permissions::CONNECT,
+        )
+    } else {
+        (Permissions::VIEW_CHANNEL, Permissions::empty())
+    };
+    for channel in channels {
+        for player in members.iter().flatten() {
+            channel
+                .create_permission(
+                    ctx.http(),
+                    PermissionOverwrite {
+                        allow,
+                        deny,
+                        kind: PermissionOverwriteType::Member(*player),
+                    },
+                )
+                .await?;
+        }
+    }
+    let response = if private {
+        format!("Locked players out of {}.", name)
+    } else {
+        format!("Restored player access to {}.", name)
+    };
+    audit(
+        &ctx,
+        &queue_id,
+        AuditAction::SetTeamChannelsPrivate,
+        AuditTarget::Match(name),
+        response.clone(),
+    )
+    .await?;
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
     Ok(())
 }
 
-async fn force_result(ctx: Context<'_>, result: MatchResult) -> Result<(), Error> {
+/// Moves match players in the wrong voice channel into their team's channel
+///
+/// Without `stragglers` only players in another team's channel are moved, otherwise players in any
+/// voice channel outside the match are too.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MOVE_MEMBERS"
+)]
+pub async fn fix_team_voice(
+    ctx: Context<'_>,
+    #[description = "Also move players in voice channels outside the match"]
+    #[flag]
+    stragglers: bool,
+) -> Result<(), Error> {
     let match_number = {
         let match_channels = ctx.data().match_channels.lock().unwrap();
         match_channels.get(&ctx.channel_id()).cloned()
@@ -298,94 +712,902 @@ async fn force_result(ctx: Context<'_>, result: MatchResult) -> Result<(), Error
     let Some(match_number) = match_number else {
         ctx.send(
             CreateReply::default()
-                .content("Not in match: cannot force outcome.")
+                .content("This command must be done in a match channel!")
                 .ephemeral(true),
         )
         .await?;
         return Ok(());
     };
-    let queue_id = ctx
-        .data()
-        .match_data
-        .lock()
-        .unwrap()
-        .get(&match_number)
-        .unwrap()
-        .queue;
-    let post_match_channel = ctx
-        .data()
-        .configuration
-        .get(&queue_id)
-        .unwrap()
-        .post_match_channel
-        .clone();
-    let (channels, players) = {
+    let (channels, members, queue_id, name) = {
         let match_data = ctx.data().match_data.lock().unwrap();
-        let match_data = match_data.get(&match_number).unwrap();
-        log_match_results(ctx.data().clone(), &result, &match_data);
-        (match_data.channels.clone(), match_data.members.clone())
+        let match_data = match_data
+            .get(&match_number)
+            .ok_or("Could not get match data")?;
+        (
+            match_data.channels.clone(),
+            match_data.members.clone(),
+            match_data.queue,
+            match_data.name.clone(),
+        )
     };
-
-    apply_match_results(ctx.data().clone(), result, &players, queue_id);
-
+    // The team voice channels come first, followed by the text channel
+    let team_channels = &channels[..members.len().min(channels.len())];
+    let (misplaced, outsiders) = {
+        let Some(guild) = ctx.guild() else {
+            ctx.send(
+                CreateReply::default()
+                    .content("Could not find who is in voice.")
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        };
+        let misplaced = members
+            .iter()
+            .zip(team_channels)
+            .flat_map(|(team, team_channel)| {
+                team.iter().filter_map(|player| {
+                    let current_channel = guild.voice_states.get(player)?.channel_id?;
+                    let wrong_channel = if team_channels.contains(&current_channel) {
+                        current_channel != *team_channel
+                    } else {
+                        stragglers
+                    };
+                    wrong_channel.then_some((*player, *team_channel))
+                })
+            })
+            .collect_vec();
+        // Anyone else in the team channels is only reported, since they may be spectating
+        let outsiders = guild
+            .voice_states
+            .values()
+            .filter(|state| {
+                state
+                    .channel_id
+                    .is_some_and(|channel| team_channels.contains(&channel))
+                    && !members.iter().flatten().contains(&state.user_id)
+            })
+            .map(|state| state.user_id)
+            .collect_vec();
+        (misplaced, outsiders)
+    };
+    ctx.defer_ephemeral().await?;
     let guild_id = ctx.guild_id().unwrap();
-    if let Some(post_match_channel) = post_match_channel {
-        for player in players.iter().flat_map(|t| t) {
-            ctx.data()
-                .global_player_data
-                .lock()
-                .unwrap()
-                .get_mut(player)
-                .unwrap()
-                .queue_state = QueueState::None;
-            ctx.http()
-                .get_member(guild_id, *player)
-                .await?
-                .edit(
-                    ctx.http(),
-                    EditMember::new().voice_channel(post_match_channel),
-                )
-                .await
-                .ok();
+    let mut moved = vec![];
+    let mut failed = vec![];
+    for (player, team_channel) in misplaced {
+        match guild_id.move_member(ctx.http(), player, team_channel).await {
+            Ok(_) => moved.push(player),
+            Err(err) => {
+                eprintln!("Could not move {} to their team channel: {}", player, err);
+                failed.push(player);
+            }
         }
     }
-    for channel in channels {
-        ctx.data().match_channels.lock().unwrap().remove(&channel);
-        ctx.http().delete_channel(channel, None).await?;
+    let mut response = if moved.is_empty() && failed.is_empty() {
+        format!(
+            "All players in voice in {} are in their team's channel.",
+            name
+        )
+    } else {
+        let mut response = vec![];
+        if !moved.is_empty() {
+            response.push(format!(
+                "Moved {} to their team's channel in {}.",
+                moved.iter().map(|player| player.mention()).join(", "),
+                name
+            ));
+        }
+        if !failed.is_empty() {
+            response.push(format!(
+                "Could not move {} to their team's channel in {}.",
+                failed.iter().map(|player| player.mention()).join(", "),
+                name
+            ));
+        }
+        response.join("\n")
+    };
+    if !outsiders.is_empty() {
+        response += format!(
+            "\nNot in the match but in its team channels: {}",
+            outsiders.iter().map(|user| user.mention()).join(", ")
+        )
+        .as_str();
     }
-    {
-        let mut match_data = ctx.data().match_data.lock().unwrap();
-        match_data.remove(&match_number);
+    if !moved.is_empty() {
+        audit(
+            &ctx,
+            &queue_id,
+            AuditAction::FixTeamVoice,
+            AuditTarget::Match(name),
+            response.clone(),
+        )
+        .await?;
     }
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
     Ok(())
 }
 
-/// Creates a message players can enter queue with
+/// Sets the map of the current match
 #[poise::command(
     slash_command,
     prefix_command,
     default_member_permissions = "MANAGE_CHANNELS"
 )]
-pub async fn create_queue_message(ctx: Context<'_>) -> Result<(), Error> {
-    let queues = ctx
+pub async fn edit_match_map(
+    ctx: Context<'_>,
+    #[description = "Map"] map: String,
+) -> Result<(), Error> {
+    let match_number = {
+        let match_channels = ctx.data().match_channels.lock().unwrap();
+        match_channels.get(&ctx.channel_id()).cloned()
+    };
+    let Some(match_number) = match_number else {
+        ctx.send(
+            CreateReply::default()
+                .content("This command must be done in a match channel!")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let queue_id = {
+        let match_data = ctx.data().match_data.lock().unwrap();
+        match_data
+            .get(&match_number)
+            .ok_or("Could not get match data")?
+            .queue
+    };
+    let maps = ctx
         .data()
-        .guild_data
-        .lock()
+        .configuration
+        .get(&queue_id)
+        .unwrap()
+        .maps
+        .clone();
+    if !maps.contains_key(&map) {
+        ctx.send(
+            CreateReply::default()
+                .content(format!("{} is not a map for this queue.", map))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let (previous_map, name, map_vote_message) = {
+        let mut match_data = ctx.data().match_data.lock().unwrap();
+        let match_data = match_data
+            .get_mut(&match_number)
+            .ok_or("Could not get match data")?;
+        (
+            match_data.map.replace(map.clone()),
+            match_data.name.clone(),
+            match_data.map_vote_message.take(),
+        )
+    };
+    // Closes a running map vote so it can't override the chosen map
+    if let Some(map_vote_message) = map_vote_message {
+        ctx.channel_id()
+            .edit_message(
+                ctx.http(),
+                map_vote_message,
+                EditMessage::new().components(vec![]),
+            )
+            .await
+            .ok();
+    }
+    let details = match &previous_map {
+        Some(previous_map) => format!("Map changed from {} to {}.", previous_map, map),
+        None => format!("Map set to {}.", map),
+    };
+    let change = match previous_map {
+        Some(previous_map) => format!(
+            "changed the map of {} from {} to {}",
+            name, previous_map, map
+        ),
+        None => format!("set the map of {} to {}", name, map),
+    };
+    audit(
+        &ctx,
+        &queue_id,
+        AuditAction::EditMatchMap,
+        AuditTarget::Match(name),
+        details,
+    )
+    .await?;
+    ctx.say(format!("# Map: {}\nAn admin {}.", map, change))
+        .await?;
+    Ok(())
+}
+
+/// Opens a new map vote in the current match, replacing any running one
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn force_start_map_vote(ctx: Context<'_>) -> Result<(), Error> {
+    let match_number = {
+        let match_channels = ctx.data().match_channels.lock().unwrap();
+        match_channels.get(&ctx.channel_id()).cloned()
+    };
+    let Some(match_number) = match_number else {
+        ctx.send(
+            CreateReply::default()
+                .content("This command must be done in a match channel!")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let queue_id = {
+        let match_data = ctx.data().match_data.lock().unwrap();
+        match_data
+            .get(&match_number)
+            .ok_or("Could not get match data")?
+            .queue
+    };
+    let (vote_maps, select_menu, maps) = {
+        let config = ctx.data().configuration.get(&queue_id).unwrap();
+        let vote_count = match config.effective_map_vote_count() {
+            0 => config.maps.len(),
+            map_vote_count => map_vote_count,
+        };
+        (
+            config
+                .maps
+                .keys()
+                .cloned()
+                .collect_vec()
+                .choose_multiple(&mut rand::thread_rng(), vote_count.min(25))
+                .cloned()
+                .collect_vec(),
+            config.map_vote_select_menu,
+            config.maps.clone(),
+        )
+    };
+    if vote_maps.len() < 2 {
+        ctx.send(
+            CreateReply::default()
+                .content("This queue needs at least 2 maps to vote on")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let map_vote_message = ctx
+        .channel_id()
+        .send_message(
+            ctx.http(),
+            create_map_vote_message("# Map Vote".to_string(), &vote_maps, select_menu, &maps),
+        )
+        .await?;
+    let previous_map_vote_message = {
+        let mut match_data = ctx.data().match_data.lock().unwrap();
+        let match_data = match_data
+            .get_mut(&match_number)
+            .ok_or("Could not get match data")?;
+        match_data.map = None;
+        match_data.map_votes.clear();
+        match_data.map_vote_end_time = None;
+        match_data.vote_messages.push(map_vote_message.id);
+        match_data.map_vote_message.replace(map_vote_message.id)
+    };
+    if let Some(previous_map_vote_message) = previous_map_vote_message {
+        ctx.channel_id()
+            .edit_message(
+                ctx.http(),
+                previous_map_vote_message,
+                EditMessage::new().components(vec![]),
+            )
+            .await
+            .ok();
+    }
+    ctx.send(
+        CreateReply::default()
+            .content("Map vote started")
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Ends the current match's map vote, picking the map with the most votes
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn end_map_vote(ctx: Context<'_>) -> Result<(), Error> {
+    let match_number = {
+        let match_channels = ctx.data().match_channels.lock().unwrap();
+        match_channels.get(&ctx.channel_id()).cloned()
+    };
+    let Some(match_number) = match_number else {
+        ctx.send(
+            CreateReply::default()
+                .content("This command must be done in a match channel!")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let result = {
+        let mut match_data = ctx.data().match_data.lock().unwrap();
+        let match_data = match_data
+            .get_mut(&match_number)
+            .ok_or("Could not get match data")?;
+        if match_data.map_vote_message.is_none() {
+            Err("There is no map vote running in this match")
+        } else if let Some(map) = match_data
+            .map_votes
+            .values()
+            .counts()
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(map, _)| map.clone())
+        {
+            match_data.map = Some(map.clone());
+            Ok((map, match_data.map_vote_message.take().unwrap()))
+        } else {
+            Err("Nobody has voted yet, use /edit_match_map to pick a map instead")
+        }
+    };
+    let (map, map_vote_message) = match result {
+        Ok(result) => result,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    ctx.channel_id()
+        .edit_message(
+            ctx.http(),
+            map_vote_message,
+            EditMessage::new()
+                .content(format!("# Map: {}", map))
+                .components(vec![]),
+        )
+        .await
+        .ok();
+    ctx.say(format!("An admin ended the map vote. Map: {}", map))
+        .await?;
+    Ok(())
+}
+
+/// Rewrites the match's vote messages from the stored votes, resolving votes that have a majority
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn recount_votes(ctx: Context<'_>) -> Result<(), Error> {
+    let match_number = {
+        let match_channels = ctx.data().match_channels.lock().unwrap();
+        match_channels.get(&ctx.channel_id()).cloned()
+    };
+    let Some(match_number) = match_number else {
+        ctx.send(
+            CreateReply::default()
+                .content("This command must be done in a match channel!")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let tallies = {
+        let mut match_data = ctx.data().match_data.lock().unwrap();
+        let match_data = match_data
+            .get_mut(&match_number)
+            .ok_or("Could not get match data")?;
+        if match_data.resolved {
+            None
+        } else {
+            let (team_names, show_rosters, min_match_duration) = {
+                let config = ctx.data().configuration.get(&match_data.queue).unwrap();
+                (
+                    config.team_labels(match_data.members.len()),
+                    config.show_rosters_in_result_vote,
+                    config.min_match_duration,
+                )
+            };
+            let map_vote = match_data
+                .map_vote_message
+                .map(|message| (message, tally_map_votes(match_data)));
+            let lock_in_time = result_lock_in_time(match_data, min_match_duration);
+            let (mut result, mut content) =
+                tally_result_votes(match_data, &team_names, show_rosters, lock_in_time);
+            // Recounts go through the same proof gate as votes, /force_outcome skips it
+            let awaiting_proof_since =
+                hold_result_for_proof(ctx.data(), match_data, &mut result, &mut content);
+            Some((
+                map_vote,
+                (match_data.result_vote_message, result, content),
+                awaiting_proof_since,
+            ))
+        }
+    };
+    let Some((map_vote, result_vote, awaiting_proof_since)) = tallies else {
+        ctx.send(
+            CreateReply::default()
+                .content("This match has already been resolved")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    if let Some(since) = awaiting_proof_since {
+        tokio::spawn(await_result_proof(
+            ctx.data().clone(),
+            ctx.serenity_context().http.clone(),
+            match_number,
+            ctx.channel_id(),
+            since,
+        ));
+    }
+    let mut response = vec![];
+    if let Some((message, (map, content))) = map_vote {
+        let edit = match &map {
+            Some(map) => EditMessage::new()
+                .content(format!("# Map: {}", map))
+                .components(vec![]),
+            None => EditMessage::new().content(content),
+        };
+        ctx.channel_id()
+            .edit_message(ctx.http(), message, edit)
+            .await?;
+        response.push(match map {
+            Some(map) => format!("The map vote has a majority for {}.", map),
+            None => "Recounted the map vote.".to_string(),
+        });
+    }
+    let (result_vote_message, result, content) = result_vote;
+    match result_vote_message {
+        Some(message) => {
+            let mut edit = EditMessage::new().content(content);
+            if result.is_some() {
+                edit = edit.components(vec![]);
+            }
+            ctx.channel_id()
+                .edit_message(ctx.http(), message, edit)
+                .await?;
+            response.push("Recounted the result vote.".to_string());
+        }
+        None => response.push(
+            "This match's result vote message isn't known, so only its votes were checked."
+                .to_string(),
+        ),
+    }
+    if let Some(result) = &result {
+        response.push(format!("The result vote has a majority for {}.", result));
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(response.join("\n"))
+            .ephemeral(true),
+    )
+    .await?;
+    if let Some(result) = result {
+        record_game_result(
+            ctx.data().clone(),
+            ctx.serenity_context().http.clone(),
+            Some(ctx.serenity_context().cache.clone()),
+            ctx.guild_id().unwrap(),
+            match_number,
+            ctx.channel_id(),
+            result,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Forces the outcome of a game
+#[poise::command(slash_command, prefix_command, rename = "cancel")]
+async fn force_outcome_cancel(ctx: Context<'_>) -> Result<(), Error> {
+    force_result(ctx, MatchResult::Cancel).await
+}
+
+/// Forces the outcome of a game
+#[poise::command(slash_command, prefix_command, rename = "draw")]
+async fn force_outcome_draw(ctx: Context<'_>) -> Result<(), Error> {
+    force_result(ctx, MatchResult::Tie).await
+}
+
+/// Forces the outcome of a game
+#[poise::command(slash_command, prefix_command, rename = "team")]
+async fn force_outcome_team(ctx: Context<'_>, #[min = 1] team_idx: u32) -> Result<(), Error> {
+    force_result(ctx, MatchResult::Team(team_idx - 1)).await
+}
+
+/// Forces the outcome of a game
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "BAN_MEMBERS",
+    subcommands("force_outcome_cancel", "force_outcome_draw", "force_outcome_team")
+)]
+pub async fn force_outcome(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+async fn force_result(ctx: Context<'_>, result: MatchResult) -> Result<(), Error> {
+    let match_number = {
+        let match_channels = ctx.data().match_channels.lock().unwrap();
+        match_channels.get(&ctx.channel_id()).cloned()
+    };
+    let Some(match_number) = match_number else {
+        ctx.send(
+            CreateReply::default()
+                .content("Not in match: cannot force outcome.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let (queue_id, match_name, rated_result) = {
+        let match_data = ctx.data().match_data.lock().unwrap();
+        let match_data = match_data.get(&match_number).unwrap();
+        let rate_series_games = ctx
+            .data()
+            .configuration
+            .get(&match_data.queue)
+            .unwrap()
+            .rate_series_games;
+        // Games of a series rated one at a time have already counted towards ratings
+        let rated_result =
+            (!rate_series_games || match_data.series_games.is_empty()).then(|| result.clone());
+        (match_data.queue, match_data.name.clone(), rated_result)
+    };
+    let resolved = resolve_match(
+        ctx.data().clone(),
+        ctx.serenity_context().http.clone(),
+        Some(&ctx.serenity_context().cache),
+        ctx.guild_id().unwrap(),
+        match_number,
+        result.clone(),
+        rated_result,
+    )
+    .await?;
+    if !resolved {
+        ctx.send(
+            CreateReply::default()
+                .content("Match is already resolved.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    audit(
+        &ctx,
+        &queue_id,
+        AuditAction::ForceResult,
+        AuditTarget::Match(match_name),
+        format!("Resolved as {}.", result),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Starts a match from the current queue even if it exceeds the maximum queue cost
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn force_match(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    {
+        let mut guard = ctx.data().is_matchmaking.get_mut(&queue_uuid).unwrap();
+        if guard.is_some() {
+            ctx.send(
+                CreateReply::default()
+                    .content("Matchmaking is currently running, try again shortly.")
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+        *guard = Some(());
+    }
+    ctx.defer_ephemeral().await?;
+    let outcome = try_matchmaking(
+        ctx.data().clone(),
+        ctx.serenity_context().http.clone(),
+        ctx.serenity_context().cache.clone(),
+        ctx.guild_id().unwrap(),
+        &queue_uuid,
+        true,
+    )
+    .await;
+    *ctx.data().is_matchmaking.get_mut(&queue_uuid).unwrap() = None;
+    let response = match outcome? {
+        MatchmakingOutcome::NotEnoughPlayers => "Not enough players are queued.".to_string(),
+        MatchmakingOutcome::Retry(_) => {
+            "Could not find a valid team and role assignment.".to_string()
+        }
+        MatchmakingOutcome::Unmatchable(reason) => format!("{}.", reason),
+        MatchmakingOutcome::Created(cost) => {
+            ctx.data()
+                .message_edit_notify
+                .get(&queue_uuid)
+                .unwrap()
+                .notify_one();
+            let response = format!(
+                "Forced match with a cost of {} (maximum queue cost is {}).",
+                cost,
+                ctx.data()
+                    .configuration
+                    .get(&queue_uuid)
+                    .unwrap()
+                    .maximum_queue_cost
+            );
+            audit(
+                &ctx,
+                &queue_uuid,
+                AuditAction::ForceMatch,
+                AuditTarget::Queue,
+                response.clone(),
+            )
+            .await?;
+            response
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Creates a message players can enter queue with
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn create_queue_message(ctx: Context<'_>) -> Result<(), Error> {
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&ctx.guild_id().unwrap())
+        .unwrap()
+        .queues
+        .clone();
+    for queue in queues {
+        let header = ctx
+            .data()
+            .configuration
+            .get(&queue)
+            .unwrap()
+            .queue_message_header
+            .clone();
+        let msg = ctx
+            .send(
+                CreateReply::default()
+                    .content(header)
+                    .components(queue_message_components())
+                    .ephemeral(false),
+            )
+            .await?
+            .into_message()
+            .await?
+            .id;
+        ctx.data()
+            .configuration
+            .get_mut(&queue)
+            .unwrap()
+            .queue_messages
+            .push((ctx.channel_id(), msg, QueueMessageType::Queue));
+    }
+
+    Ok(())
+}
+
+fn queue_message_components() -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        ButtonData::Queue.get_button(),
+        ButtonData::LeaveQueue.get_button(),
+        ButtonData::Status.get_button(),
+    ])]
+}
+
+/// Removes references to queue messages that no longer exist
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn prune_queue_messages(
+    ctx: Context<'_>,
+    #[description = "Repost deleted queue messages"] repost: Option<bool>,
+) -> Result<(), Error> {
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&ctx.guild_id().unwrap())
+        .unwrap()
+        .queues
+        .clone();
+    let mut pruned_count = 0;
+    let mut reposted_count = 0;
+    for queue in queues {
+        let queue_messages = ctx
+            .data()
+            .configuration
+            .get(&queue)
+            .unwrap()
+            .queue_messages
+            .clone();
+        let mut remaining_messages = vec![];
+        for (channel, message, message_type) in queue_messages {
+            match ctx.http().get_message(channel, message).await {
+                Ok(_) => remaining_messages.push((channel, message, message_type)),
+                Err(error) if is_not_found(&error) => {
+                    pruned_count += 1;
+                    if !repost.unwrap_or(false) || !matches!(message_type, QueueMessageType::Queue)
+                    {
+                        continue;
+                    }
+                    let Ok(new_message) = channel
+                        .send_message(
+                            ctx.http(),
+                            CreateMessage::new()
+                                .content("## Matchmaking queue")
+                                .components(queue_message_components()),
+                        )
+                        .await
+                    else {
+                        continue;
+                    };
+                    reposted_count += 1;
+                    remaining_messages.push((channel, new_message.id, message_type));
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+        ctx.data()
+            .configuration
+            .get_mut(&queue)
+            .unwrap()
+            .queue_messages = remaining_messages;
+        ctx.data()
+            .message_edit_notify
+            .get(&queue)
+            .unwrap()
+            .notify_one();
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Pruned {} stale queue messages, reposted {}.",
+                pruned_count, reposted_count
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Removes finished matches older than the history retention from the match history
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn prune_history(
+    ctx: Context<'_>,
+    #[description = "Days of history to keep, defaults to the configured retention"]
+    #[min = 1]
+    days: Option<u32>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let configured_days = ctx
+        .data()
+        .configuration
+        .get(&queue_uuid)
+        .unwrap()
+        .history_retention_days;
+    let Some(days) = days.or((configured_days > 0).then_some(configured_days)) else {
+        ctx.send(
+            CreateReply::default()
+                .content("No history retention is configured, pass the days of history to keep")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let (pruned_count, freed_bytes) = prune_historical_matches(ctx.data(), &queue_uuid, days);
+    audit(
+        &ctx,
+        &queue_uuid,
+        AuditAction::PruneHistory,
+        AuditTarget::Queue,
+        format!("Removed {} matches older than {} days", pruned_count, days),
+    )
+    .await?;
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Removed {} matches older than {} days, freeing about {} KB.",
+                pruned_count,
+                days,
+                freed_bytes / 1024
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Creates a message players can choose a preference such as region with
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn create_preference_message(
+    ctx: Context<'_>,
+    #[description = "Preference name"] preference: String,
+) -> Result<(), Error> {
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
         .unwrap()
         .get(&ctx.guild_id().unwrap())
         .unwrap()
         .queues
         .clone();
     for queue in queues {
+        let dimension = ctx
+            .data()
+            .configuration
+            .get(&queue)
+            .unwrap()
+            .preferences
+            .get(&preference)
+            .cloned();
+        let Some(dimension) = dimension else {
+            ctx.send(
+                CreateReply::default()
+                    .content(format!("{} is not a preference for this queue", preference))
+                    .ephemeral(true),
+            )
+            .await?;
+            continue;
+        };
         let msg = ctx
             .send(
                 CreateReply::default()
-                    .content("## Matchmaking queue")
-                    .components(vec![CreateActionRow::Buttons(vec![
-                        ButtonData::Queue.get_button(),
-                        ButtonData::LeaveQueue.get_button(),
-                        ButtonData::Status.get_button(),
-                    ])])
+                    .content(format!("## {} select", preference))
+                    .components(preference_message_components(&preference, &dimension))
                     .ephemeral(false),
             )
             .await?
@@ -397,12 +1619,36 @@ pub async fn create_queue_message(ctx: Context<'_>) -> Result<(), Error> {
             .get_mut(&queue)
             .unwrap()
             .queue_messages
-            .push((ctx.channel_id(), msg, QueueMessageType::Queue));
+            .push((
+                ctx.channel_id(),
+                msg,
+                QueueMessageType::Preference(preference.clone()),
+            ));
     }
 
     Ok(())
 }
 
+fn preference_message_components(
+    preference: &str,
+    dimension: &PreferenceDimension,
+) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(
+            ButtonData::PreferenceSelect(preference.to_string()).get_id(),
+            serenity::CreateSelectMenuKind::String {
+                options: dimension
+                    .values
+                    .iter()
+                    .map(|value| CreateSelectMenuOption::new(value.clone(), value.clone()))
+                    .collect(),
+            },
+        )
+        .min_values(0)
+        .max_values(dimension.values.len() as u8),
+    )]
+}
+
 /// Creates a message players can choose roles with
 #[poise::command(
     slash_command,
@@ -425,25 +1671,7 @@ pub async fn create_roles_message(ctx: Context<'_>) -> Result<(), Error> {
             .send(
                 CreateReply::default()
                     .content("## Role select")
-                    .components(vec![CreateActionRow::SelectMenu(
-                        CreateSelectMenu::new(
-                            ButtonData::RoleSelect.get_id(),
-                            serenity::CreateSelectMenuKind::String {
-                                options: roles
-                                    .iter()
-                                    .map(|(role_id, role)| {
-                                        CreateSelectMenuOption::new(
-                                            role.name.clone(),
-                                            role_id.clone(),
-                                        )
-                                        .description(role.description.clone())
-                                        .default_selection(true)
-                                    })
-                                    .collect(),
-                            },
-                        )
-                        .max_values(roles.len() as u8),
-                    )])
+                    .components(roles_message_components(&roles))
                     .ephemeral(false),
             )
             .await?
@@ -461,6 +1689,25 @@ pub async fn create_roles_message(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+fn roles_message_components(roles: &HashMap<String, RoleConfiguration>) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(
+            ButtonData::RoleSelect.get_id(),
+            serenity::CreateSelectMenuKind::String {
+                options: roles
+                    .iter()
+                    .map(|(role_id, role)| {
+                        CreateSelectMenuOption::new(role.name.clone(), role_id.clone())
+                            .description(role.description.clone())
+                            .default_selection(true)
+                    })
+                    .collect(),
+            },
+        )
+        .max_values(roles.len() as u8),
+    )]
+}
+
 /// Creates a message where players can register to queue with an mmr
 #[poise::command(
     slash_command,
@@ -496,12 +1743,7 @@ pub async fn create_register_message(
         .await?;
         return Ok(());
     };
-    let button_rows = buttons_data
-        .iter()
-        .chunks(5)
-        .into_iter()
-        .map(|row| CreateActionRow::Buttons(row.cloned().collect_vec()))
-        .collect_vec();
+    let button_rows = register_button_rows(buttons_data);
     let queues = ctx
         .data()
         .guild_data
@@ -533,3 +1775,600 @@ pub async fn create_register_message(
 
     Ok(())
 }
+
+fn register_button_rows(buttons: Vec<serenity::CreateButton>) -> Vec<CreateActionRow> {
+    buttons
+        .into_iter()
+        .chunks(5)
+        .into_iter()
+        .map(|row| CreateActionRow::Buttons(row.collect_vec()))
+        .collect_vec()
+}
+
+/// Reposts every message of a queue in the channel it was created in, replacing the old ones
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn recreate_queue_messages(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    ctx.defer_ephemeral().await?;
+    let (queue_messages, roles, preferences) = {
+        let config = ctx.data().configuration.get(&queue_uuid).unwrap();
+        (
+            config.queue_messages.clone(),
+            config.roles.clone(),
+            config.preferences.clone(),
+        )
+    };
+    let mut recreated_messages = vec![];
+    let mut recreated_count = 0;
+    let mut skipped = vec![];
+    for (channel, message, message_type) in queue_messages {
+        let old_message = ctx.http().get_message(channel, message).await.ok();
+        let message_name = match &message_type {
+            QueueMessageType::Queue => "Queue".to_string(),
+            QueueMessageType::Roles => "Roles".to_string(),
+            QueueMessageType::Register => "Register".to_string(),
+            QueueMessageType::Preference(preference) => format!("{} preference", preference),
+        };
+        let new_message = match &message_type {
+            QueueMessageType::Queue => Some(
+                CreateMessage::new()
+                    .content("## Matchmaking queue")
+                    .components(queue_message_components()),
+            ),
+            QueueMessageType::Roles => Some(
+                CreateMessage::new()
+                    .content("## Role select")
+                    .components(roles_message_components(&roles)),
+            ),
+            // Register buttons aren't stored, so they can only be copied from the old message
+            QueueMessageType::Register => old_message.as_ref().map(|old_message| {
+                let buttons = old_message
+                    .components
+                    .iter()
+                    .flat_map(|row| row.components.iter())
+                    .filter_map(|component| match component {
+                        serenity::ActionRowComponent::Button(serenity::Button {
+                            data: serenity::ButtonKind::NonLink { custom_id, .. },
+                            ..
+                        }) => ButtonData::from_id(custom_id),
+                        _ => None,
+                    })
+                    .map(|button| button.get_button())
+                    .collect_vec();
+                CreateMessage::new()
+                    .content("## Register for queue")
+                    .components(register_button_rows(buttons))
+            }),
+            QueueMessageType::Preference(preference) => {
+                preferences.get(preference).map(|dimension| {
+                    CreateMessage::new()
+                        .content(format!("## {} select", preference))
+                        .components(preference_message_components(preference, dimension))
+                })
+            }
+        };
+        let Some(new_message) = new_message else {
+            skipped.push(format!(
+                "{} message in {} can't be rebuilt",
+                message_name,
+                channel.mention()
+            ));
+            continue;
+        };
+        match channel.send_message(ctx.http(), new_message).await {
+            Ok(new_message) => {
+                if let Some(old_message) = old_message {
+                    old_message.delete(ctx.http()).await.ok();
+                }
+                recreated_messages.push((channel, new_message.id, message_type));
+                recreated_count += 1;
+            }
+            Err(error) => {
+                skipped.push(format!(
+                    "{} message in {} couldn't be posted: {}",
+                    message_name,
+                    channel.mention(),
+                    error
+                ));
+                // The old message is kept if it's still there
+                if old_message.is_some() {
+                    recreated_messages.push((channel, message, message_type));
+                }
+            }
+        }
+    }
+    ctx.data()
+        .configuration
+        .get_mut(&queue_uuid)
+        .unwrap()
+        .queue_messages = recreated_messages;
+    ctx.data()
+        .message_edit_notify
+        .get(&queue_uuid)
+        .unwrap()
+        .notify_one();
+    let mut response = format!("Recreated {} queue messages.", recreated_count);
+    for skipped in skipped {
+        response += format!("\nSkipped: {}", skipped).as_str();
+    }
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Restricts a command to a role instead of its built-in permission
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub async fn set_command_role(
+    ctx: Context<'_>,
+    #[description = "Command name, e.g. force_outcome or configure matchmaking"] command: String,
+    #[description = "Role required to use the command, leave empty to restore the default"]
+    role: Option<serenity::RoleId>,
+) -> Result<(), Error> {
+    let command = command.trim_start_matches('/').split_whitespace().join(" ");
+    let mut commands = ctx.framework().options().commands.iter().collect_vec();
+    let mut known_command = false;
+    while let Some(registered) = commands.pop() {
+        if registered.qualified_name == command {
+            known_command = true;
+            break;
+        }
+        commands.extend(registered.subcommands.iter());
+    }
+    if !known_command {
+        ctx.send(
+            CreateReply::default()
+                .content(format!("Unknown command `{}`.", command))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let queues = {
+        let mut guild_data = ctx.data().guild_data.lock().unwrap();
+        let guild_data = guild_data.entry(ctx.guild_id().unwrap()).or_default();
+        if let Some(role) = role {
+            guild_data.command_roles.insert(command.clone(), role);
+        } else {
+            guild_data.command_roles.remove(&command);
+        }
+        guild_data.queues.clone()
+    };
+    // Command roles apply to the whole guild, so every queue's audit log records them
+    for queue in queues {
+        audit(
+            &ctx,
+            &queue,
+            AuditAction::SetCommandRole,
+            AuditTarget::Command(command.clone()),
+            match role {
+                Some(role) => format!("Restricted to {}", role.mention()),
+                None => "Restored the default permissions".to_string(),
+            },
+        )
+        .await?;
+    }
+    let response = if let Some(role) = role {
+        format!(
+            "`{}` now requires {} instead of its default permissions.",
+            command,
+            role.mention()
+        )
+    } else {
+        format!("`{}` uses its default permissions again.", command)
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new().empty_roles()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Shows how player ratings are distributed in a queue
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn queue_stats_graph(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    const BUCKET_COUNT: usize = 10;
+    const MAX_BAR_WIDTH: usize = 20;
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let ratings = ctx
+        .data()
+        .player_data
+        .get(&queue_uuid)
+        .unwrap()
+        .values()
+        .filter_map(|player| player.rating.map(|rating| rating.rating))
+        .collect_vec();
+    let (Some(min), Some(max)) = (
+        ratings.iter().cloned().reduce(f64::min),
+        ratings.iter().cloned().reduce(f64::max),
+    ) else {
+        ctx.send(
+            CreateReply::default()
+                .content("No rated players in this queue.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let min = min.floor();
+    let bucket_width = ((max.ceil() - min) / BUCKET_COUNT as f64).ceil().max(1.0);
+    let mut buckets = [0usize; BUCKET_COUNT];
+    for rating in ratings.iter() {
+        let bucket = ((rating - min) / bucket_width) as usize;
+        buckets[bucket.min(BUCKET_COUNT - 1)] += 1;
+    }
+    let largest_bucket = *buckets.iter().max().unwrap();
+    let last_bucket = buckets.iter().rposition(|count| *count > 0).unwrap();
+    let chart = buckets[..=last_bucket]
+        .iter()
+        .enumerate()
+        .map(|(idx, count)| {
+            let bucket_start = min + bucket_width * idx as f64;
+            let bar_width = (count * MAX_BAR_WIDTH).div_ceil(largest_bucket);
+            format!(
+                "{:>6.0}-{:<6.0} {:<width$} {}",
+                bucket_start,
+                bucket_start + bucket_width,
+                "█".repeat(bar_width),
+                count,
+                width = MAX_BAR_WIDTH
+            )
+        })
+        .join("\n");
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                serenity::CreateEmbed::new()
+                    .title("Rating Distribution")
+                    .description(format!("```\n{}\n```", chart))
+                    .footer(serenity::CreateEmbedFooter::new(format!(
+                        "{} rated players",
+                        ratings.len()
+                    ))),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Compares activity and population of the server's queues
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn compare_queues(ctx: Context<'_>) -> Result<(), Error> {
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&ctx.guild_id().unwrap())
+        .map(|guild_data| guild_data.queues.clone())
+        .unwrap_or_default();
+    if queues.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .content("No queues available.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let day_start = Utc::now()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc()
+        .timestamp() as u64;
+    let rows = queues
+        .iter()
+        .enumerate()
+        .map(|(queue_idx, queue)| {
+            let name = ctx
+                .data()
+                .configuration
+                .get(queue)
+                .and_then(|config| config.name.clone())
+                .unwrap_or_else(|| format!("Queue {}", queue_idx));
+            let queued = ctx
+                .data()
+                .queued_players
+                .get(queue)
+                .map(|queued| {
+                    queued
+                        .iter()
+                        .filter(|player| !is_fake_player(player))
+                        .count()
+                })
+                .unwrap_or_default();
+            let active_matches = ctx
+                .data()
+                .current_games
+                .get(queue)
+                .map(|games| games.len())
+                .unwrap_or_default();
+            let (players, ratings) = ctx
+                .data()
+                .player_data
+                .get(queue)
+                .map(|player_data| {
+                    let players = player_data
+                        .keys()
+                        .filter(|player| !is_fake_player(player))
+                        .count();
+                    let ratings = player_data
+                        .iter()
+                        .filter(|(player, _)| !is_fake_player(player))
+                        .filter_map(|(_, player)| player.rating.map(|rating| rating.rating))
+                        .collect_vec();
+                    (players, ratings)
+                })
+                .unwrap_or_default();
+            let average_rating = if ratings.is_empty() {
+                "-".to_string()
+            } else {
+                format!("{:.0}", ratings.iter().sum::<f64>() / ratings.len() as f64)
+            };
+            let games_today = {
+                let historical_match_data = ctx.data().historical_match_data.lock().unwrap();
+                let match_data = ctx.data().match_data.lock().unwrap();
+                historical_match_data
+                    .values()
+                    .chain(match_data.values())
+                    .filter(|match_data| match_data.queue == *queue)
+                    .filter(|match_data| {
+                        match_data
+                            .match_start_time
+                            .is_some_and(|start_time| start_time >= day_start)
+                    })
+                    .count()
+            };
+            [
+                name,
+                queued.to_string(),
+                active_matches.to_string(),
+                players.to_string(),
+                average_rating,
+                games_today.to_string(),
+            ]
+        })
+        .collect_vec();
+    let header = [
+        "Queue",
+        "Queued",
+        "Matches",
+        "Players",
+        "Avg rating",
+        "Games today",
+    ]
+    .map(String::from);
+    let widths = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .chain([&header])
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect_vec();
+    let table = [&header]
+        .into_iter()
+        .chain(rows.iter())
+        .map(|row| {
+            row.iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .join("  ")
+        })
+        .join("\n");
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "```\n{}\n```\nGames today count since 00:00 UTC.",
+                table
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Keeps two players from being placed in the same match
+#[poise::command(slash_command, prefix_command, rename = "add")]
+async fn blacklist_pair_add(
+    ctx: Context<'_>,
+    #[description = "Player"] player: UserId,
+    #[description = "Player to keep them apart from"] other_player: UserId,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = if player == other_player {
+        "A player can't be kept apart from themselves.".to_string()
+    } else {
+        ctx.data()
+            .blacklisted_pairs
+            .entry(queue_uuid)
+            .or_default()
+            .insert(blacklisted_pair(player, other_player));
+        audit(
+            &ctx,
+            &queue_uuid,
+            AuditAction::BlacklistPairAdd,
+            AuditTarget::Player(player),
+            format!("Kept apart from {}", other_player.mention()),
+        )
+        .await?;
+        format!(
+            "{} and {} will no longer be matched together.",
+            player.mention(),
+            other_player.mention()
+        )
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new().empty_users()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Allows two players to be matched together again
+#[poise::command(slash_command, prefix_command, rename = "remove")]
+async fn blacklist_pair_remove(
+    ctx: Context<'_>,
+    #[description = "Player"] player: UserId,
+    #[description = "Player they were kept apart from"] other_player: UserId,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let removed = ctx
+        .data()
+        .blacklisted_pairs
+        .get_mut(&queue_uuid)
+        .is_some_and(|mut pairs| pairs.remove(&blacklisted_pair(player, other_player)));
+    let response = if removed {
+        audit(
+            &ctx,
+            &queue_uuid,
+            AuditAction::BlacklistPairRemove,
+            AuditTarget::Player(player),
+            format!("No longer kept apart from {}", other_player.mention()),
+        )
+        .await?;
+        format!(
+            "{} and {} can be matched together again.",
+            player.mention(),
+            other_player.mention()
+        )
+    } else {
+        format!(
+            "{} and {} weren't being kept apart.",
+            player.mention(),
+            other_player.mention()
+        )
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new().empty_users()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Lists players who are kept apart
+#[poise::command(slash_command, prefix_command, rename = "list")]
+async fn blacklist_pair_list(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let pairs = ctx
+        .data()
+        .blacklisted_pairs
+        .get(&queue_uuid)
+        .map(|pairs| {
+            pairs
+                .iter()
+                .map(|(player, other_player)| {
+                    format!("{} and {}", player.mention(), other_player.mention())
+                })
+                .join("\n")
+        })
+        .unwrap_or_default();
+    let response = if pairs.is_empty() {
+        "No players are being kept apart.".to_string()
+    } else {
+        format!("# Blacklisted Pairs\n{}", pairs)
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new().empty_users()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Manages pairs of players who are never matched together
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "BAN_MEMBERS",
+    subcommands("blacklist_pair_add", "blacklist_pair_remove", "blacklist_pair_list")
+)]
+pub async fn blacklist_pair(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
@@ -0,0 +1,200 @@
+//! Read-only HTTP API exposing queue standings as JSON, served when built with the `http-api`
+//! feature and the `API_BIND_ADDRESS` environment variable is set. If `API_TOKEN` is set requests
+//! must send it as a bearer token.
+
+use std::sync::Arc;
+
+use crate::Data;
+
+#[cfg(feature = "http-api")]
+mod server {
+    use std::sync::Arc;
+
+    use axum::{
+        extract::{Path, State},
+        http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+        routing::get,
+        Json, Router,
+    };
+    use itertools::Itertools;
+    use poise::serenity_prelude::UserId;
+    use serde::Serialize;
+
+    use crate::{format_unix_timestamp, Data, MatchResult, QueueUuid};
+
+    #[derive(Clone)]
+    struct ApiState {
+        data: Arc<Data>,
+        token: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct LeaderboardEntry {
+        rank: usize,
+        player: UserId,
+        rating: Option<f64>,
+        wins: u32,
+        losses: u32,
+        draws: u32,
+    }
+
+    #[derive(Serialize)]
+    struct RecentMatch {
+        name: String,
+        team: Option<usize>,
+        map: Option<String>,
+        result: Option<MatchResult>,
+        ended_at: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct PlayerStanding {
+        player: UserId,
+        rating: Option<f64>,
+        wins: u32,
+        losses: u32,
+        draws: u32,
+        games_played: u32,
+        recent_matches: Vec<RecentMatch>,
+    }
+
+    fn authorize(state: &ApiState, headers: &HeaderMap) -> Result<(), StatusCode> {
+        let Some(token) = &state.token else {
+            return Ok(());
+        };
+        let provided = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if provided == Some(token.as_str()) {
+            Ok(())
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+
+    async fn leaderboard(
+        State(state): State<ApiState>,
+        Path(queue_id): Path<uuid::Uuid>,
+        headers: HeaderMap,
+    ) -> Result<Json<Vec<LeaderboardEntry>>, StatusCode> {
+        authorize(&state, &headers)?;
+        let queue_id = QueueUuid(queue_id);
+        let (default_rating, hide_mmr) = {
+            let config = state
+                .data
+                .configuration
+                .get(&queue_id)
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (config.default_player_data.rating, config.hide_mmr)
+        };
+        let player_data = state
+            .data
+            .player_data
+            .get(&queue_id)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let entries = player_data
+            .iter()
+            .map(|(id, data)| (id, data, data.rating.unwrap_or(default_rating)))
+            .sorted_by(|(_, _, rating_a), (_, _, rating_b)| {
+                rating_b.rating.total_cmp(&rating_a.rating)
+            })
+            .enumerate()
+            .map(|(idx, (id, data, rating))| LeaderboardEntry {
+                rank: idx + 1,
+                player: *id,
+                rating: (!hide_mmr).then_some(rating.rating),
+                wins: data.stats.wins,
+                losses: data.stats.losses,
+                draws: data.stats.draws,
+            })
+            .collect();
+        Ok(Json(entries))
+    }
+
+    async fn player_standing(
+        State(state): State<ApiState>,
+        Path((queue_id, player)): Path<(uuid::Uuid, u64)>,
+        headers: HeaderMap,
+    ) -> Result<Json<PlayerStanding>, StatusCode> {
+        authorize(&state, &headers)?;
+        let queue_id = QueueUuid(queue_id);
+        let player = UserId::new(player);
+        let (default_rating, hide_mmr) = {
+            let config = state
+                .data
+                .configuration
+                .get(&queue_id)
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (config.default_player_data.rating, config.hide_mmr)
+        };
+        let player_data = state
+            .data
+            .player_data
+            .get(&queue_id)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .get(&player)
+            .cloned()
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let recent_matches = {
+            let historical_match_data = state.data.historical_match_data.lock().unwrap();
+            player_data
+                .game_history
+                .iter()
+                .rev()
+                .take(10)
+                .filter_map(|match_id| historical_match_data.get(match_id))
+                .map(|match_data| RecentMatch {
+                    name: match_data.name.clone(),
+                    team: match_data
+                        .members
+                        .iter()
+                        .position(|team| team.contains(&player)),
+                    map: match_data.map.clone(),
+                    result: match_data.final_result.clone(),
+                    ended_at: match_data.match_end_time.and_then(format_unix_timestamp),
+                })
+                .collect()
+        };
+        Ok(Json(PlayerStanding {
+            player,
+            rating: (!hide_mmr).then_some(player_data.rating.unwrap_or(default_rating).rating),
+            wins: player_data.stats.wins,
+            losses: player_data.stats.losses,
+            draws: player_data.stats.draws,
+            games_played: player_data.stats.games_played(),
+            recent_matches,
+        }))
+    }
+
+    pub async fn serve(data: Arc<Data>, address: String) -> std::io::Result<()> {
+        let state = ApiState {
+            data,
+            token: std::env::var("API_TOKEN").ok(),
+        };
+        let app = Router::new()
+            .route("/queues/:queue_id/leaderboard", get(leaderboard))
+            .route("/queues/:queue_id/players/:player_id", get(player_standing))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind(&address).await?;
+        println!("Serving standings API on {}", address);
+        axum::serve(listener, app).await
+    }
+}
+
+/// Starts the standings API if `API_BIND_ADDRESS` is set
+pub fn spawn_server(data: Arc<Data>) {
+    #[cfg(feature = "http-api")]
+    {
+        let Ok(address) = std::env::var("API_BIND_ADDRESS") else {
+            return;
+        };
+        tokio::spawn(async move {
+            if let Err(err) = server::serve(data, address).await {
+                eprintln!("Standings API failed: {}", err);
+            }
+        });
+    }
+    #[cfg(not(feature = "http-api"))]
+    let _ = data;
+}
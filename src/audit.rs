@@ -0,0 +1,136 @@
+//! Audit log of moderation actions, posted to the queue's audit channel and appended to
+//! `audit.jsonl`
+
+use std::{fs::OpenOptions, io::Write};
+
+use chrono::Utc;
+use poise::serenity_prelude::{CreateAllowedMentions, CreateMessage, Mentionable, UserId};
+use serde::Serialize;
+
+use crate::{format_timestamp, Context, Error, QueueUuid};
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Ban,
+    Unban,
+    ApproveLeaverAppeal,
+    SetTeamChannelsPrivate,
+    FixTeamVoice,
+    EditMatchMap,
+    ForceResult,
+    ForceMatch,
+    BlacklistPairAdd,
+    BlacklistPairRemove,
+    SetCommandRole,
+    PruneHistory,
+    QueueMany,
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                AuditAction::Ban => "ban",
+                AuditAction::Unban => "unban",
+                AuditAction::ApproveLeaverAppeal => "approve_leaver_appeal",
+                AuditAction::SetTeamChannelsPrivate => "set_team_channels_private",
+                AuditAction::FixTeamVoice => "fix_team_voice",
+                AuditAction::EditMatchMap => "edit_match_map",
+                AuditAction::ForceResult => "force_result",
+                AuditAction::ForceMatch => "force_match",
+                AuditAction::BlacklistPairAdd => "blacklist_pair_add",
+                AuditAction::BlacklistPairRemove => "blacklist_pair_remove",
+                AuditAction::SetCommandRole => "set_command_role",
+                AuditAction::PruneHistory => "prune_history",
+                AuditAction::QueueMany => "queue_many",
+            }
+        )
+    }
+}
+
+/// What a moderation action was taken on
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditTarget {
+    Player(UserId),
+    /// A match, by its name
+    Match(String),
+    Queue,
+    /// A command, by its qualified name
+    Command(String),
+}
+
+impl std::fmt::Display for AuditTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditTarget::Player(player) => write!(f, "{}", player.mention()),
+            AuditTarget::Match(name) => write!(f, "match {}", name),
+            AuditTarget::Queue => write!(f, "queue"),
+            AuditTarget::Command(name) => write!(f, "command `{}`", name),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuditEntry {
+    action: AuditAction,
+    actor: UserId,
+    target: AuditTarget,
+    queue: QueueUuid,
+    details: String,
+    timestamp: String,
+}
+
+/// Records a moderation action by the command's author
+pub async fn audit(
+    ctx: &Context<'_>,
+    queue: &QueueUuid,
+    action: AuditAction,
+    target: AuditTarget,
+    details: String,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let entry = AuditEntry {
+        action,
+        actor: ctx.author().id,
+        target,
+        queue: *queue,
+        details,
+        timestamp: format_timestamp(now),
+    };
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open("audit.jsonl")
+        .unwrap();
+    if let Err(e) = writeln!(file, "{}", serde_json::to_string(&entry).unwrap()) {
+        eprintln!("Couldn't write to file: {}", e);
+    }
+    let (audit_channel, queue_name) = {
+        let config = ctx.data().configuration.get(queue).unwrap();
+        (config.audit_channel, config.name.clone())
+    };
+    let Some(audit_channel) = audit_channel else {
+        return Ok(());
+    };
+    audit_channel
+        .send_message(
+            ctx.http(),
+            CreateMessage::new()
+                .content(format!(
+                    "`{}` by {} on {} in {} <t:{}:f>\n{}",
+                    entry.action,
+                    entry.actor.mention(),
+                    entry.target,
+                    queue_name.unwrap_or_else(|| format!("queue `{}`", queue.0)),
+                    now.timestamp(),
+                    entry.details
+                ))
+                .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
+        )
+        .await?;
+    Ok(())
+}
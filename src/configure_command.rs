@@ -1,18 +1,17 @@
-use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
-};
+use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
 use poise::{
     serenity_prelude::{self as serenity, Mentionable},
     CreateReply,
 };
-use tokio::sync::Notify;
 
-use crate::{Context, Error, QueueConfiguration, QueueUuid, RoleConfiguration};
+use crate::{
+    locale::Locale, spawn_queue_workers, Context, Error, PreferenceDimension, QueueConfiguration,
+    QueueUuid, RoleConfiguration, DEFAULT_QUEUE_MESSAGE_BODY, DEFAULT_QUEUE_MESSAGE_HEADER,
+};
 
-fn get_queue_uuid(ctx: &Context, queue_idx: Option<u32>) -> Result<QueueUuid, String> {
+pub fn get_queue_uuid(ctx: &Context, queue_idx: Option<u32>) -> Result<QueueUuid, String> {
     let queues = ctx
         .data()
         .guild_data
@@ -40,13 +39,13 @@ fn get_queue_uuid(ctx: &Context, queue_idx: Option<u32>) -> Result<QueueUuid, St
 }
 
 macro_rules! configure_server_parameter {
-    ($func_name:ident, $prop:ident, $prop_type:ty, $rename:expr, $name:expr, $doc:expr$(, $limits:meta)?) => {
+    ($func_name:ident, $($prop:ident).+, $prop_type:ty, $rename:expr, $name:expr, $doc:expr$(, $limits:meta)*) => {
 #[doc=$doc]
 #[poise::command(slash_command, rename=$rename)]
 pub async fn $func_name(
     ctx: Context<'_>,
     #[description = "New value"]
-    $(#[$limits])?
+    $(#[$limits])*
     new_value: Option<$prop_type>,
     #[description = "Queue index"]
     #[min = 0]
@@ -62,11 +61,11 @@ pub async fn $func_name(
     };
     let response = if let Some(new_value) = new_value {
         let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
-        data_lock.$prop = new_value;
+        data_lock.$($prop).+ = new_value;
         format!("{} set to {}", $name, new_value)
     } else {
         let data_lock = ctx.data().configuration.get(&queue_uuid).unwrap();
-        format!("{} is currently {}", $name, data_lock.$prop)
+        format!("{} is currently {}", $name, data_lock.$($prop).+)
     };
     ctx.send(CreateReply::default().content(response).ephemeral(true))
         .await?;
@@ -113,6 +112,48 @@ impl ConfigurationModifiers {
         "Displays or sets time maps for the vote (0 for no timeout)",
         min = 0
     );
+    configure_server_parameter!(
+        configure_result_vote_time,
+        result_vote_time,
+        u32,
+        "result_vote_time",
+        "Result vote time",
+        "Displays or sets seconds before a match resolves to its most voted result (0 for no timeout)",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_min_match_duration,
+        min_match_duration,
+        u32,
+        "min_match_duration",
+        "Minimum match duration",
+        "Displays or sets seconds after a match starts before result votes can resolve it (0 for none)",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_require_result_proof,
+        require_result_proof,
+        bool,
+        "require_result_proof",
+        "Require result proof?",
+        "Displays or sets whether a screenshot or link must be posted before a vote resolves a match"
+    );
+    configure_server_parameter!(
+        configure_result_proof_timeout_minutes,
+        result_proof_timeout_minutes,
+        u32,
+        "result_proof_timeout_minutes",
+        "Result proof timeout minutes",
+        "Displays or sets minutes a result waits for proof before admins are alerted (0 to never)"
+    );
+    configure_server_parameter!(
+        configure_locale,
+        locale,
+        Locale,
+        "locale",
+        "Locale",
+        "Displays or sets the language of messages shown to players"
+    );
     configure_server_parameter!(
         configure_maximum_queue_cost,
         maximum_queue_cost,
@@ -121,6 +162,14 @@ impl ConfigurationModifiers {
         "Max queue cost",
         "Displays or sets maximum cost it will allow for a match to be created"
     );
+    configure_server_parameter!(
+        configure_normalize_queue_cost,
+        normalize_queue_cost,
+        bool,
+        "normalize_queue_cost",
+        "Normalize queue cost?",
+        "Displays or sets whether match costs are divided by player count, making max cost per player"
+    );
     configure_server_parameter!(
         configure_incorrect_roles_cost,
         incorrect_roles_cost,
@@ -138,6 +187,15 @@ impl ConfigurationModifiers {
         "Should log match chats?",
         "Displays or sets whether to log match chats"
     );
+    configure_server_parameter!(
+        configure_history_retention_days,
+        history_retention_days,
+        u32,
+        "history_retention_days",
+        "History retention days",
+        "Displays or sets days finished matches are kept in the match history (0 to keep forever)",
+        min = 0
+    );
     configure_server_parameter!(
         configure_prevent_recent_maps,
         prevent_recent_maps,
@@ -146,6 +204,489 @@ impl ConfigurationModifiers {
         "Prevent recent maps?",
         "Displays or sets whether to prevent recent maps from being played"
     );
+    configure_server_parameter!(
+        configure_show_rosters_in_result_vote,
+        show_rosters_in_result_vote,
+        bool,
+        "show_rosters_in_result_vote",
+        "Show rosters in result vote?",
+        "Displays or sets whether to list team rosters on the result vote message"
+    );
+    configure_server_parameter!(
+        configure_post_match_summary,
+        post_match_summary,
+        bool,
+        "post_match_summary",
+        "Post match summary?",
+        "Displays or sets whether match results are posted to the post match channel"
+    );
+    configure_server_parameter!(
+        configure_keep_match_channels,
+        keep_match_channels,
+        bool,
+        "keep_match_channels",
+        "Keep match channels?",
+        "Displays or sets whether the match text channel is kept after resolution instead of deleted"
+    );
+    configure_server_parameter!(
+        configure_clean_up_match_messages,
+        clean_up_match_messages,
+        bool,
+        "clean_up_match_messages",
+        "Clean up match messages?",
+        "Displays or sets whether vote messages are deleted from kept match channels on resolution"
+    );
+    configure_server_parameter!(
+        configure_lock_kept_match_channels,
+        lock_kept_match_channels,
+        bool,
+        "lock_kept_match_channels",
+        "Lock kept match channels?",
+        "Displays or sets whether kept match channels become read only after resolution"
+    );
+    configure_server_parameter!(
+        configure_map_vote_select_menu,
+        map_vote_select_menu,
+        bool,
+        "map_vote_select_menu",
+        "Map vote select menu?",
+        "Displays or sets whether map votes use a select menu instead of buttons"
+    );
+    configure_server_parameter!(
+        configure_max_concurrent_matches,
+        max_concurrent_matches,
+        u32,
+        "max_concurrent_matches",
+        "Max concurrent matches",
+        "Displays or sets how many matches can run at once, 0 for no limit"
+    );
+    configure_server_parameter!(
+        configure_series_length,
+        series_length,
+        u32,
+        "series_length",
+        "Series length",
+        "Displays or sets how many games a best-of series match has, 1 for single games",
+        min = 1
+    );
+    configure_server_parameter!(
+        configure_rate_series_games,
+        rate_series_games,
+        bool,
+        "rate_series_games",
+        "Rate series games?",
+        "Displays or sets whether each series game is rated instead of only the series result"
+    );
+    configure_server_parameter!(
+        configure_draft_mode,
+        draft_mode,
+        bool,
+        "draft_mode",
+        "Captains draft?",
+        "Displays or sets whether captains pick the teams instead of automatic team balancing"
+    );
+    configure_server_parameter!(
+        configure_mvp_vote,
+        mvp_vote,
+        bool,
+        "mvp_vote",
+        "MVP vote?",
+        "Displays or sets whether each team votes for an MVP during the result vote"
+    );
+    configure_server_parameter!(
+        configure_shot_callers,
+        shot_callers,
+        bool,
+        "shot_callers",
+        "Shot-callers?",
+        "Displays or sets whether the highest rated player of each team is marked as its shot-caller"
+    );
+    configure_server_parameter!(
+        configure_afk_check_in_channel,
+        afk_check_in_channel,
+        bool,
+        "afk_check_in_channel",
+        "Afk checks in channel?",
+        "Displays or sets whether afk checks are posted in the queue channel instead of sent as DMs"
+    );
+    configure_server_parameter!(
+        configure_leaver_verification_time,
+        leaver_verification_time,
+        u32,
+        "leaver_verification_time",
+        "Seconds to dispute a leaver report",
+        "Displays or sets how long a reported player has to dispute being marked as a leaver"
+    );
+    configure_server_parameter!(
+        configure_leaver_decay_days,
+        leaver_decay_days,
+        u32,
+        "leaver_decay_days",
+        "Days before a leave is forgiven",
+        "Displays or sets how many days without leaving remove one leave from a player, 0 to never"
+    );
+    configure_server_parameter!(
+        configure_min_account_age_days,
+        min_account_age_days,
+        u32,
+        "min_account_age_days",
+        "Minimum account age in days",
+        "Displays or sets how old a Discord account must be to queue, 0 to allow any account"
+    );
+    configure_server_parameter!(
+        configure_min_guild_member_days,
+        min_guild_member_days,
+        u32,
+        "min_guild_member_days",
+        "Minimum days in the server",
+        "Displays or sets how long a player must have been in the server to queue, 0 to allow anyone"
+    );
+    configure_server_parameter!(
+        configure_leaver_reports_required,
+        leaver_reports_required,
+        u32,
+        "leaver_reports_required",
+        "Reports needed to check a leaver",
+        "Displays or sets how many match members must report a player before they're checked as a leaver",
+        min = 1
+    );
+    configure_server_parameter!(
+        configure_enforce_leaver_disconnect,
+        enforce_leaver_disconnect,
+        bool,
+        "enforce_leaver_disconnect",
+        "Disconnect leavers?",
+        "Displays or sets whether confirmed leavers are disconnected from voice or only recorded"
+    );
+    configure_server_parameter!(
+        configure_leaver_surrender,
+        leaver_surrender,
+        bool,
+        "leaver_surrender",
+        "Leaver surrender?",
+        "Displays or sets whether a team with a confirmed leaver can surrender"
+    );
+    configure_server_parameter!(
+        configure_leaver_surrender_delay_minutes,
+        leaver_surrender_delay_minutes,
+        u32,
+        "leaver_surrender_delay_minutes",
+        "Leaver surrender delay",
+        "Displays or sets minutes after a leaver is confirmed before their team can surrender",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_surrender_votes_required,
+        surrender_votes_required,
+        u32,
+        "surrender_votes_required",
+        "Surrender votes required",
+        "Displays or sets how many players left on a team must vote to surrender",
+        min = 1
+    );
+    configure_server_parameter!(
+        configure_reconnect_grace_seconds,
+        reconnect_grace_seconds,
+        u32,
+        "reconnect_grace_seconds",
+        "Reconnect grace seconds",
+        "Displays or sets how long after the bot reconnects voice disconnects don't remove players"
+    );
+    configure_server_parameter!(
+        configure_count_cancels,
+        count_cancels,
+        bool,
+        "count_cancels",
+        "Count cancels?",
+        "Displays or sets whether cancelled matches count against the players who voted to cancel"
+    );
+    configure_server_parameter!(
+        configure_cancel_alert_threshold,
+        cancel_alert_threshold,
+        u32,
+        "cancel_alert_threshold",
+        "Cancel alert threshold",
+        "Displays or sets the cancel count that alerts the audit channel (0 to never alert)",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_cancel_vote_cooldown_minutes,
+        cancel_vote_cooldown_minutes,
+        u32,
+        "cancel_vote_cooldown_minutes",
+        "Cancel vote cooldown",
+        "Displays or sets minutes before a player that voted to cancel a match can vote to cancel again",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_avoided_player_cost,
+        avoided_player_cost,
+        f32,
+        "avoided_player_cost",
+        "Cost of matching avoided players",
+        "Displays or sets the cost for each player in a lobby with someone they avoid",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_party_coordination_mmr_bonus,
+        party_coordination_mmr_bonus,
+        f32,
+        "party_coordination_mmr_bonus",
+        "Party coordination mmr bonus",
+        "Displays or sets the rating added to party members per other party member when balancing",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_placement_games,
+        placement_games,
+        u32,
+        "placement_games",
+        "Placement games",
+        "Displays or sets games before a player's rating counts towards lobby rating ranges (0 to disable)",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_max_avoided_players,
+        max_avoided_players,
+        u32,
+        "max_avoided_players",
+        "Players each player can avoid",
+        "Displays or sets how many players each player can avoid, 0 to disable avoiding"
+    );
+    configure_server_parameter!(
+        configure_allow_multi_queue,
+        allow_multi_queue,
+        bool,
+        "allow_multi_queue",
+        "Allow queueing elsewhere too?",
+        "Displays or sets whether players in this queue can also be queued in other queues"
+    );
+    configure_server_parameter!(
+        configure_hide_mmr,
+        hide_mmr,
+        bool,
+        "hide_mmr",
+        "Hide mmr?",
+        "Displays or sets whether to hide mmr numbers from non-admins"
+    );
+    configure_server_parameter!(
+        configure_queue_notify_offset,
+        queue_notify_offset,
+        u32,
+        "queue_notify_offset",
+        "Players missing when notifying",
+        "Displays or sets how many players short of a match /queue_notify subscribers are messaged",
+        min = 1
+    );
+    configure_server_parameter!(
+        configure_allow_unrated_players,
+        allow_unrated_players,
+        bool,
+        "allow_unrated_players",
+        "Allow unrated players?",
+        "Displays or sets whether players without a rating may join when mmr bounds are set"
+    );
+    configure_server_parameter!(
+        configure_split_oversized_parties,
+        split_oversized_parties,
+        bool,
+        "split_oversized_parties",
+        "Split oversized parties?",
+        "Displays or sets whether parties too large for any team's open slots are split across teams"
+    );
+    configure_server_parameter!(
+        configure_stale_party_hours,
+        stale_party_hours,
+        u32,
+        "stale_party_hours",
+        "Hours before idle parties disband",
+        "Displays or sets how long a party can be inactive before it is disbanded, 0 to never disband"
+    );
+    configure_server_parameter!(
+        configure_party_invite_expiry_minutes,
+        party_invite_expiry_minutes,
+        u32,
+        "party_invite_expiry_minutes",
+        "Minutes before party invites expire",
+        "Displays or sets how long party invites stay valid without other party activity"
+    );
+    configure_server_parameter!(
+        configure_min_start_players,
+        min_start_players,
+        u32,
+        "min_start_players",
+        "Minimum players to start",
+        "Displays or sets the smallest match that can start after waiting, 0 to only start full matches"
+    );
+    configure_server_parameter!(
+        configure_min_start_wait_minutes,
+        min_start_wait_minutes,
+        u32,
+        "min_start_wait_minutes",
+        "Minutes before short matches",
+        "Displays or sets how long a player must wait before a match below full size can start"
+    );
+    configure_server_parameter!(
+        configure_requeue_winners_together,
+        requeue_winners_together,
+        bool,
+        "requeue_winners_together",
+        "Requeue winning teams together?",
+        "Displays or sets whether winning teams that opted in are requeued together as a party"
+    );
+    configure_server_parameter!(
+        configure_max_streak_requeues,
+        max_streak_requeues,
+        u32,
+        "max_streak_requeues",
+        "Wins a team is kept together for",
+        "Displays or sets how many consecutive wins a requeued team is kept together for"
+    );
+    configure_server_parameter!(
+        configure_balance_teams,
+        balance_teams,
+        bool,
+        "balance_teams",
+        "Balance teams?",
+        "Displays or sets whether players are swapped between teams after selection to lower the cost"
+    );
+    configure_server_parameter!(
+        configure_wait_vs_fairness,
+        wait_vs_fairness,
+        f32,
+        "wait_vs_fairness",
+        "Wait vs fairness",
+        "Displays or sets the bias towards fast (1) or fair (0) matches",
+        min = 0,
+        max = 1
+    );
+    configure_server_parameter!(
+        configure_max_map_rerolls,
+        max_map_rerolls,
+        u32,
+        "max_map_rerolls",
+        "Max map rerolls",
+        "Displays or sets how many times a majority can reroll a randomly picked map"
+    );
+    configure_server_parameter!(
+        configure_dm_ban_notifications,
+        dm_ban_notifications,
+        bool,
+        "dm_ban_notifications",
+        "DM ban notifications?",
+        "Displays or sets whether players are messaged when banned or unbanned (never for shadow bans)"
+    );
+    configure_server_parameter!(
+        configure_unregistered_mix_cost,
+        unregistered_mix_cost,
+        f32,
+        "unregistered_mix_cost",
+        "Unregistered mix cost",
+        "Displays or sets the cost per unregistered player matched with registered players",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_matchmaking_max_retries,
+        matchmaking_max_retries,
+        u32,
+        "matchmaking_max_retries",
+        "Matchmaking max retries",
+        "Displays or sets how often an unmatchable queue is retried before pausing until it changes"
+    );
+    configure_server_parameter!(
+        configure_default_rating,
+        default_player_data.rating.rating,
+        f64,
+        "default_rating",
+        "Default rating",
+        "Displays or sets the rating new players start with"
+    );
+    configure_server_parameter!(
+        configure_default_uncertainty,
+        default_player_data.rating.uncertainty,
+        f64,
+        "default_uncertainty",
+        "Default uncertainty",
+        "Displays or sets the rating uncertainty new players start with",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_default_cost_per_avg_mmr_differential,
+        default_player_data
+            .player_queueing_config
+            .cost_per_avg_mmr_differential,
+        f32,
+        "cost_per_avg_mmr_differential",
+        "Default average mmr difference cost",
+        "Displays or sets the default cost for the difference in average mmr between teams",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_default_acceptable_mmr_differential,
+        default_player_data
+            .player_queueing_config
+            .acceptable_mmr_differential,
+        f32,
+        "acceptable_mmr_differential",
+        "Default acceptable average mmr difference",
+        "Displays or sets the default acceptable difference in average mmr between teams",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_default_cost_per_mmr_std_differential,
+        default_player_data
+            .player_queueing_config
+            .cost_per_mmr_std_differential,
+        f32,
+        "cost_per_mmr_std_differential",
+        "Default mmr variation difference cost",
+        "Displays or sets the default cost for the difference in mmr std between teams",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_default_acceptable_mmr_std_differential,
+        default_player_data
+            .player_queueing_config
+            .acceptable_mmr_std_differential,
+        f32,
+        "acceptable_mmr_std_differential",
+        "Default acceptable mmr variation difference",
+        "Displays or sets the default acceptable difference in mmr std between teams",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_default_cost_per_mmr_range,
+        default_player_data
+            .player_queueing_config
+            .cost_per_mmr_range,
+        f32,
+        "cost_per_mmr_range",
+        "Default mmr range cost",
+        "Displays or sets the default cost for the mmr range of a lobby",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_default_acceptable_mmr_range,
+        default_player_data
+            .player_queueing_config
+            .acceptable_mmr_range,
+        f32,
+        "acceptable_mmr_range",
+        "Default acceptable mmr range",
+        "Displays or sets the default acceptable mmr range of a lobby",
+        min = 0
+    );
+    configure_server_parameter!(
+        configure_default_new_lobby_host_cost,
+        default_player_data
+            .player_queueing_config
+            .new_lobby_host_cost,
+        f32,
+        "new_lobby_host_cost",
+        "Default new lobby host cost",
+        "Displays or sets the default cost for getting a different lobby host",
+        min = 0
+    );
 }
 
 /// Displays or sets queue category
@@ -264,17 +805,17 @@ async fn configure_maps(
         let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
         if let Some(value) = map {
             if remove {
-                if data_lock.maps.remove(&value) {
+                if data_lock.maps.remove(&value).is_some() {
                     format!("{} removed as map", value)
                 } else {
                     format!("{} wasn't a map", value)
                 }
             } else {
-                data_lock.maps.insert(value.clone());
+                data_lock.maps.entry(value.clone()).or_default();
                 format!("{} added as map", value)
             }
         } else {
-            format!("Maps are {}", data_lock.maps.iter().join(", "))
+            format!("Maps are {}", data_lock.maps.keys().join(", "))
         }
     };
     ctx.send(CreateReply::default().content(response).ephemeral(true))
@@ -466,11 +1007,14 @@ async fn configure_audit_channel(
     Ok(())
 }
 
-/// Sets the role for registered players
-#[poise::command(slash_command, prefix_command, rename = "register_role")]
-async fn configure_register_role(
+/// Sets the channel to publicly announce new matches in
+#[poise::command(slash_command, prefix_command, rename = "announce_channel")]
+async fn configure_announce_channel(
     ctx: Context<'_>,
-    #[description = "Register role"] new_value: Option<serenity::RoleId>,
+    #[flag] clear: bool,
+    #[description = "Announce channel"]
+    #[channel_types("Text")]
+    new_value: Option<serenity::Channel>,
     #[description = "Queue index"]
     #[min = 0]
     queue_idx: Option<u32>,
@@ -483,18 +1027,19 @@ async fn configure_register_role(
             return Ok(());
         }
     };
-    let response = if let Some(new_value) = new_value {
+    let response = {
         let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
-        data_lock.register_role = Some(new_value);
-        format!("Register role changed to {}", new_value.to_string())
-    } else {
-        let data_lock = ctx.data().configuration.get(&queue_uuid).unwrap();
+        if clear {
+            data_lock.announce_channel = None;
+        }
+        if let Some(new_value) = new_value {
+            data_lock.announce_channel = Some(new_value.id());
+        }
         format!(
-            "Register role is {}",
+            "Announce channel is {}",
             data_lock
-                .register_role
-                .as_ref()
-                .map(|c| format!("{}", c.mention()))
+                .announce_channel
+                .map(|c| c.mention().to_string())
                 .unwrap_or("not set".to_string())
         )
     };
@@ -503,12 +1048,12 @@ async fn configure_register_role(
     Ok(())
 }
 
-/// Configures roles that can see match channels of matches their not in
-#[poise::command(slash_command, prefix_command, rename = "visability_override_roles")]
-async fn configure_visability_override_roles(
+/// Configures the names teams are shown with, in team order
+#[poise::command(slash_command, prefix_command, rename = "team_names")]
+async fn configure_team_names(
     ctx: Context<'_>,
     #[flag] remove: bool,
-    #[description = "Override role"] channel: Option<serenity::RoleId>,
+    #[description = "Team name, added after the current ones"] name: Option<String>,
     #[description = "Queue index"]
     #[min = 0]
     queue_idx: Option<u32>,
@@ -523,26 +1068,27 @@ async fn configure_visability_override_roles(
     };
     let response = {
         let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
-        if let Some(value) = channel {
+        if let Some(value) = name {
             if remove {
-                if data_lock.visability_override_roles.remove(&value) {
-                    format!("{} removed as override role", value)
+                let team_count = data_lock.team_names.len();
+                data_lock.team_names.retain(|team_name| *team_name != value);
+                if data_lock.team_names.len() < team_count {
+                    format!("{} removed as team name", value)
                 } else {
-                    format!("{} wasn't a override role", value)
+                    format!("{} wasn't a team name", value)
                 }
             } else {
-                data_lock.visability_override_roles.insert(value.clone());
-                format!("{} added as override role", value)
+                data_lock.team_names.push(value.clone());
+                format!(
+                    "{} added as the name of team {}",
+                    value,
+                    data_lock.team_names.len()
+                )
             }
+        } else if data_lock.team_names.is_empty() {
+            "Teams are numbered".to_string()
         } else {
-            format!(
-                "Override roles are {}",
-                data_lock
-                    .visability_override_roles
-                    .iter()
-                    .map(|c| c.mention())
-                    .join(", ")
-            )
+            format!("Team names are {}", data_lock.team_names.iter().join(", "))
         }
     };
     ctx.send(CreateReply::default().content(response).ephemeral(true))
@@ -550,35 +1096,807 @@ async fn configure_visability_override_roles(
     Ok(())
 }
 
-/// Displays your or another user's account creation date
-#[poise::command(
-    slash_command,
-    prefix_command,
-    default_member_permissions = "MANAGE_CHANNELS",
-    subcommands(
-        "ConfigurationModifiers::configure_team_size",
-        "ConfigurationModifiers::configure_team_count",
-        "configure_queue_category",
-        "configure_queue_channels",
+/// Sets the role for registered players
+#[poise::command(slash_command, prefix_command, rename = "register_role")]
+async fn configure_register_role(
+    ctx: Context<'_>,
+    #[description = "Register role"] new_value: Option<serenity::RoleId>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = if let Some(new_value) = new_value {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        data_lock.register_role = Some(new_value);
+        format!("Register role changed to {}", new_value.to_string())
+    } else {
+        let data_lock = ctx.data().configuration.get(&queue_uuid).unwrap();
+        format!(
+            "Register role is {}",
+            data_lock
+                .register_role
+                .as_ref()
+                .map(|c| format!("{}", c.mention()))
+                .unwrap_or("not set".to_string())
+        )
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sets the mmr range players must be within to join the queue
+#[poise::command(slash_command, prefix_command, rename = "mmr_bounds")]
+async fn configure_mmr_bounds(
+    ctx: Context<'_>,
+    #[flag] clear: bool,
+    #[description = "Minimum mmr"] floor: Option<f64>,
+    #[description = "Maximum mmr"] ceiling: Option<f64>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let (new_floor, new_ceiling) = {
+        let data_lock = ctx.data().configuration.get(&queue_uuid).unwrap();
+        if clear {
+            (floor, ceiling)
+        } else {
+            (
+                floor.or(data_lock.mmr_floor),
+                ceiling.or(data_lock.mmr_ceiling),
+            )
+        }
+    };
+    if let (Some(new_floor), Some(new_ceiling)) = (new_floor, new_ceiling) {
+        if new_floor > new_ceiling {
+            ctx.send(
+                CreateReply::default()
+                    .content(format!(
+                        "Mmr floor {} can't be above mmr ceiling {}",
+                        new_floor, new_ceiling
+                    ))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+    let response = {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        data_lock.mmr_floor = new_floor;
+        data_lock.mmr_ceiling = new_ceiling;
+        format!(
+            "Mmr floor is {} and mmr ceiling is {}",
+            data_lock
+                .mmr_floor
+                .map(|floor| floor.to_string())
+                .unwrap_or("not set".to_string()),
+            data_lock
+                .mmr_ceiling
+                .map(|ceiling| ceiling.to_string())
+                .unwrap_or("not set".to_string())
+        )
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sets the url and signing secret for match webhooks
+#[poise::command(slash_command, prefix_command, rename = "webhook")]
+async fn configure_webhook(
+    ctx: Context<'_>,
+    #[flag] clear: bool,
+    #[description = "Url to post match events to"] url: Option<String>,
+    #[description = "Secret used to sign webhook payloads"] secret: Option<String>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        if clear {
+            data_lock.webhook_url = None;
+            data_lock.webhook_secret = None;
+        }
+        if let Some(url) = url {
+            data_lock.webhook_url = Some(url);
+        }
+        if let Some(secret) = secret {
+            data_lock.webhook_secret = Some(secret);
+        }
+        format!(
+            "Webhook url is {} and payloads are {}",
+            data_lock
+                .webhook_url
+                .clone()
+                .unwrap_or("not set".to_string()),
+            if data_lock.webhook_secret.is_some() {
+                "signed"
+            } else {
+                "not signed"
+            }
+        )
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sets the largest party allowed, defaulting to the team size
+#[poise::command(slash_command, prefix_command, rename = "max_party_size")]
+async fn configure_max_party_size(
+    ctx: Context<'_>,
+    #[flag] reset: bool,
+    #[description = "Max party size"]
+    #[min = 1]
+    new_value: Option<u32>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        if reset {
+            data_lock.max_party_size = None;
+        }
+        if let Some(new_value) = new_value {
+            data_lock.max_party_size = Some(new_value);
+        }
+        match data_lock.max_party_size {
+            Some(max_party_size) => format!("Max party size is {}", max_party_size),
+            None => format!("Max party size is the team size ({})", data_lock.team_size),
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sets a role exempt from the minimum account age and membership duration
+#[poise::command(slash_command, prefix_command, rename = "account_age_exempt_role")]
+async fn configure_account_age_exempt_role(
+    ctx: Context<'_>,
+    #[flag] clear: bool,
+    #[description = "Exempt role"] new_value: Option<serenity::RoleId>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        if clear {
+            data_lock.account_age_exempt_role = None;
+        }
+        if let Some(new_value) = new_value {
+            data_lock.account_age_exempt_role = Some(new_value);
+        }
+        format!(
+            "Account age exempt role is {}",
+            data_lock
+                .account_age_exempt_role
+                .map(|role| role.mention().to_string())
+                .unwrap_or("not set".to_string())
+        )
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(serenity::CreateAllowedMentions::new().empty_roles()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Adds or replaces a game category with its ordered list of roles
+#[poise::command(slash_command, prefix_command, rename = "add")]
+async fn configure_game_category_add(
+    ctx: Context<'_>,
+    #[description = "Category name"] name: String,
+    #[description = "Roles in order, as mentions or ids separated by spaces"] roles: String,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let guild_roles = ctx.guild_id().unwrap().roles(ctx).await?;
+    let Ok(category_roles) = roles
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|role| !role.is_empty())
+        .map(|role| {
+            role.trim_start_matches("<@&")
+                .trim_end_matches('>')
+                .parse::<serenity::RoleId>()
+        })
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        ctx.send(
+            CreateReply::default()
+                .content("Invalid roles")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let response = if category_roles.is_empty() {
+        "A game category needs at least one role".to_string()
+    } else if let Some(missing_role) = category_roles
+        .iter()
+        .find(|role| !guild_roles.contains_key(role))
+    {
+        format!("{} is not a role in this server", missing_role)
+    } else {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        let response = format!(
+            "Game category {} set to {}",
+            name,
+            category_roles.iter().map(|role| role.mention()).join(", ")
+        );
+        data_lock.game_categories.insert(name, category_roles);
+        response
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Removes a game category
+#[poise::command(slash_command, prefix_command, rename = "remove")]
+async fn configure_game_category_remove(
+    ctx: Context<'_>,
+    #[description = "Category name"] name: String,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        if data_lock.game_categories.remove(&name).is_some() {
+            format!("Game category {} removed", name)
+        } else {
+            format!("{} wasn't a game category", name)
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Lists game categories and their roles
+#[poise::command(slash_command, prefix_command, rename = "list")]
+async fn configure_game_category_list(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let data_lock = ctx.data().configuration.get(&queue_uuid).unwrap();
+        format!(
+            "Game categories:\n{}",
+            data_lock
+                .game_categories
+                .iter()
+                .map(|(name, roles)| format!(
+                    "* {}: {}",
+                    name,
+                    roles.iter().map(|role| role.mention()).join(", ")
+                ))
+                .join("\n")
+        )
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Configures game categories such as regions or platforms
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "game_category",
+    subcommands(
+        "configure_game_category_add",
+        "configure_game_category_remove",
+        "configure_game_category_list",
+    )
+)]
+async fn configure_game_category(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Adds or replaces a matchmaking preference such as region or platform
+#[poise::command(slash_command, prefix_command, rename = "add")]
+async fn configure_preference_add(
+    ctx: Context<'_>,
+    #[description = "Preference name"] name: String,
+    #[description = "Values players can choose, separated by commas"] values: String,
+    #[description = "Cost per player that doesn't accept the lobby's value"]
+    #[min = 0]
+    mismatch_cost: f32,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let values = values
+        .split(',')
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .unique()
+        .collect_vec();
+    let response = if values.is_empty() || values.len() > 25 {
+        "A preference needs between 1 and 25 values".to_string()
+    } else {
+        let response = format!(
+            "Preference {} set to {} with a mismatch cost of {}",
+            name,
+            values.join(", "),
+            mismatch_cost
+        );
+        ctx.data()
+            .configuration
+            .get_mut(&queue_uuid)
+            .unwrap()
+            .preferences
+            .insert(
+                name,
+                PreferenceDimension {
+                    values,
+                    mismatch_cost,
+                },
+            );
+        response
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Removes a matchmaking preference
+#[poise::command(slash_command, prefix_command, rename = "remove")]
+async fn configure_preference_remove(
+    ctx: Context<'_>,
+    #[description = "Preference name"] name: String,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let removed = ctx
+        .data()
+        .configuration
+        .get_mut(&queue_uuid)
+        .unwrap()
+        .preferences
+        .remove(&name)
+        .is_some();
+    let response = if removed {
+        format!("Preference {} removed", name)
+    } else {
+        format!("{} wasn't a preference", name)
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Lists matchmaking preferences
+#[poise::command(slash_command, prefix_command, rename = "list")]
+async fn configure_preference_list(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let data_lock = ctx.data().configuration.get(&queue_uuid).unwrap();
+        format!(
+            "Preferences:\n{}",
+            data_lock
+                .preferences
+                .iter()
+                .map(|(name, dimension)| format!(
+                    "* {}: {} (mismatch cost {})",
+                    name,
+                    dimension.values.join(", "),
+                    dimension.mismatch_cost
+                ))
+                .join("\n")
+        )
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Configures soft matchmaking preferences such as region or platform
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "preference",
+    subcommands(
+        "configure_preference_add",
+        "configure_preference_remove",
+        "configure_preference_list",
+    )
+)]
+async fn configure_preference(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Configures players who host new lobbies whenever they're in them
+#[poise::command(slash_command, prefix_command, rename = "designated_hosts")]
+async fn configure_designated_hosts(
+    ctx: Context<'_>,
+    #[flag] remove: bool,
+    #[description = "Host"] player: Option<serenity::UserId>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        if let Some(value) = player {
+            if remove {
+                if data_lock.designated_hosts.remove(&value) {
+                    format!("{} removed as designated host", value.mention())
+                } else {
+                    format!("{} wasn't a designated host", value.mention())
+                }
+            } else {
+                data_lock.designated_hosts.insert(value);
+                format!("{} added as designated host", value.mention())
+            }
+        } else {
+            format!(
+                "Designated hosts are {}",
+                data_lock
+                    .designated_hosts
+                    .iter()
+                    .map(|c| c.mention())
+                    .join(", ")
+            )
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Configures roles that can see match channels of matches their not in
+#[poise::command(slash_command, prefix_command, rename = "visability_override_roles")]
+async fn configure_visability_override_roles(
+    ctx: Context<'_>,
+    #[flag] remove: bool,
+    #[description = "Override role"] channel: Option<serenity::RoleId>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let mut data_lock = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        if let Some(value) = channel {
+            if remove {
+                if data_lock.visability_override_roles.remove(&value) {
+                    format!("{} removed as override role", value)
+                } else {
+                    format!("{} wasn't a override role", value)
+                }
+            } else {
+                data_lock.visability_override_roles.insert(value.clone());
+                format!("{} added as override role", value)
+            }
+        } else {
+            format!(
+                "Override roles are {}",
+                data_lock
+                    .visability_override_roles
+                    .iter()
+                    .map(|c| c.mention())
+                    .join(", ")
+            )
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Displays your or another user's account creation date
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS",
+    subcommands(
+        "ConfigurationModifiers::configure_team_size",
+        "ConfigurationModifiers::configure_team_count",
+        "configure_queue_category",
+        "configure_queue_channels",
         "configure_post_match_channel",
         "configure_maps",
         "configure_roles",
         "configure_role_combinations",
         "ConfigurationModifiers::configure_map_vote_count",
         "ConfigurationModifiers::configure_map_vote_time",
-        "ConfigurationModifiers::configure_maximum_queue_cost",
-        "ConfigurationModifiers::configure_incorrect_roles_cost",
+        "configure_results",
+        "configure_costs",
         "configure_register_role",
         "configure_audit_channel",
-        "ConfigurationModifiers::configure_log_chats",
+        "configure_history",
         "ConfigurationModifiers::configure_prevent_recent_maps",
         "configure_visability_override_roles",
+        "configure_matchmaking",
+        "configure_display",
+        "configure_default_player",
+        "configure_game_category",
+        "configure_webhook",
+        "configure_preference",
+        "configure_leavers",
     )
 )]
 pub async fn configure(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Configures who can be matched and how matches are picked
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "matchmaking",
+    subcommands(
+        "configure_mmr_bounds",
+        "ConfigurationModifiers::configure_allow_unrated_players",
+        "configure_max_party_size",
+        "ConfigurationModifiers::configure_split_oversized_parties",
+        "ConfigurationModifiers::configure_wait_vs_fairness",
+        "ConfigurationModifiers::configure_balance_teams",
+        "configure_designated_hosts",
+        "ConfigurationModifiers::configure_allow_multi_queue",
+        "ConfigurationModifiers::configure_max_avoided_players",
+        "ConfigurationModifiers::configure_party_coordination_mmr_bonus",
+        "ConfigurationModifiers::configure_placement_games",
+        "ConfigurationModifiers::configure_min_account_age_days",
+        "ConfigurationModifiers::configure_min_guild_member_days",
+        "configure_account_age_exempt_role",
+        "ConfigurationModifiers::configure_min_start_players",
+        "ConfigurationModifiers::configure_min_start_wait_minutes",
+        "ConfigurationModifiers::configure_stale_party_hours",
+        "ConfigurationModifiers::configure_party_invite_expiry_minutes",
+        "ConfigurationModifiers::configure_requeue_winners_together",
+        "ConfigurationModifiers::configure_max_streak_requeues",
+        "ConfigurationModifiers::configure_matchmaking_max_retries",
+        "ConfigurationModifiers::configure_max_concurrent_matches",
+        "ConfigurationModifiers::configure_series_length",
+        "ConfigurationModifiers::configure_rate_series_games",
+        "ConfigurationModifiers::configure_draft_mode",
+    )
+)]
+async fn configure_matchmaking(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Configures how match results are voted on
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "results",
+    subcommands(
+        "ConfigurationModifiers::configure_result_vote_time",
+        "ConfigurationModifiers::configure_min_match_duration",
+        "ConfigurationModifiers::configure_require_result_proof",
+        "ConfigurationModifiers::configure_result_proof_timeout_minutes",
+    )
+)]
+async fn configure_results(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Configures how much a match may cost before it's held back
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "costs",
+    subcommands(
+        "ConfigurationModifiers::configure_maximum_queue_cost",
+        "ConfigurationModifiers::configure_normalize_queue_cost",
+        "ConfigurationModifiers::configure_incorrect_roles_cost",
+        "ConfigurationModifiers::configure_avoided_player_cost",
+        "ConfigurationModifiers::configure_unregistered_mix_cost",
+    )
+)]
+async fn configure_costs(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Configures what's kept of finished matches
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "history",
+    subcommands(
+        "ConfigurationModifiers::configure_log_chats",
+        "ConfigurationModifiers::configure_history_retention_days",
+    )
+)]
+async fn configure_history(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Configures how leavers are tracked
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "leavers",
+    subcommands(
+        "ConfigurationModifiers::configure_leaver_verification_time",
+        "ConfigurationModifiers::configure_leaver_decay_days",
+        "ConfigurationModifiers::configure_leaver_reports_required",
+        "ConfigurationModifiers::configure_count_cancels",
+        "ConfigurationModifiers::configure_cancel_alert_threshold",
+        "ConfigurationModifiers::configure_cancel_vote_cooldown_minutes",
+        "ConfigurationModifiers::configure_enforce_leaver_disconnect",
+        "ConfigurationModifiers::configure_leaver_surrender",
+        "ConfigurationModifiers::configure_leaver_surrender_delay_minutes",
+        "ConfigurationModifiers::configure_surrender_votes_required",
+        "ConfigurationModifiers::configure_reconnect_grace_seconds",
+    )
+)]
+async fn configure_leavers(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Configures what players are shown and messaged
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "display",
+    subcommands(
+        "ConfigurationModifiers::configure_show_rosters_in_result_vote",
+        "ConfigurationModifiers::configure_hide_mmr",
+        "ConfigurationModifiers::configure_queue_notify_offset",
+        "ConfigurationModifiers::configure_max_map_rerolls",
+        "ConfigurationModifiers::configure_dm_ban_notifications",
+        "ConfigurationModifiers::configure_post_match_summary",
+        "ConfigurationModifiers::configure_keep_match_channels",
+        "ConfigurationModifiers::configure_clean_up_match_messages",
+        "ConfigurationModifiers::configure_lock_kept_match_channels",
+        "ConfigurationModifiers::configure_map_vote_select_menu",
+        "ConfigurationModifiers::configure_mvp_vote",
+        "ConfigurationModifiers::configure_shot_callers",
+        "configure_announce_channel",
+        "ConfigurationModifiers::configure_afk_check_in_channel",
+        "configure_team_names",
+        "ConfigurationModifiers::configure_locale",
+    )
+)]
+async fn configure_display(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Configures the rating and queueing costs players start with
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "default_player",
+    subcommands(
+        "ConfigurationModifiers::configure_default_rating",
+        "ConfigurationModifiers::configure_default_uncertainty",
+        "ConfigurationModifiers::configure_default_cost_per_avg_mmr_differential",
+        "ConfigurationModifiers::configure_default_acceptable_mmr_differential",
+        "ConfigurationModifiers::configure_default_cost_per_mmr_std_differential",
+        "ConfigurationModifiers::configure_default_acceptable_mmr_std_differential",
+        "ConfigurationModifiers::configure_default_cost_per_mmr_range",
+        "ConfigurationModifiers::configure_default_acceptable_mmr_range",
+        "ConfigurationModifiers::configure_default_new_lobby_host_cost",
+    )
+)]
+async fn configure_default_player(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
 /// Creates a queue
 #[poise::command(
     slash_command,
@@ -599,13 +1917,16 @@ pub async fn create_queue(ctx: Context<'_>) -> Result<(), Error> {
     ctx.data()
         .leaver_data
         .insert(queue_uuid, HashMap::default());
-    ctx.data()
-        .message_edit_notify
-        .insert(queue_uuid, Arc::new(Notify::new()));
     ctx.data().player_bans.insert(queue_uuid, HashMap::new());
     ctx.data().player_data.insert(queue_uuid, HashMap::new());
     ctx.data().queue_idx.insert(queue_uuid, 0);
     ctx.data().queued_players.insert(queue_uuid, HashSet::new());
+    spawn_queue_workers(
+        ctx.data().clone(),
+        ctx.serenity_context().http.clone(),
+        ctx.serenity_context().cache.clone(),
+        queue_uuid,
+    );
 
     ctx.data()
         .guild_data
@@ -622,6 +1943,197 @@ pub async fn create_queue(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Sets the text of queue messages
+///
+/// Use {queued}, {in_game}, {total}, {players} and {eta} as placeholders and \n for line breaks.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn set_queue_channel_prompt(
+    ctx: Context<'_>,
+    #[description = "First line of the message"] header: Option<String>,
+    #[description = "Rest of the message"] body: Option<String>,
+    #[description = "Restore the default text"]
+    #[flag]
+    reset: bool,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let mut config = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        if reset {
+            config.queue_message_header = DEFAULT_QUEUE_MESSAGE_HEADER.to_string();
+            config.queue_message_body = DEFAULT_QUEUE_MESSAGE_BODY.to_string();
+        }
+        // Slash command options can't contain line breaks
+        if let Some(header) = header {
+            config.queue_message_header = header.replace("\\n", "\n");
+        }
+        if let Some(body) = body {
+            config.queue_message_body = body.replace("\\n", "\n");
+        }
+        format!(
+            "Queue message is now:\n```\n{}\n{}\n```",
+            config.queue_message_header, config.queue_message_body
+        )
+    };
+    ctx.data()
+        .message_edit_notify
+        .get(&queue_uuid)
+        .unwrap()
+        .notify_one();
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sets the image shown for a map in map votes
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn set_map_image(
+    ctx: Context<'_>,
+    #[description = "Map"] map: String,
+    #[description = "Link to an image, leave empty to clear"] image_url: Option<String>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let image_url = image_url
+        .map(|image_url| image_url.trim().to_string())
+        .filter(|image_url| !image_url.is_empty());
+    // Discord only embeds images it can fetch over the web
+    if let Some(image_url) = &image_url {
+        if !reqwest::Url::parse(image_url)
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+        {
+            ctx.send(
+                CreateReply::default()
+                    .content(format!("`{}` is not an http(s) link", image_url))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+    let response = {
+        let mut config = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        match config.maps.get_mut(&map) {
+            Some(map_data) => {
+                map_data.image_url = image_url.clone();
+                match image_url {
+                    Some(image_url) => format!("Image for {} set to {}", map, image_url),
+                    None => format!("Image for {} cleared", map),
+                }
+            }
+            None => format!("{} isn't a map, add it with `/configure maps` first", map),
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Renames a queue
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn rename_queue(
+    ctx: Context<'_>,
+    #[description = "New name, leave empty to clear"] name: Option<String>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let name = name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    ctx.data().configuration.get_mut(&queue_uuid).unwrap().name = name.clone();
+    let response = if let Some(name) = name {
+        format!("Queue renamed to `{}`", name)
+    } else {
+        "Queue name cleared".to_string()
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Moves a queue to a new position in the server's queue order
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn move_queue(
+    ctx: Context<'_>,
+    #[description = "New queue index"]
+    #[min = 0]
+    new_idx: u32,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let response = {
+        let mut guild_data = ctx.data().guild_data.lock().unwrap();
+        let queues = &mut guild_data.get_mut(&ctx.guild_id().unwrap()).unwrap().queues;
+        if new_idx as usize >= queues.len() {
+            "Invalid new queue idx.".to_string()
+        } else {
+            // Everything else refers to queues by uuid, so only the order shown to users changes
+            let old_idx = queues
+                .iter()
+                .position(|queue| *queue == queue_uuid)
+                .unwrap();
+            let queue = queues.remove(old_idx);
+            queues.insert(new_idx as usize, queue);
+            format!("Queue moved from index {} to {}", old_idx, new_idx)
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
 /// Imports configuration
 #[poise::command(
     slash_command,
@@ -643,15 +2155,40 @@ pub async fn import_config(
             return Ok(());
         }
     };
-    let new_config: QueueConfiguration = serde_json::from_str(&new_config.as_str())?;
-    *ctx.data().configuration.get_mut(&queue_uuid).unwrap() = new_config;
-    let config = serde_json::to_string_pretty(ctx.data())?;
-    let response = format!("Configuration set to: ```json\n{}\n```", config);
-    ctx.send(CreateReply::default().content(response).ephemeral(true))
-        .await?;
+    let mut new_config: QueueConfiguration = serde_json::from_str(&new_config.as_str())?;
+    {
+        let mut config = ctx.data().configuration.get_mut(&queue_uuid).unwrap();
+        // Exports leave out the webhook secret, so importing one keeps the current secret
+        if new_config.webhook_secret.is_none() {
+            new_config.webhook_secret = config.webhook_secret.take();
+        }
+        *config = new_config;
+    }
+    ctx.send(
+        CreateReply::default()
+            .content("Configuration set to, without the webhook secret:")
+            .attachment(config_attachment(&ctx, queue_uuid)?)
+            .ephemeral(true),
+    )
+    .await?;
     Ok(())
 }
 
+/// A queue's configuration as a file, since full configurations are too long for a message
+fn config_attachment(
+    ctx: &Context<'_>,
+    queue_uuid: QueueUuid,
+) -> Result<serenity::CreateAttachment, Error> {
+    let mut config = ctx.data().configuration.get(&queue_uuid).unwrap().clone();
+    // The secret signs webhooks, so it shouldn't end up wherever the export is shared
+    config.webhook_secret = None;
+    let config = serde_json::to_string_pretty(&config)?;
+    Ok(serenity::CreateAttachment::bytes(
+        config.into_bytes(),
+        "config.json",
+    ))
+}
+
 /// Exports configuration
 #[poise::command(slash_command, prefix_command)]
 pub async fn export_config(
@@ -668,10 +2205,12 @@ pub async fn export_config(
             return Ok(());
         }
     };
-    let config =
-        serde_json::to_string_pretty(&ctx.data().configuration.get(&queue_uuid).unwrap().clone())?;
-    let response = format!("Configuration: ```json\n{}\n```", config);
-    ctx.send(CreateReply::default().content(response).ephemeral(true))
-        .await?;
+    ctx.send(
+        CreateReply::default()
+            .content("Configuration, without the webhook secret:")
+            .attachment(config_attachment(&ctx, queue_uuid)?)
+            .ephemeral(true),
+    )
+    .await?;
     Ok(())
 }
@@ -0,0 +1,143 @@
+//! Player facing messages, looked up in the catalog of the queue's locale
+
+use poise::serenity_prelude::{ChannelId, Mentionable};
+use serde::{Deserialize, Serialize};
+
+use crate::{Data, QueueUuid};
+
+/// Languages queues can talk to players in
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug, poise::ChoiceParameter,
+)]
+pub enum Locale {
+    #[default]
+    English,
+}
+
+impl Locale {
+    fn catalog(self) -> &'static dyn Catalog {
+        match self {
+            Locale::English => &English,
+        }
+    }
+
+    pub fn text(self, message: Message) -> String {
+        self.catalog().text(message)
+    }
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", poise::ChoiceParameter::name(self))
+    }
+}
+
+pub enum Message<'a> {
+    InGame,
+    AlreadyQueued,
+    /// Queued in the queue with this index, which doesn't allow queueing elsewhere
+    QueuedElsewhere(usize),
+    PartyHasPendingInvites,
+    NotInServer,
+    Banned(Option<&'a str>),
+    JoinedQueue,
+    /// Mentions of the party members that joined together
+    JoinedQueueWithParty(&'a str),
+    LeftQueue,
+    PartyLeftQueue,
+    /// Mention of the party member whose leaving removed the rest of the party
+    PartyMemberLeftQueue(&'a str),
+    LeftPartySolo,
+    NotQueued,
+    StatusNotQueued,
+    /// Unix timestamp of when the player joined
+    StatusQueued(i64),
+    /// Index of the other queue, if it's still in the server
+    StatusQueuedElsewhere(Option<usize>),
+    /// Name and text channel of the player's match
+    StatusInMatch(&'a str, ChannelId),
+    StatusInGame,
+    /// Minimum account age in days
+    AccountTooNew(u32),
+    /// Minimum days in the server
+    GuildMembershipTooNew(u32),
+    /// Indices of other queues the player's mmr is allowed in, if there are any
+    MmrOutOfBounds(Option<&'a str>),
+}
+
+/// Translations of every message
+trait Catalog: Sync {
+    fn text(&self, message: Message) -> String;
+}
+
+struct English;
+
+impl Catalog for English {
+    fn text(&self, message: Message) -> String {
+        match message {
+            Message::InGame => "Cannot queue while in game!".to_string(),
+            Message::AlreadyQueued => "You're already in this queue!".to_string(),
+            Message::QueuedElsewhere(queue_idx) => format!(
+                "You're already queued in queue {}, leave it first to join this queue",
+                queue_idx
+            ),
+            Message::PartyHasPendingInvites => "Cannot queue while your party has pending invites! Do `/party leave` to exit party.".to_string(),
+            Message::NotInServer => "Could not find you in this server!".to_string(),
+            Message::Banned(Some(reason)) => {
+                format!("Cannot queue because you're banned for {}", reason)
+            }
+            Message::Banned(None) => "Cannot queue because you're banned".to_string(),
+            Message::JoinedQueue => "Joined queue!".to_string(),
+            Message::JoinedQueueWithParty(members) => format!("Joined queue with {}", members),
+            Message::LeftQueue => "You are no longer queueing!".to_string(),
+            Message::PartyLeftQueue => "Party left queue".to_string(),
+            Message::PartyMemberLeftQueue(member) => format!(
+                "{} left the queue, so your party was removed from it too. Parties can only be matched together.",
+                member
+            ),
+            Message::LeftPartySolo => {
+                "You left your party, the rest of it stays queued without you.".to_string()
+            }
+            Message::NotQueued => "You weren't queued!".to_string(),
+            Message::StatusNotQueued => "You are not in queue".to_string(),
+            Message::StatusQueued(since) => {
+                format!("You've been in queue since <t:{}:R>.", since)
+            }
+            Message::StatusQueuedElsewhere(Some(queue_idx)) => {
+                format!("You are queued in queue {} instead.", queue_idx)
+            }
+            Message::StatusQueuedElsewhere(None) => {
+                "You are queued in a different queue.".to_string()
+            }
+            Message::StatusInMatch(name, channel) => {
+                format!("You are in match {}: {}", name, channel.mention())
+            }
+            Message::StatusInGame => "You are in a game.".to_string(),
+            Message::AccountTooNew(days) => format!(
+                "Cannot queue because your account must be at least {} days old",
+                days
+            ),
+            Message::GuildMembershipTooNew(days) => format!(
+                "Cannot queue until you've been in this server for {} days",
+                days
+            ),
+            Message::MmrOutOfBounds(Some(queues)) => format!(
+                "Your mmr is outside the range allowed in this queue. Try queue {} instead.",
+                queues
+            ),
+            Message::MmrOutOfBounds(None) => {
+                "Your mmr is outside the range allowed in this queue.".to_string()
+            }
+        }
+    }
+}
+
+/// Message in the queue's locale
+pub fn text(data: &Data, queue: &QueueUuid, message: Message) -> String {
+    let locale = data
+        .configuration
+        .get(queue)
+        .map(|config| config.locale)
+        .unwrap_or_default();
+    locale.text(message)
+}
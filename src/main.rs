@@ -1,36 +1,54 @@
 mod admin_commands;
+mod api;
+mod audit;
 mod configure_command;
+mod locale;
+mod metrics;
 mod party_command;
 mod player_config_commands;
+mod webhook;
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Display,
     fs::{self, OpenOptions},
     hash::Hash,
     io::prelude::*,
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
 };
 
 use admin_commands::{
-    create_queue_message, create_register_message, create_roles_message, force_outcome,
-    list_leavers, manage_player, register,
+    approve_leaver_appeal, blacklist_pair, compare_queues, create_preference_message,
+    create_queue_message, create_register_message, create_roles_message, edit_match_map,
+    end_map_vote, fix_team_voice, force_match, force_outcome, force_start_map_vote, leaver_stats,
+    list_leavers, manage_player, prune_history, prune_queue_messages, queue_stats_graph,
+    recount_votes, recreate_queue_messages, register, search_player, set_command_role,
+    set_team_channels_private,
+};
+use audit::{audit, AuditAction, AuditTarget};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use configure_command::{
+    configure, create_queue, export_config, get_queue_uuid, import_config, move_queue,
+    rename_queue, set_map_image, set_queue_channel_prompt,
 };
-use chrono::{DateTime, Utc};
-use configure_command::{configure, create_queue, export_config, import_config};
 use dashmap::DashMap;
 use hopcroft_karp::matching;
 use itertools::{Itertools, MinMaxResult};
-use party_command::{leave_party, list_parties, party};
+use locale::{Locale, Message};
+use party_command::{
+    join_party, leave_party, list_parties, party, prune_stale_parties, queue_as_premade,
+    reject_party,
+};
 use player_config_commands::player_config;
 use poise::{
     serenity_prelude::{
         self as serenity, futures::future, Builder, CacheHttp, ChannelId, ChannelType,
         ComponentInteraction, ComponentInteractionDataKind, CreateActionRow, CreateAllowedMentions,
-        CreateChannel, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
-        EditInteractionResponse, EditMember, EditMessage, GuildId, Http, Mentionable, MessageId,
-        PermissionOverwrite, PermissionOverwriteType, Permissions, RoleId, UserId, VoiceState,
+        CreateChannel, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+        CreateMessage, CreateSelectMenu, CreateSelectMenuOption, EditInteractionResponse,
+        EditMember, EditMessage, GuildId, Http, Mentionable, MessageId, PermissionOverwrite,
+        PermissionOverwriteType, Permissions, RoleId, UserId, VoiceState,
     },
     CreateReply,
 };
@@ -84,7 +102,7 @@ impl QueueUuid {
 #[derive(Serialize, Deserialize)]
 struct Data {
     #[serde(default)]
-    global_player_data: Mutex<HashMap<UserId, GlobalPlayerData>>,
+    global_player_data: Mutex<HashMap<GuildId, HashMap<UserId, GlobalPlayerData>>>,
     #[serde(default)]
     match_channels: Mutex<HashMap<ChannelId, MatchUuid>>,
     #[serde(default)]
@@ -103,16 +121,34 @@ struct Data {
     current_games: DashMap<QueueUuid, HashSet<MatchUuid>>,
     #[serde(skip)]
     message_edit_notify: DashMap<QueueUuid, Arc<Notify>>,
+    #[serde(skip)]
+    matchmaking_notify: DashMap<QueueUuid, Arc<Notify>>,
+    #[serde(skip)]
+    matchmaking_paused: DashMap<QueueUuid, String>,
     #[serde(default)]
     queue_idx: DashMap<QueueUuid, u32>,
     #[serde(default)]
     player_bans: DashMap<QueueUuid, HashMap<UserId, BanData>>,
     #[serde(default)]
-    leaver_data: DashMap<QueueUuid, HashMap<UserId, u32>>,
+    leaver_data: DashMap<QueueUuid, HashMap<UserId, LeaverRecord>>,
     #[serde(default)]
     player_data: DashMap<QueueUuid, HashMap<UserId, DerivedPlayerData>>,
     #[serde(default)]
     is_matchmaking: DashMap<QueueUuid, Option<()>>,
+    #[serde(default)]
+    queue_notify_subscribers: DashMap<QueueUuid, HashSet<UserId>>,
+    /// Pairs of players who are never placed in the same match, see `blacklisted_pair`
+    #[serde(default)]
+    blacklisted_pairs: DashMap<QueueUuid, HashSet<(UserId, UserId)>>,
+    /// Captains drafts that are running or waiting to become a match
+    #[serde(skip)]
+    drafts: DashMap<QueueUuid, Draft>,
+    /// When the gateway last connected or resumed, see `in_reconnect_grace`
+    #[serde(skip)]
+    connected_at: Mutex<Option<Instant>>,
+    /// Start times of each queue's latest matches, oldest first, see `estimate_match_interval`
+    #[serde(skip)]
+    recent_match_starts: DashMap<QueueUuid, VecDeque<u64>>,
 } // User data, which is stored and accessible in all command invocations
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Arc<Data>, Error>;
@@ -134,9 +170,140 @@ impl Default for Data {
             player_bans: DashMap::new(),
             leaver_data: DashMap::new(),
             message_edit_notify: DashMap::new(),
+            matchmaking_notify: DashMap::new(),
+            matchmaking_paused: DashMap::new(),
             is_matchmaking: DashMap::new(),
+            queue_notify_subscribers: DashMap::new(),
+            blacklisted_pairs: DashMap::new(),
+            drafts: DashMap::new(),
+            connected_at: Mutex::new(None),
+            recent_match_starts: DashMap::new(),
+        }
+    }
+}
+
+/// How long a captain has to pick before the draft falls back to automatic teams
+const DRAFT_PICK_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Teams being picked by their captains, who are the first member of each team
+struct Draft {
+    teams: Vec<Vec<UserId>>,
+    /// Players left to pick, grouped by party since parties are picked together
+    pool: Vec<Vec<UserId>>,
+    /// Most players a team can have
+    team_size: usize,
+    /// Display names for the select menu, which can't show mentions
+    names: HashMap<UserId, String>,
+    team_names: Vec<String>,
+    message: Option<(ChannelId, MessageId)>,
+    /// When the current captain started picking
+    pick_started: Instant,
+}
+
+impl Draft {
+    /// Whether picking the pool's `party` for `team` leaves room for every other party in the pool
+    fn can_pick(&self, team: usize, party: usize) -> bool {
+        let mut free_slots = self
+            .teams
+            .iter()
+            .map(|team| self.team_size.saturating_sub(team.len()))
+            .collect_vec();
+        let Some(slots) = free_slots[team].checked_sub(self.pool[party].len()) else {
+            return false;
+        };
+        free_slots[team] = slots;
+        let remaining_parties = self
+            .pool
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx != party)
+            .map(|(_, party)| party.len())
+            .sorted()
+            .rev()
+            .collect_vec();
+        parties_fit(&remaining_parties, &mut free_slots)
+    }
+
+    /// Index of the team whose captain picks next, the smallest team with a valid pick going first
+    fn picking_team(&self) -> usize {
+        (0..self.teams.len())
+            .filter(|team| (0..self.pool.len()).any(|party| self.can_pick(*team, party)))
+            .min_by_key(|team| self.teams[*team].len())
+            .unwrap_or_default()
+    }
+
+    fn players(&self) -> impl Iterator<Item = &UserId> {
+        self.teams
+            .iter()
+            .flatten()
+            .chain(self.pool.iter().flatten())
+    }
+
+    fn content(&self) -> String {
+        let mut content = format!(
+            "# Captains draft\n{}",
+            get_team_rosters(&self.teams, &self.team_names)
+        );
+        if self.pool.is_empty() {
+            content += "Draft complete, creating the match";
+        } else {
+            content += format!(
+                "{} is picking",
+                self.teams[self.picking_team()][0].mention()
+            )
+            .as_str();
         }
+        content
     }
+
+    fn components(&self, queue_id: QueueUuid) -> Vec<CreateActionRow> {
+        if self.pool.is_empty() {
+            return vec![];
+        }
+        vec![CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                ButtonData::DraftPick(queue_id).get_id(),
+                serenity::CreateSelectMenuKind::String {
+                    options: self
+                        .pool
+                        .iter()
+                        .map(|party| {
+                            let names = party
+                                .iter()
+                                .map(|player| {
+                                    self.names
+                                        .get(player)
+                                        .cloned()
+                                        .unwrap_or_else(|| player.to_string())
+                                })
+                                .join(" + ");
+                            CreateSelectMenuOption::new(names, party[0].to_string())
+                        })
+                        .collect(),
+                },
+            )
+            .placeholder("Pick a player"),
+        )]
+    }
+}
+
+/// Whether parties of the given sizes, largest first, can all be put on teams with `free_slots`
+fn parties_fit(party_sizes: &[usize], free_slots: &mut [usize]) -> bool {
+    let Some((party_size, rest)) = party_sizes.split_first() else {
+        return true;
+    };
+    for team in 0..free_slots.len() {
+        if free_slots[team] < *party_size {
+            continue;
+        }
+        free_slots[team] -= party_size;
+        let fits = parties_fit(rest, free_slots);
+        free_slots[team] += party_size;
+        if fits {
+            return true;
+        }
+    }
+    false
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -149,20 +316,39 @@ struct BanData {
 #[derive(Serialize, Deserialize, Debug)]
 struct GuildData {
     queues: Vec<QueueUuid>,
+    /// Roles required to use commands, keyed by qualified command name
+    #[serde(default)]
+    command_roles: HashMap<String, RoleId>,
 }
 
 impl Default for GuildData {
     fn default() -> Self {
         Self {
             queues: Default::default(),
+            command_roles: HashMap::new(),
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 struct QueueGroup {
+    guild: GuildId,
     players: HashSet<UserId>,
     pending_invites: HashSet<UserId>,
+    #[serde(default)]
+    invite_link: Option<PartyInviteLink>,
+    #[serde(default = "Utc::now")]
+    last_active: DateTime<Utc>,
+    /// Consecutive wins this party has been kept together for, set on parties formed from a
+    /// winning team
+    #[serde(default)]
+    streak_requeues: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct PartyInviteLink {
+    nonce: u64,
+    expires: DateTime<Utc>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -176,10 +362,49 @@ enum QueueMessageType {
     Queue,
     Roles,
     Register,
+    Preference(String),
+}
+
+/// Details of a map in a queue's map pool
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct MapData {
+    /// Shown when the map is a candidate in a map vote
+    image_url: Option<String>,
+}
+
+/// Reads a queue's maps, which configs saved before maps had details store as a list of names
+fn deserialize_maps<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, MapData>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredMaps {
+        Names(HashSet<String>),
+        WithData(HashMap<String, MapData>),
+    }
+    Ok(match StoredMaps::deserialize(deserializer)? {
+        StoredMaps::Names(names) => names
+            .into_iter()
+            .map(|name| (name, MapData::default()))
+            .collect(),
+        StoredMaps::WithData(maps) => maps,
+    })
+}
+
+/// A soft matchmaking preference such as region or platform. Lobbies where some players don't
+/// accept the most common value cost `mismatch_cost` per such player.
+#[derive(Serialize, Deserialize, Clone)]
+struct PreferenceDimension {
+    values: Vec<String>,
+    mismatch_cost: f32,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 struct QueueConfiguration {
+    /// Display name shown when listing queues
+    name: Option<String>,
     team_size: u32,
     team_count: u32,
     category: Option<ChannelId>,
@@ -189,11 +414,20 @@ struct QueueConfiguration {
     queue_messages: Vec<(ChannelId, MessageId, QueueMessageType)>,
     register_role: Option<RoleId>,
     audit_channel: Option<ChannelId>,
-    maps: HashSet<String>,
+    /// Channel where new matches are publicly announced
+    announce_channel: Option<ChannelId>,
+    #[serde(deserialize_with = "deserialize_maps")]
+    maps: HashMap<String, MapData>,
     map_vote_count: u32,
     map_vote_time: u32,
+    /// Seconds before an unresolved match is resolved to its most voted result, 0 to wait forever
+    result_vote_time: u32,
     prevent_recent_maps: bool,
     leaver_verification_time: u32,
+    /// Days without leaving before a player's leave count drops by one, 0 to never decay
+    leaver_decay_days: u32,
+    /// Distinct match members that must report a player before they get a leaver check
+    leaver_reports_required: u32,
     default_player_data: PlayerData,
     maximum_queue_cost: f32,
     incorrect_roles_cost: f32,
@@ -202,11 +436,160 @@ struct QueueConfiguration {
     role_combinations: Vec<(Vec<String>, f32)>,
     log_chats: bool,
     max_lobby_keep_time: u64,
+    show_rosters_in_result_vote: bool,
+    hide_mmr: bool,
+    queue_notify_offset: u32,
+    mmr_floor: Option<f64>,
+    mmr_ceiling: Option<f64>,
+    allow_unrated_players: bool,
+    max_party_size: Option<u32>,
+    split_oversized_parties: bool,
+    /// Biases matchmaking between fairness and wait time. At 0 time in queue is ignored and only
+    /// the fairness costs count, at 1 the fairness costs are ignored and the longest waiting
+    /// players are matched first. 0.5 weighs them as configured.
+    wait_vs_fairness: f32,
+    max_map_rerolls: u32,
+    dm_ban_notifications: bool,
+    unregistered_mix_cost: f32,
+    matchmaking_max_retries: u32,
+    post_match_summary: bool,
+    balance_teams: bool,
+    stale_party_hours: u32,
+    party_invite_expiry_minutes: u32,
+    /// Smallest match that can start once a player has waited `min_start_wait_minutes`, 0 to
+    /// only start full matches
+    min_start_players: u32,
+    min_start_wait_minutes: u32,
+    webhook_url: Option<String>,
+    webhook_secret: Option<String>,
+    preferences: HashMap<String, PreferenceDimension>,
+    /// Players who host whenever they're in a new lobby instead of asking for a volunteer
+    designated_hosts: HashSet<UserId>,
+    /// Whether players can be queued in this queue and others at the same time
+    allow_multi_queue: bool,
+    /// Cost for each player in a lobby with someone they avoid
+    avoided_player_cost: f32,
+    /// How many players each player can avoid, 0 disables avoiding
+    max_avoided_players: u32,
+    min_account_age_days: u32,
+    min_guild_member_days: u32,
+    /// Role that can queue regardless of account age or membership duration
+    account_age_exempt_role: Option<RoleId>,
+    requeue_winners_together: bool,
+    /// Keep the match text channel after resolution, renamed with a `done-` prefix
+    keep_match_channels: bool,
+    /// Delete the map, result, MVP and surrender vote messages from kept match channels
+    clean_up_match_messages: bool,
+    /// Make kept match channels read only
+    lock_kept_match_channels: bool,
+    /// Consecutive wins a team is kept together for before it is split up again
+    max_streak_requeues: u32,
+    /// Show map vote options in a select menu instead of buttons
+    map_vote_select_menu: bool,
+    /// Let each team vote for an MVP during the result vote
+    mvp_vote: bool,
+    /// Post afk checks in the queue channel instead of DMing players
+    afk_check_in_channel: bool,
+    /// Matches that can run at once before new ones are held back, 0 for no limit
+    max_concurrent_matches: u32,
+    /// Games in a best-of series, 1 for single game matches
+    series_length: u32,
+    /// Rate every game of a series instead of only the series result
+    rate_series_games: bool,
+    /// Let the highest rated players of a lobby pick their teams in the queue channel
+    draft_mode: bool,
+    /// Divide lobby costs by their player count, making `maximum_queue_cost` a per player cost
+    normalize_queue_cost: bool,
+    /// Count cancelled matches against the players who voted to cancel them
+    count_cancels: bool,
+    /// Cancel count at which, and at every multiple of which, the audit channel is alerted, 0 to
+    /// never alert
+    cancel_alert_threshold: u32,
+    /// Minutes after voting to cancel a match before a player can vote to cancel another one
+    cancel_vote_cooldown_minutes: u32,
+    /// Disconnect confirmed leavers from voice, otherwise they're only recorded
+    enforce_leaver_disconnect: bool,
+    /// Days finished matches are kept in the match history, 0 to keep them forever
+    history_retention_days: u32,
+    /// Names given to the teams in order, teams past the end are numbered instead
+    team_names: Vec<String>,
+    /// Seconds after a match starts before result votes other than cancel can resolve it
+    min_match_duration: u32,
+    /// Let a team with a confirmed leaver surrender the game, or vote to remake it when there are
+    /// more than two teams
+    leaver_surrender: bool,
+    /// Minutes after a leaver is confirmed before their team is offered to surrender
+    leaver_surrender_delay_minutes: u32,
+    /// Votes needed from the leaver's team to surrender, capped at the players left on it
+    surrender_votes_required: u32,
+    /// Rating added to each party member for every other member of their party in the lobby when
+    /// balancing, since coordinated parties play above their ratings
+    party_coordination_mmr_bonus: f32,
+    /// Games a player plays before their rating is settled, during which lobbies' rating range
+    /// leaves them out so they can face a wider spread of skill, 0 to disable
+    placement_games: u32,
+    /// First line of queue messages, see `render_queue_template` for the placeholders
+    queue_message_header: String,
+    /// Rest of the queue messages, see `render_queue_template` for the placeholders
+    queue_message_body: String,
+    /// Language of the messages shown to players
+    locale: Locale,
+    /// Seconds after the bot connects or resumes during which players disconnecting from voice
+    /// stay queued and afk checks are skipped, since an outage looks like everyone leaving
+    reconnect_grace_seconds: u32,
+    /// Require a screenshot or link posted in the match channel before a vote can resolve a match
+    /// to anything but a cancel. Admins can still force results.
+    require_result_proof: bool,
+    /// Minutes an agreed result waits for proof before admins are alerted, 0 to never alert
+    result_proof_timeout_minutes: u32,
+    /// Mark the highest rated player of each team as its shot-caller in the roster, which is only
+    /// informational
+    shot_callers: bool,
+}
+
+impl QueueConfiguration {
+    /// Rating for a player registering at `mmr`, with the queue's default uncertainty
+    fn registration_rating(&self, mmr: f64) -> WengLinRating {
+        WengLinRating {
+            rating: mmr,
+            uncertainty: self.default_player_data.rating.uncertainty,
+        }
+    }
+
+    /// Maps offered in a map vote, which can't be more than there are maps
+    fn effective_map_vote_count(&self) -> usize {
+        (self.map_vote_count as usize).min(self.maps.len())
+    }
+
+    /// Channel of the first queue message, used for posts that aren't tied to a match
+    fn queue_channel(&self) -> Option<ChannelId> {
+        self.queue_messages
+            .iter()
+            .find(|(_, _, message_type)| matches!(message_type, QueueMessageType::Queue))
+            .map(|(channel, _, _)| *channel)
+    }
+
+    /// Names of the first `team_count` teams, numbering any without a configured name
+    fn team_labels(&self, team_count: usize) -> Vec<String> {
+        (0..team_count)
+            .map(|team| {
+                self.team_names
+                    .get(team)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Team {}", team + 1))
+            })
+            .collect()
+    }
 }
 
+const DEFAULT_QUEUE_MESSAGE_HEADER: &str = "## Matchmaking Queue";
+const DEFAULT_QUEUE_MESSAGE_BODY: &str =
+    "### {total} people are playing right now\nThere are {queued} queued players: {players}";
+
 impl Default for QueueConfiguration {
     fn default() -> Self {
         Self {
+            name: None,
             team_size: 5,
             team_count: 2,
             category: None,
@@ -216,11 +599,15 @@ impl Default for QueueConfiguration {
             queue_messages: vec![],
             register_role: None,
             audit_channel: None,
-            maps: HashSet::new(),
+            announce_channel: None,
+            maps: HashMap::new(),
             map_vote_count: 0,
             map_vote_time: 0,
+            result_vote_time: 0,
             prevent_recent_maps: false,
             leaver_verification_time: 30,
+            leaver_decay_days: 0,
+            leaver_reports_required: 1,
             default_player_data: PlayerData::default(),
             maximum_queue_cost: 50.0,
             incorrect_roles_cost: 10.0,
@@ -229,6 +616,67 @@ impl Default for QueueConfiguration {
             role_combinations: vec![],
             log_chats: true,
             max_lobby_keep_time: 15 * 60,
+            show_rosters_in_result_vote: false,
+            hide_mmr: false,
+            queue_notify_offset: 1,
+            mmr_floor: None,
+            mmr_ceiling: None,
+            allow_unrated_players: true,
+            max_party_size: None,
+            split_oversized_parties: false,
+            wait_vs_fairness: 0.5,
+            max_map_rerolls: 0,
+            dm_ban_notifications: false,
+            unregistered_mix_cost: 0.0,
+            matchmaking_max_retries: 5,
+            post_match_summary: false,
+            balance_teams: false,
+            stale_party_hours: 24,
+            party_invite_expiry_minutes: 60,
+            min_start_players: 0,
+            min_start_wait_minutes: 10,
+            webhook_url: None,
+            webhook_secret: None,
+            preferences: HashMap::new(),
+            designated_hosts: HashSet::new(),
+            allow_multi_queue: true,
+            avoided_player_cost: 10.0,
+            max_avoided_players: 3,
+            min_account_age_days: 0,
+            min_guild_member_days: 0,
+            account_age_exempt_role: None,
+            requeue_winners_together: false,
+            keep_match_channels: false,
+            clean_up_match_messages: false,
+            lock_kept_match_channels: false,
+            max_streak_requeues: 3,
+            map_vote_select_menu: false,
+            mvp_vote: false,
+            afk_check_in_channel: false,
+            max_concurrent_matches: 0,
+            series_length: 1,
+            rate_series_games: false,
+            draft_mode: false,
+            normalize_queue_cost: false,
+            count_cancels: false,
+            cancel_alert_threshold: 0,
+            cancel_vote_cooldown_minutes: 0,
+            enforce_leaver_disconnect: true,
+            history_retention_days: 0,
+            team_names: vec![],
+            min_match_duration: 0,
+            leaver_surrender: false,
+            leaver_surrender_delay_minutes: 0,
+            surrender_votes_required: 1,
+            party_coordination_mmr_bonus: 0.0,
+            placement_games: 0,
+            queue_message_header: DEFAULT_QUEUE_MESSAGE_HEADER.to_string(),
+            queue_message_body: DEFAULT_QUEUE_MESSAGE_BODY.to_string(),
+            locale: Locale::default(),
+            reconnect_grace_seconds: 120,
+            require_result_proof: false,
+            result_proof_timeout_minutes: 30,
+            shot_callers: false,
         }
     }
 }
@@ -240,6 +688,19 @@ enum MatchResult {
     Cancel,
 }
 
+impl MatchResult {
+    /// Result as shown to players, using the match's team names
+    fn label(&self, team_names: &[String]) -> String {
+        match self {
+            MatchResult::Team(team) => team_names
+                .get(*team as usize)
+                .cloned()
+                .unwrap_or_else(|| self.to_string()),
+            _ => self.to_string(),
+        }
+    }
+}
+
 impl std::fmt::Display for MatchResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -266,6 +727,66 @@ struct MatchData {
     resolved: bool,
     name: String,
     queue: QueueUuid,
+    #[serde(default)]
+    map: Option<String>,
+    #[serde(default)]
+    map_reroll_votes: HashSet<UserId>,
+    #[serde(default)]
+    map_rerolls: u32,
+    #[serde(default)]
+    final_result: Option<MatchResult>,
+    #[serde(default)]
+    locked: bool,
+    /// Members who've reported each player as a leaver since that player's last leaver check
+    #[serde(default)]
+    leaver_reports: HashMap<UserId, HashSet<UserId>>,
+    /// Each member's MVP pick from their own team
+    #[serde(default)]
+    mvp_votes: HashMap<UserId, UserId>,
+    /// Players voted MVP of their team by a majority of it
+    #[serde(default)]
+    mvps: Vec<UserId>,
+    /// Role names assigned to each member, in the same layout as `members`
+    #[serde(default)]
+    roles: Vec<Vec<String>>,
+    #[serde(default)]
+    match_start_time: Option<u64>,
+    /// Results of the finished games of a best-of series
+    #[serde(default)]
+    series_games: Vec<MatchResult>,
+    /// Message of the map vote while it's open
+    #[serde(default)]
+    map_vote_message: Option<MessageId>,
+    /// Members who voted for the cancel a cancelled match resolved to
+    #[serde(default)]
+    cancel_voters: Vec<UserId>,
+    /// Message of the current game's result vote
+    #[serde(default)]
+    result_vote_message: Option<MessageId>,
+    /// Members of a team with a confirmed leaver who voted to surrender
+    #[serde(default)]
+    surrender_votes: HashSet<UserId>,
+    /// Screenshots and links members posted as proof of the results
+    #[serde(default)]
+    result_proof: Vec<ResultProof>,
+    /// When the result vote agreed on a result that's still waiting for proof
+    #[serde(default)]
+    awaiting_proof_since: Option<u64>,
+    /// Highest rated player of each team, if the queue marks shot-callers
+    #[serde(default)]
+    shot_callers: Vec<UserId>,
+    /// Map, result, MVP and surrender vote messages, removed from kept match channels by
+    /// `clean_up_match_messages`
+    #[serde(default)]
+    vote_messages: Vec<MessageId>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ResultProof {
+    submitter: UserId,
+    url: String,
+    /// Game of the series the proof was posted during
+    game: usize,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -369,6 +890,18 @@ impl Default for PlayerData {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct LeaverRecord {
+    count: u32,
+    /// The match and time of each recorded leave, leaves counted before these were stored have
+    /// no entry
+    #[serde(default)]
+    leaves: Vec<(MatchUuid, DateTime<Utc>)>,
+    /// When the count last decayed, the clean period restarts from here or the latest leave
+    #[serde(default)]
+    last_decay: Option<DateTime<Utc>>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct PlayerStats {
     wins: u32,
@@ -376,6 +909,12 @@ struct PlayerStats {
     draws: u32,
 }
 
+impl PlayerStats {
+    fn games_played(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+}
+
 impl Default for PlayerStats {
     fn default() -> Self {
         Self {
@@ -393,6 +932,30 @@ struct DerivedPlayerData {
     game_categories: HashMap<String, Vec<usize>>,
     stats: PlayerStats,
     game_history: Vec<MatchUuid>,
+    /// Accepted values for each preference dimension, players without an entry accept any
+    #[serde(default)]
+    preferences: HashMap<String, Vec<String>>,
+    /// Requeue automatically with the rest of the team after winning
+    #[serde(default)]
+    requeue_after_win: bool,
+    /// Ping the player in the match channel when their match starts
+    #[serde(default)]
+    ping_on_match_start: bool,
+    /// Players this player would rather not be matched with
+    #[serde(default)]
+    avoided_players: HashSet<UserId>,
+    #[serde(default)]
+    mvp_count: u32,
+    /// Cancelled matches the player voted to cancel, while `count_cancels` is on
+    #[serde(default)]
+    cancel_count: u32,
+    /// When the player last voted for the cancel of a cancelled match
+    #[serde(default)]
+    last_cancel_vote: Option<u64>,
+    /// When the player joined this queue, kept so players queued in several queues keep each
+    /// queue's wait time
+    #[serde(default)]
+    queue_enter_time: Option<DateTime<Utc>>,
 }
 
 impl Default for DerivedPlayerData {
@@ -403,6 +966,14 @@ impl Default for DerivedPlayerData {
             game_categories: HashMap::new(),
             stats: PlayerStats::default(),
             game_history: vec![],
+            preferences: HashMap::new(),
+            requeue_after_win: false,
+            ping_on_match_start: false,
+            avoided_players: HashSet::new(),
+            mvp_count: 0,
+            cancel_count: 0,
+            last_cancel_vote: None,
+            queue_enter_time: None,
         }
     }
 }
@@ -411,13 +982,16 @@ impl Default for DerivedPlayerData {
 enum QueueState {
     None,
     Queued(QueueUuid, DateTime<Utc>),
-    InGame,
+    InGame(MatchUuid),
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 struct GlobalPlayerData {
     party: Option<GroupUuid>,
     queue_state: QueueState,
+    /// Afk checks and requeueing after a win are skipped until this time
+    #[serde(default)]
+    snoozed_until: Option<DateTime<Utc>>,
 }
 
 impl Default for GlobalPlayerData {
@@ -425,9 +999,75 @@ impl Default for GlobalPlayerData {
         Self {
             party: None,
             queue_state: QueueState::None,
+            snoozed_until: None,
         }
     }
 }
+
+impl GlobalPlayerData {
+    fn is_snoozed(&self) -> bool {
+        self.snoozed_until.is_some_and(|until| until > Utc::now())
+    }
+}
+
+/// Moves a command's built-in permissions from Discord into [`check_command_role`], so a role
+/// override can stand in for them. Discord would otherwise hide the command from the role's members.
+fn move_permissions_to_check(command: &mut poise::Command<Arc<Data>, Error>) {
+    command.custom_data = Box::new(command.default_member_permissions);
+    command.default_member_permissions = Permissions::empty();
+    command
+        .subcommands
+        .iter_mut()
+        .for_each(move_permissions_to_check);
+}
+
+/// Checks the author has the role the guild restricted the command to, or the command's built-in
+/// permissions when there's no override.
+async fn check_command_role(ctx: Context<'_>) -> Result<bool, Error> {
+    // Subcommands inherit the permissions of their groups
+    let builtin_permissions = ctx
+        .parent_commands()
+        .iter()
+        .copied()
+        .chain([ctx.command()])
+        .filter_map(|command| command.custom_data.downcast_ref::<Permissions>())
+        .fold(Permissions::empty(), |all, permissions| all | *permissions);
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(builtin_permissions.is_empty());
+    };
+    let required_role = {
+        let guild_data = ctx.data().guild_data.lock().unwrap();
+        // The most specific override applies, so restricting `configure` covers its subcommands
+        let words = ctx.command().qualified_name.split(' ').collect_vec();
+        guild_data.get(&guild_id).and_then(|guild_data| {
+            (1..=words.len()).rev().find_map(|len| {
+                guild_data
+                    .command_roles
+                    .get(&words[..len].join(" "))
+                    .cloned()
+            })
+        })
+    };
+    if required_role.is_none() && builtin_permissions.is_empty() {
+        return Ok(true);
+    }
+    let Some(member) = ctx.author_member().await else {
+        return Ok(false);
+    };
+    let permissions = member
+        .permissions
+        .or_else(|| ctx.guild().map(|guild| guild.member_permissions(&member)))
+        .unwrap_or_default();
+    // Administrators can't be locked out of restoring the default permissions
+    if permissions.administrator() {
+        return Ok(true);
+    }
+    Ok(match required_role {
+        Some(required_role) => member.roles.contains(&required_role),
+        None => permissions.contains(builtin_permissions),
+    })
+}
+
 async fn on_error(error: poise::FrameworkError<'_, Arc<Data>, Error>) {
     // This is our custom error handler
     // They are many errors that can occur, so we only handle the ones we want to customize
@@ -440,6 +1080,17 @@ async fn on_error(error: poise::FrameworkError<'_, Arc<Data>, Error>) {
         poise::FrameworkError::EventHandler { error, event, .. } => {
             println!("Error in event `{}`: {:?}", event.snake_case_name(), error,);
         }
+        poise::FrameworkError::CommandCheckFailed {
+            error: None, ctx, ..
+        } => {
+            ctx.send(
+                CreateReply::default()
+                    .content("You don't have permission to use this command.")
+                    .ephemeral(true),
+            )
+            .await
+            .ok();
+        }
         error => {
             if let Err(e) = poise::builtins::on_error(error).await {
                 println!("Error while handling error: {}", e)
@@ -465,12 +1116,14 @@ async fn try_queue_player(
         data.global_player_data
             .lock()
             .unwrap()
+            .entry(guild_id)
+            .or_default()
             .entry(user_id)
             .or_default()
             .queue_state,
-        QueueState::InGame
+        QueueState::InGame(_)
     ) {
-        return Err("Cannot queue while in game!".to_string());
+        return Err(locale::text(&data, queue_id, Message::InGame));
     }
     if data
         .queued_players
@@ -478,12 +1131,15 @@ async fn try_queue_player(
         .unwrap()
         .contains(&user_id)
     {
-        return Err("You're already in this queue!".to_string());
+        return Err(locale::text(&data, queue_id, Message::AlreadyQueued));
     }
+    check_multi_queue(&data, queue_id, user_id, guild_id)?;
     if let Some(group) = data
         .global_player_data
         .lock()
         .unwrap()
+        .get(&guild_id)
+        .unwrap()
         .get(&user_id)
         .unwrap()
         .party
@@ -498,7 +1154,11 @@ async fn try_queue_player(
             .len()
             > 0
         {
-            return Err("Cannot queue while your party has pending invites! Do `/party leave` to exit party.".to_string());
+            return Err(locale::text(
+                &data,
+                queue_id,
+                Message::PartyHasPendingInvites,
+            ));
         }
     }
     for queue in data
@@ -520,7 +1180,11 @@ async fn try_queue_player(
     let user_roles = if is_bot {
         vec![]
     } else {
-        guild_id.member(http.clone(), user_id).await.unwrap().roles
+        let Ok(member) = guild_id.member(http.clone(), user_id).await else {
+            return Err(locale::text(&data, queue_id, Message::NotInServer));
+        };
+        check_account_age(&data, queue_id, &member)?;
+        member.roles
     };
     let player_categories: HashMap<String, Vec<usize>> = game_categories
         .iter()
@@ -541,24 +1205,48 @@ async fn try_queue_player(
         player_data.get_mut(&user_id).unwrap().game_categories = player_categories;
         if let Some(player_ban) = data.player_bans.get(&queue_id).unwrap().get(&user_id) {
             if !player_ban.shadow_ban {
-                if let Some(ban_reason) = player_ban.reason.clone() {
-                    return Err(format!(
-                        "Cannot queue because you're banned for {}",
-                        ban_reason
-                    ));
-                }
-                return Err("Cannot queue because you're banned".to_string());
+                return Err(locale::text(
+                    &data,
+                    queue_id,
+                    Message::Banned(player_ban.reason.as_deref()),
+                ));
             }
         }
     }
+    if !is_bot {
+        check_mmr_bounds(&data, queue_id, user_id, guild_id)?;
+    }
     let queue_enter_time = chrono::offset::Utc::now();
-    {
+    data.player_data
+        .get_mut(queue_id)
+        .unwrap()
+        .get_mut(&user_id)
+        .unwrap()
+        .queue_enter_time = Some(queue_enter_time);
+    let queued_count = {
         let mut queued_players = data.queued_players.get_mut(&queue_id).unwrap();
-        queued_players.insert(user_id);
+        // Checked again under the lock since a concurrent join may have raced past the check above
+        if !queued_players.insert(user_id) {
+            return Err(locale::text(&data, queue_id, Message::AlreadyQueued));
+        }
+        queued_players.len() as u32
+    };
+    if !is_bot {
+        notify_queue_subscribers(
+            &data,
+            http.clone(),
+            guild_id,
+            queue_id,
+            user_id,
+            queued_count - 1,
+            queued_count,
+        );
     }
     let party_id = {
         let mut global_player_data = data.global_player_data.lock().unwrap();
         let global_player_data = global_player_data
+            .entry(guild_id)
+            .or_default()
             .entry(user_id)
             .or_insert(GlobalPlayerData::default());
 
@@ -624,6 +1312,189 @@ async fn try_queue_player(
     Ok(())
 }
 
+fn rating_in_bounds(config: &QueueConfiguration, rating: Option<f64>) -> bool {
+    let Some(rating) = rating else {
+        return config.allow_unrated_players
+            || (config.mmr_floor.is_none() && config.mmr_ceiling.is_none());
+    };
+    config.mmr_floor.is_none_or(|floor| rating >= floor)
+        && config.mmr_ceiling.is_none_or(|ceiling| rating <= ceiling)
+}
+
+/// Refuses joining a queue while queued in another one if either queue only allows being in one
+/// queue at a time
+fn check_multi_queue(
+    data: &Data,
+    queue_id: &QueueUuid,
+    user_id: UserId,
+    guild_id: GuildId,
+) -> Result<(), String> {
+    let guild_queues = data
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .map(|guild_data| guild_data.queues.clone())
+        .unwrap_or_default();
+    let allows_multi_queue =
+        |queue: &QueueUuid| data.configuration.get(queue).unwrap().allow_multi_queue;
+    let conflicting_queue = guild_queues.iter().position(|queue| {
+        queue != queue_id
+            && data
+                .queued_players
+                .get(queue)
+                .is_some_and(|queued_players| queued_players.contains(&user_id))
+            && !(allows_multi_queue(queue) && allows_multi_queue(queue_id))
+    });
+    match conflicting_queue {
+        Some(queue_idx) => Err(locale::text(
+            data,
+            queue_id,
+            Message::QueuedElsewhere(queue_idx),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Refuses accounts or guild memberships newer than the queue allows unless the member has the
+/// exempt role
+fn check_account_age(
+    data: &Data,
+    queue_id: &QueueUuid,
+    member: &serenity::Member,
+) -> Result<(), String> {
+    let (exempt_role, min_account_age_days, min_guild_member_days) = {
+        let config = data.configuration.get(queue_id).unwrap();
+        (
+            config.account_age_exempt_role,
+            config.min_account_age_days,
+            config.min_guild_member_days,
+        )
+    };
+    if exempt_role.is_some_and(|role| member.roles.contains(&role)) {
+        return Ok(());
+    }
+    let now = Utc::now().timestamp();
+    let days_since = |timestamp: serenity::Timestamp| (now - timestamp.unix_timestamp()) / 86400;
+    if days_since(member.user.id.created_at()) < min_account_age_days as i64 {
+        return Err(locale::text(
+            data,
+            queue_id,
+            Message::AccountTooNew(min_account_age_days),
+        ));
+    }
+    if min_guild_member_days > 0
+        && member
+            .joined_at
+            .is_none_or(|joined_at| days_since(joined_at) < min_guild_member_days as i64)
+    {
+        return Err(locale::text(
+            data,
+            queue_id,
+            Message::GuildMembershipTooNew(min_guild_member_days),
+        ));
+    }
+    Ok(())
+}
+
+/// Refuses players whose rating is outside the queue's mmr bounds, pointing them to queues they can join
+fn check_mmr_bounds(
+    data: &Data,
+    queue_id: &QueueUuid,
+    user_id: UserId,
+    guild_id: GuildId,
+) -> Result<(), String> {
+    let queue_rating = |queue: &QueueUuid| {
+        data.player_data
+            .get(queue)
+            .unwrap()
+            .get(&user_id)
+            .and_then(|player| player.rating)
+            .map(|rating| rating.rating)
+    };
+    if rating_in_bounds(
+        &data.configuration.get(queue_id).unwrap(),
+        queue_rating(queue_id),
+    ) {
+        return Ok(());
+    }
+    let queues = data
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .unwrap()
+        .queues
+        .clone();
+    let suitable_queues = queues
+        .iter()
+        .enumerate()
+        .filter(|(_, queue)| *queue != queue_id)
+        .filter(|(_, queue)| {
+            rating_in_bounds(&data.configuration.get(queue).unwrap(), queue_rating(queue))
+        })
+        .map(|(idx, _)| idx.to_string())
+        .join(", ");
+    Err(locale::text(
+        data,
+        queue_id,
+        Message::MmrOutOfBounds((!suitable_queues.is_empty()).then_some(&suitable_queues)),
+    ))
+}
+
+/// DMs players subscribed with `/queue_notify` once the queue count goes from `previous_count` to
+/// `queued_count` past the point where it's close to popping. Subscribers are only messaged once.
+fn notify_queue_subscribers(
+    data: &Data,
+    http: Arc<Http>,
+    guild_id: GuildId,
+    queue_id: &QueueUuid,
+    queued_user: UserId,
+    previous_count: u32,
+    queued_count: u32,
+) {
+    let (total_player_count, notify_offset) = {
+        let config = data.configuration.get(queue_id).unwrap();
+        (
+            config.team_size * config.team_count,
+            config.queue_notify_offset,
+        )
+    };
+    let threshold = total_player_count.saturating_sub(notify_offset);
+    let subscribers = {
+        let mut subscribers = data.queue_notify_subscribers.entry(*queue_id).or_default();
+        subscribers.remove(&queued_user);
+        if !(previous_count < threshold && threshold <= queued_count) {
+            return;
+        }
+        std::mem::take(&mut *subscribers)
+    };
+    let queue_idx = data
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .and_then(|guild_data| guild_data.queues.iter().position(|queue| queue == queue_id))
+        .unwrap_or_default();
+    // Slow DMs shouldn't hold up joining the queue
+    tokio::spawn(async move {
+        for subscriber in subscribers {
+            if let Err(err) = subscriber
+                .direct_message(
+                    http.clone(),
+                    CreateMessage::new().content(format!(
+                        "Queue {} needs {} more player(s) to pop!",
+                        queue_idx, notify_offset
+                    )),
+                )
+                .await
+            {
+                eprintln!("Could not send queue notification: {}", err);
+            }
+        }
+    });
+}
+
 async fn ensure_wants_queue(
     data: Arc<Data>,
     http: Arc<Http>,
@@ -634,20 +1505,42 @@ async fn ensure_wants_queue(
     if !data.queued_players.get(&queue_id).unwrap().contains(user) {
         return Ok(true);
     }
-    match data
-        .global_player_data
-        .lock()
+    if is_fake_player(user) {
+        player_leave_queue(data.clone(), *user, false, queue_id);
+        return Ok(true);
+    }
+    let Some(guild_id) = queue_guild(&data, queue_id) else {
+        return Ok(true);
+    };
+    let snoozed = {
+        let global_player_data = data.global_player_data.lock().unwrap();
+        let player = global_player_data
+            .get(&guild_id)
+            .unwrap()
+            .get(user)
+            .unwrap();
+        match player.queue_state {
+            QueueState::Queued(_, current_queue_enter_time)
+                if current_queue_enter_time != queue_enter_time =>
+            {
+                return Ok(true);
+            }
+            _ => {}
+        }
+        player.is_snoozed()
+    };
+    if snoozed || in_reconnect_grace(&data, queue_id) {
+        return Ok(false);
+    }
+    // Repeated afk checks would hint to shadow banned players that they are never matched
+    if data
+        .player_bans
+        .get(queue_id)
         .unwrap()
         .get(user)
-        .unwrap()
-        .queue_state
+        .is_some_and(|ban| ban.shadow_ban)
     {
-        QueueState::Queued(_, current_queue_enter_time)
-            if current_queue_enter_time != queue_enter_time =>
-        {
-            return Ok(true);
-        }
-        _ => {}
+        return Ok(false);
     }
     let leaver_message_content =
         format!("# Are you still wanting to queue {}?\nEnds <t:{}:R>, otherwise you will be kicked from queue", user.mention(), 
@@ -657,15 +1550,44 @@ async fn ensure_wants_queue(
             .get(&queue_id)
             .unwrap()
             .leaver_verification_time as u64);
-    let leaver_message = CreateMessage::default()
-        .content(leaver_message_content)
-        .components(vec![CreateActionRow::Buttons(vec![
-            ButtonData::QueueCheck.get_button(),
-            ButtonData::AfkLeaveQueue(queue_id.clone()).get_button(),
-        ])]);
-    let Ok(leaver_message) = user.direct_message(http.clone(), leaver_message).await else {
-        data.queued_players
-            .get_mut(&queue_id)
+    let (afk_check_in_channel, queue_channel) = {
+        let config = data.configuration.get(queue_id).unwrap();
+        (config.afk_check_in_channel, config.queue_channel())
+    };
+    let dm_result = if afk_check_in_channel {
+        None
+    } else {
+        let leaver_message = CreateMessage::default()
+            .content(leaver_message_content.clone())
+            .components(vec![CreateActionRow::Buttons(vec![
+                ButtonData::QueueCheck.get_button(),
+                ButtonData::AfkLeaveQueue(*queue_id).get_button(),
+            ])]);
+        Some(user.direct_message(http.clone(), leaver_message).await)
+    };
+    // Players with closed DMs are asked in the queue channel instead
+    let leaver_message = match (dm_result, queue_channel) {
+        (Some(Ok(leaver_message)), _) => Ok(leaver_message),
+        (_, Some(queue_channel)) => {
+            queue_channel
+                .send_message(
+                    http.clone(),
+                    CreateMessage::default()
+                        .content(leaver_message_content)
+                        .allowed_mentions(CreateAllowedMentions::new().users(vec![*user]))
+                        .components(vec![CreateActionRow::Buttons(vec![
+                            ButtonData::ChannelQueueCheck(*user).get_button(),
+                            ButtonData::ChannelAfkLeaveQueue(*queue_id, *user).get_button(),
+                        ])]),
+                )
+                .await
+        }
+        (Some(Err(err)), None) => Err(err),
+        (None, None) => Err(serenity::Error::Other("No queue channel for afk check")),
+    };
+    let Ok(leaver_message) = leaver_message else {
+        data.queued_players
+            .get_mut(&queue_id)
             .unwrap()
             .remove(&user);
         data.message_edit_notify
@@ -693,7 +1615,7 @@ async fn ensure_wants_queue(
             else {
                 return;
             };
-            player_leave_queue(data.clone(), user, true, &queue_id);
+            party_leave_queue(data.clone(), &ctx1, user, &queue_id).await;
             message
                 .edit(
                     ctx1.clone(),
@@ -713,17 +1635,31 @@ async fn ensure_wants_queue(
 enum ButtonData {
     JoinParty(GroupUuid),
     RejectParty(GroupUuid),
+    JoinPartyLink(GroupUuid, u64),
     LeaverCheck(UserId),
+    LeaverAppeal(QueueUuid),
     QueueCheck,
+    /// Afk check posted in the queue channel for a player, who's the only one that can answer it
+    ChannelQueueCheck(UserId),
     Queue,
+    /// Joins a queue after a partied player picked whether to bring their party
+    QueueWithParty(QueueUuid, bool),
     Register(String, f64),
     LeaveQueue,
     AfkLeaveQueue(QueueUuid),
+    ChannelAfkLeaveQueue(QueueUuid, UserId),
     Status,
     RoleSelect,
+    PreferenceSelect(String),
     VolunteerHost,
     MapVote(String),
+    MapVoteSelect,
+    MapReroll,
     ResultVote(MatchResult),
+    MvpVote(usize),
+    DraftPick(QueueUuid),
+    /// Surrenders for a team, given with the player that left it
+    Surrender(usize, UserId),
 }
 
 impl ButtonData {
@@ -731,26 +1667,47 @@ impl ButtonData {
         ron::ser::to_string(self).unwrap()
     }
 
+    fn from_id(custom_id: &str) -> Option<Self> {
+        ron::de::from_str(custom_id).ok()
+    }
+
     fn get_button(&self) -> serenity::CreateButton {
         use serenity::ButtonStyle;
         let button = serenity::CreateButton::new(self.get_id());
         match self {
             ButtonData::JoinParty(_) => button.label("Join party").style(ButtonStyle::Success),
             ButtonData::RejectParty(_) => button.label("Reject invite").style(ButtonStyle::Danger),
+            ButtonData::JoinPartyLink(..) => button.label("Join party").style(ButtonStyle::Success),
             ButtonData::LeaverCheck(_) => button.label("No, I'm here.").style(ButtonStyle::Primary),
-            ButtonData::QueueCheck => button.label("Yes, I'm here.").style(ButtonStyle::Primary),
+            ButtonData::LeaverAppeal(_) => button.label("Appeal").style(ButtonStyle::Secondary),
+            ButtonData::QueueCheck | ButtonData::ChannelQueueCheck(_) => {
+                button.label("Yes, I'm here.").style(ButtonStyle::Primary)
+            }
             ButtonData::Queue => button.label("Join Queue").style(ButtonStyle::Primary),
+            ButtonData::QueueWithParty(_, true) => {
+                button.label("Queue party").style(ButtonStyle::Primary)
+            }
+            ButtonData::QueueWithParty(_, false) => button
+                .label("Leave party and queue solo")
+                .style(ButtonStyle::Secondary),
             ButtonData::Register(label, _mmr) => button.label(label).style(ButtonStyle::Secondary),
             ButtonData::LeaveQueue => button.label("Leave Queue").style(ButtonStyle::Danger),
-            ButtonData::AfkLeaveQueue(_) => {
+            ButtonData::AfkLeaveQueue(_) | ButtonData::ChannelAfkLeaveQueue(..) => {
                 button.label("No, exit queue.").style(ButtonStyle::Primary)
             }
             ButtonData::Status => button.label("Status").style(ButtonStyle::Secondary),
             ButtonData::RoleSelect => panic!("Invalid conversion from role select to button"),
+            ButtonData::PreferenceSelect(_) => {
+                panic!("Invalid conversion from preference select to button")
+            }
             ButtonData::VolunteerHost => button
                 .label("Volunteer to host")
                 .style(ButtonStyle::Primary),
             ButtonData::MapVote(map) => button.label(map).style(ButtonStyle::Secondary),
+            ButtonData::MapVoteSelect => {
+                panic!("Invalid conversion from map vote select to button")
+            }
+            ButtonData::MapReroll => button.label("Reroll map").style(ButtonStyle::Secondary),
             ButtonData::ResultVote(match_result) => match match_result {
                 MatchResult::Team(team) => button
                     .label(format!("Team {}", team + 1))
@@ -758,6 +1715,9 @@ impl ButtonData {
                 MatchResult::Tie => button.label("Tie").style(ButtonStyle::Secondary),
                 MatchResult::Cancel => button.label("Cancel").style(ButtonStyle::Danger),
             },
+            ButtonData::MvpVote(_) => panic!("Invalid conversion from mvp vote to button"),
+            ButtonData::DraftPick(_) => panic!("Invalid conversion from draft pick to button"),
+            ButtonData::Surrender(..) => button.label("Surrender").style(ButtonStyle::Danger),
         }
     }
 
@@ -769,183 +1729,114 @@ impl ButtonData {
     ) -> Result<(), Error> {
         match self {
             ButtonData::JoinParty(party_uuid) => {
-                if let Err(e) = {
-                    let mut player_data = data.global_player_data.lock().unwrap();
-                    let player_data = player_data
-                        .entry(message_component.user.id)
-                        .or_insert(GlobalPlayerData::default());
-                    match player_data.queue_state {
-                        QueueState::None => Ok(()),
-                        QueueState::Queued(..) => Err("Cannot join party while queued!"),
-                        QueueState::InGame => Err("Cannot join party while in game!"),
-                    }
-                } {
+                join_party(message_component, data, ctx, party_uuid, None).await
+            }
+            ButtonData::JoinPartyLink(party_uuid, nonce) => {
+                join_party(message_component, data, ctx, party_uuid, Some(nonce)).await
+            }
+            ButtonData::RejectParty(party_uuid) => {
+                reject_party(message_component, data, ctx, party_uuid).await
+            }
+            ButtonData::LeaverCheck(player) => {
+                if message_component.user.id != player {
                     message_component
                         .create_response(
                             ctx,
                             serenity::CreateInteractionResponse::Message(
-                                CreateInteractionResponseMessage::new().content(format!("{}", e)),
+                                CreateInteractionResponseMessage::new()
+                                    .content(format!("You aren't the right player silly :P"))
+                                    .ephemeral(true),
                             ),
                         )
                         .await?;
                     return Ok(());
                 }
-                let group_members = 'group_members: {
-                    let mut group_data = data.group_data.lock().unwrap();
-                    let party = group_data.get_mut(&party_uuid);
-                    let Some(party) = party else {
-                        break 'group_members Err("Party no longer exists.");
-                    };
-                    if !party.pending_invites.remove(&message_component.user.id) {
-                        break 'group_members Err("Party invite no longer valid.");
-                    }
-                    party.players.insert(message_component.user.id);
-                    Ok(party.players.clone())
-                };
-                let group_members = match group_members {
-                    Ok(group_members) => group_members,
-                    Err(e) => {
-                        message_component
-                            .create_response(
-                                ctx,
-                                serenity::CreateInteractionResponse::Message(
-                                    CreateInteractionResponseMessage::new()
-                                        .content(format!("{}", e)),
-                                ),
-                            )
-                            .await?;
-                        return Ok(());
-                    }
-                };
-                let old_party = {
-                    let mut player_data = data.global_player_data.lock().unwrap();
-                    let player_data = player_data
-                        .entry(message_component.user.id)
-                        .or_insert(GlobalPlayerData::default());
-                    let old_party = player_data.party;
-                    player_data.party = Some(party_uuid);
-                    old_party
-                };
-                if let Some(old_party) = old_party {
-                    if old_party != party_uuid {
-                        leave_party(
-                            data,
-                            &message_component.user.id,
-                            Arc::new(ctx.http()),
-                            old_party,
-                        )
-                        .await?;
-                    }
-                }
-
-                for group_member in group_members {
-                    if group_member == message_component.user.id {
-                        continue;
-                    }
-                    group_member
-                        .direct_message(
-                            ctx,
-                            CreateMessage::new().content(format!(
-                                "{} joined your party!",
-                                message_component.user.id.mention()
-                            )),
-                        )
-                        .await?;
-                }
-                message_component.message.delete(ctx).await?;
-                message_component
-                    .create_response(
+                let (del_future, resp_future) = future::join(
+                    message_component.message.delete(ctx),
+                    message_component.create_response(
                         ctx,
                         serenity::CreateInteractionResponse::Message(
                             CreateInteractionResponseMessage::new()
-                                .content(format!("Joined party!"))
+                                .content(format!("You are no longer marked as a leaver."))
                                 .ephemeral(true),
                         ),
-                    )
-                    .await?;
+                    ),
+                )
+                .await;
+                del_future?;
+                resp_future?;
                 Ok(())
             }
-            ButtonData::RejectParty(party_uuid) => {
-                let group_members = {
-                    let mut group_data = data.group_data.lock().unwrap();
-                    let party = group_data.get_mut(&party_uuid);
-                    if let Some(party) = party {
-                        party.pending_invites.remove(&message_component.user.id);
-                        Some(party.players.clone())
-                    } else {
-                        None
-                    }
-                };
-                let Some(group_members) = group_members else {
+            ButtonData::LeaverAppeal(queue_uuid) => {
+                let audit_channel = data
+                    .configuration
+                    .get(&queue_uuid)
+                    .and_then(|config| config.audit_channel);
+                let Some(audit_channel) = audit_channel else {
                     message_component
                         .create_response(
                             ctx,
                             serenity::CreateInteractionResponse::Message(
                                 CreateInteractionResponseMessage::new()
-                                    .content(format!("Party no longer exists.")),
+                                    .content("There is no moderator channel to send appeals to.")
+                                    .ephemeral(true),
                             ),
                         )
                         .await?;
                     return Ok(());
                 };
-                for group_member in group_members {
-                    if group_member == message_component.user.id {
-                        continue;
-                    }
-                    group_member
-                        .direct_message(
-                            ctx,
-                            CreateMessage::new().content(format!(
-                                "{} rejected your party invite",
-                                message_component.user.id.mention()
-                            )),
-                        )
-                        .await?;
-                }
-                message_component.message.delete(ctx).await?;
+                // Appeals are sent from DMs, so the queue's guild gives its index
+                let queue_idx = queue_guild(&data, &queue_uuid)
+                    .and_then(|guild_id| {
+                        data.guild_data
+                            .lock()
+                            .unwrap()
+                            .get(&guild_id)?
+                            .queues
+                            .iter()
+                            .position(|queue| *queue == queue_uuid)
+                    })
+                    .unwrap_or_default();
+                audit_channel
+                    .send_message(
+                        ctx,
+                        CreateMessage::new()
+                            .content(format!(
+                                "{} is appealing a leaver report. Use `/approve_leaver_appeal` with queue index {} to remove it.",
+                                message_component.user.id.mention(),
+                                queue_idx
+                            ))
+                            .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
+                    )
+                    .await?;
                 message_component
                     .create_response(
                         ctx,
-                        serenity::CreateInteractionResponse::Message(
+                        serenity::CreateInteractionResponse::UpdateMessage(
                             CreateInteractionResponseMessage::new()
-                                .content(format!("Rejected party invite."))
-                                .ephemeral(true),
+                                .content("Your appeal has been sent to the moderators.")
+                                .components(vec![]),
                         ),
                     )
                     .await?;
                 Ok(())
             }
-            ButtonData::LeaverCheck(player) => {
-                if message_component.user.id != player {
-                    message_component
-                        .create_response(
-                            ctx,
-                            serenity::CreateInteractionResponse::Message(
-                                CreateInteractionResponseMessage::new()
-                                    .content(format!("You aren't the right player silly :P"))
-                                    .ephemeral(true),
-                            ),
-                        )
-                        .await?;
-                    return Ok(());
-                }
-                let (del_future, resp_future) = future::join(
-                    message_component.message.delete(ctx),
-                    message_component.create_response(
-                        ctx,
-                        serenity::CreateInteractionResponse::Message(
+            ButtonData::ChannelQueueCheck(user) | ButtonData::ChannelAfkLeaveQueue(_, user)
+                if user != message_component.user.id =>
+            {
+                message_component
+                    .create_response(
+                        ctx.http(),
+                        CreateInteractionResponse::Message(
                             CreateInteractionResponseMessage::new()
-                                .content(format!("You are no longer marked as a leaver."))
+                                .content("This afk check is for someone else")
                                 .ephemeral(true),
                         ),
-                    ),
-                )
-                .await;
-                del_future?;
-                resp_future?;
+                    )
+                    .await?;
                 Ok(())
             }
-            ButtonData::QueueCheck => {
+            ButtonData::QueueCheck | ButtonData::ChannelQueueCheck(_) => {
                 message_component.message.delete(ctx).await?;
                 message_component
                     .create_response(
@@ -973,47 +1864,65 @@ impl ButtonData {
                         .await?;
                     return Ok(());
                 };
-                message_component.defer_ephemeral(ctx.http()).await?;
-                match try_queue_player(
-                    data.clone(),
-                    &queue,
-                    message_component.user.id,
-                    ctx.http.clone(),
+                let party_members = get_party_members(
+                    &data,
                     message_component.guild_id.unwrap(),
-                    true,
-                    false,
-                )
-                .await
-                {
-                    Ok(()) => {
-                        message_component
-                            .edit_response(
-                                ctx.http(),
-                                EditInteractionResponse::new().content("Joined queue!"),
-                            )
-                            .await?;
-                        data.message_edit_notify
-                            .get_mut(&queue)
-                            .unwrap()
-                            .notify_one();
-                        matchmake(
+                    message_component.user.id,
+                );
+                if party_members.len() > 1 {
+                    message_component
+                        .create_response(
+                            ctx.http(),
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content(format!(
+                                        "You're in a party with {}. Queueing the party will queue: {}",
+                                        party_members
+                                            .iter()
+                                            .filter(|member| **member != message_component.user.id)
+                                            .map(|member| member.mention())
+                                            .join(", "),
+                                        party_members.iter().map(|member| member.mention()).join(", ")
+                                    ))
+                                    .components(vec![CreateActionRow::Buttons(vec![
+                                        ButtonData::QueueWithParty(queue, true).get_button(),
+                                        ButtonData::QueueWithParty(queue, false).get_button(),
+                                    ])])
+                                    .allowed_mentions(CreateAllowedMentions::new().empty_users())
+                                    .ephemeral(true),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+                message_component.defer_ephemeral(ctx.http()).await?;
+                join_queue_from_interaction(message_component, data, ctx, queue, false).await
+            }
+            ButtonData::QueueWithParty(queue, with_party) => {
+                message_component.defer(ctx.http()).await?;
+                let guild_id = message_component.guild_id.unwrap();
+                if !with_party {
+                    let old_party = data
+                        .global_player_data
+                        .lock()
+                        .unwrap()
+                        .entry(guild_id)
+                        .or_default()
+                        .entry(message_component.user.id)
+                        .or_default()
+                        .party
+                        .take();
+                    if let Some(old_party) = old_party {
+                        leave_party(
                             data.clone(),
+                            &message_component.user.id,
                             ctx.http.clone(),
-                            message_component.guild_id.unwrap(),
-                            &queue,
+                            old_party,
                         )
                         .await?;
                     }
-                    Err(reason) => {
-                        message_component
-                            .edit_response(
-                                ctx.http(),
-                                EditInteractionResponse::new().content(reason),
-                            )
-                            .await?;
-                    }
                 }
-                Ok(())
+                join_queue_from_interaction(message_component, data, ctx, queue, with_party).await
             }
             ButtonData::Register(_, register_mmr) => {
                 let Some(queue) = get_queue(data.clone(), message_component) else {
@@ -1029,23 +1938,31 @@ impl ButtonData {
                         .await?;
                     return Ok(());
                 };
-                let default_uncertainty = data
+                let rating = data
                     .configuration
                     .get(&queue)
                     .unwrap()
-                    .default_player_data
-                    .rating
-                    .uncertainty;
+                    .registration_rating(register_mmr);
                 data.player_data
                     .get_mut(&queue)
                     .unwrap()
                     .entry(message_component.user.id)
                     .or_default()
-                    .rating = Some(WengLinRating {
-                    rating: register_mmr,
-                    uncertainty: default_uncertainty,
-                });
-                message_component.create_response(ctx.http(), CreateInteractionResponse::Message(CreateInteractionResponseMessage::new().content("Registered!"))).await?;
+                    .rating = Some(rating);
+                let hide_mmr = data.configuration.get(&queue).unwrap().hide_mmr;
+                let response = match format_rating(
+                    &rating,
+                    hide_mmr,
+                    message_component
+                        .member
+                        .as_ref()
+                        .and_then(|member| member.permissions),
+                    false,
+                ) {
+                    Some(rating) => format!("Registered with mmr {}!", rating),
+                    None => "Registered!".to_string(),
+                };
+                message_component.create_response(ctx.http(), CreateInteractionResponse::Message(CreateInteractionResponseMessage::new().content(response))).await?;
                 if let Some(role) = data.configuration.get(&queue).unwrap().register_role {
                     if let Err(_) = message_component
                         .member
@@ -1080,7 +1997,7 @@ impl ButtonData {
                     return Ok(());
                 };
                 let response =
-                    player_leave_queue(data.clone(), message_component.user.id, true, &queue);
+                    party_leave_queue(data.clone(), ctx, message_component.user.id, &queue).await;
                 message_component
                     .create_response(
                         ctx.http(),
@@ -1093,9 +2010,11 @@ impl ButtonData {
                     .await?;
                 Ok(())
             }
-            ButtonData::AfkLeaveQueue(queue_uuid) => {
+            ButtonData::AfkLeaveQueue(queue_uuid)
+            | ButtonData::ChannelAfkLeaveQueue(queue_uuid, _) => {
                 let response =
-                    player_leave_queue(data.clone(), message_component.user.id, true, &queue_uuid);
+                    party_leave_queue(data.clone(), ctx, message_component.user.id, &queue_uuid)
+                        .await;
                 message_component
                     .create_response(
                         ctx.http(),
@@ -1125,17 +2044,42 @@ impl ButtonData {
                 };
                 let player_state = {
                     let mut global_data = data.global_player_data.lock().unwrap();
-                    let player_data = global_data.entry(message_component.user.id).or_default();
+                    let player_data = global_data
+                        .entry(message_component.guild_id.unwrap())
+                        .or_default()
+                        .entry(message_component.user.id)
+                        .or_default();
                     player_data.queue_state.clone()
                 };
                 let response = match player_state {
-                    QueueState::None => "You are not in queue".to_string(),
-                    QueueState::Queued(id, q_entry_time) if id == queue => format!(
-                        "You'e been in queue since <t:{}:R>.",
-                        q_entry_time.timestamp()
+                    QueueState::None => locale::text(&data, &queue, Message::StatusNotQueued),
+                    QueueState::Queued(id, q_entry_time) if id == queue => locale::text(
+                        &data,
+                        &queue,
+                        Message::StatusQueued(q_entry_time.timestamp()),
                     ),
-                    QueueState::Queued(..) => "You are queued in a different queue.".to_string(),
-                    QueueState::InGame => "You are in a game.".to_string(),
+                    QueueState::Queued(id, _) => {
+                        let queue_idx = data
+                            .guild_data
+                            .lock()
+                            .unwrap()
+                            .get(&message_component.guild_id.unwrap())
+                            .and_then(|guild_data| {
+                                guild_data.queues.iter().position(|queue| *queue == id)
+                            });
+                        locale::text(&data, &queue, Message::StatusQueuedElsewhere(queue_idx))
+                    }
+                    QueueState::InGame(match_id) => {
+                        let match_data = data.match_data.lock().unwrap();
+                        let message = match match_data.get(&match_id) {
+                            Some(match_data) => Message::StatusInMatch(
+                                &match_data.name,
+                                *match_data.channels.last().unwrap(),
+                            ),
+                            None => Message::StatusInGame,
+                        };
+                        locale::text(&data, &queue, message)
+                    }
                 };
                 message_component
                     .create_response(
@@ -1185,6 +2129,46 @@ impl ButtonData {
                     .await?;
                 Ok(())
             }
+            ButtonData::PreferenceSelect(dimension) => {
+                let Some(queue) = get_queue(data.clone(), message_component) else {
+                    message_component
+                        .create_response(
+                            ctx.http(),
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content("Could not find queue!")
+                                    .ephemeral(true),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                };
+                let ComponentInteractionDataKind::StringSelect { values } =
+                    message_component.data.kind.clone()
+                else {
+                    return Err("Invalid type for preference select repsonse".into());
+                };
+                {
+                    let mut players_data = data.player_data.get_mut(&queue).unwrap();
+                    let player_data = players_data.entry(message_component.user.id).or_default();
+                    if values.is_empty() {
+                        player_data.preferences.remove(&dimension);
+                    } else {
+                        player_data.preferences.insert(dimension.clone(), values);
+                    }
+                }
+                message_component
+                    .create_response(
+                        ctx.http(),
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!("{} preference configured!", dimension))
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await?;
+                Ok(())
+            }
             ButtonData::VolunteerHost => {
                 let match_number = {
                     let match_channels = data.match_channels.lock().unwrap();
@@ -1241,74 +2225,353 @@ impl ButtonData {
                     .await?;
                 Ok(())
             }
-            ButtonData::MapVote(map) => {
+            ButtonData::MapVote(map) => record_map_vote(message_component, data, ctx, map).await,
+            ButtonData::MapVoteSelect => {
+                let ComponentInteractionDataKind::StringSelect { values } =
+                    message_component.data.kind.clone()
+                else {
+                    return Err("Invalid type for map vote select repsonse".into());
+                };
+                let Some(map) = values.into_iter().next() else {
+                    return Ok(());
+                };
+                record_map_vote(message_component, data, ctx, map).await
+            }
+            ButtonData::MvpVote(team_idx) => {
+                let ComponentInteractionDataKind::StringSelect { values } =
+                    message_component.data.kind.clone()
+                else {
+                    return Err("Invalid type for mvp vote repsonse".into());
+                };
+                let Some(vote) = values.first().and_then(|vote| vote.parse::<u64>().ok()) else {
+                    return Err("Invalid mvp vote".into());
+                };
+                let vote = UserId::new(vote);
                 let match_number = {
                     let match_channels = data.match_channels.lock().unwrap();
                     match_channels.get(&message_component.channel_id).cloned()
                 };
                 let Some(match_number) = match_number else {
-                    return Err("Invalid state for volunteer host interaction".into());
+                    return Err("Invalid state for mvp vote interaction".into());
                 };
-
-                let (vote_result, mut content) = {
+                let response = {
                     let mut match_data = data.match_data.lock().unwrap();
                     let Some(match_data) = match_data.get_mut(&match_number) else {
                         return Ok(());
                     };
-                    let required_votes = {
-                        let config = data.configuration.get(&match_data.queue).unwrap();
-                        config.team_count * config.team_size / 2 + 1
-                    };
-                    match_data
-                        .map_votes
-                        .insert(message_component.user.id, map.to_string());
-
-                    let votes = match_data
-                        .map_votes
-                        .iter()
-                        .map(|(_, vote)| vote)
-                        .counts()
-                        .into_iter()
-                        .sorted_by_key(|(_, count)| *count)
-                        .rev()
-                        .collect_vec();
-                    let content = format!(
-                        "# Map Vote{}{}",
-                        match_data
-                            .map_vote_end_time
-                            .map(|map_vote_end_time| format!("\nEnds <t:{}:R>", map_vote_end_time))
-                            .unwrap_or("".to_string()),
-                        votes
-                            .iter()
-                            .map(|(vote_type, count)| format!("\n{}: {}", vote_type, count))
-                            .join("")
-                    );
-                    (
-                        votes
-                            .into_iter()
-                            .next()
-                            .filter(|(_, count)| *count >= required_votes as usize)
-                            .map(|(vote_type, _)| vote_type.clone()),
-                        content,
-                    )
+                    let voter = message_component.user.id;
+                    if !match_data
+                        .members
+                        .get(team_idx)
+                        .is_some_and(|team| team.contains(&voter))
+                    {
+                        "You can only vote for the MVP of your own team".to_string()
+                    } else if vote == voter {
+                        "You can't vote for yourself".to_string()
+                    } else {
+                        match_data.mvp_votes.insert(voter, vote);
+                        format!("Voted for {} as MVP", vote.mention())
+                    }
                 };
-                if let Some(vote_result) = vote_result {
-                    ctx.http
-                        .clone()
-                        .get_message(message_component.channel_id, message_component.message.id)
-                        .await?
-                        .edit(ctx.http.clone(), EditMessage::new().components(vec![]))
-                        .await?;
-                    content = format!("# Map: {}", vote_result);
-                }
-                ctx.http
-                    .clone()
-                    .get_message(message_component.channel_id, message_component.message.id)
-                    .await?
-                    .edit(ctx.http.clone(), EditMessage::new().content(content))
+                message_component
+                    .create_response(
+                        ctx.http(),
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(response)
+                                .ephemeral(true),
+                        ),
+                    )
                     .await?;
                 Ok(())
             }
+            ButtonData::DraftPick(queue_id) => {
+                let ComponentInteractionDataKind::StringSelect { values } =
+                    message_component.data.kind.clone()
+                else {
+                    return Err("Invalid type for draft pick repsonse".into());
+                };
+                let Some(pick) = values.first().and_then(|pick| pick.parse::<u64>().ok()) else {
+                    return Err("Invalid draft pick".into());
+                };
+                let pick = UserId::new(pick);
+                let response = {
+                    match data.drafts.get_mut(&queue_id) {
+                        None => Err("This draft is over".to_string()),
+                        Some(mut draft) => {
+                            let picking_team = draft.picking_team();
+                            let captain = draft.teams[picking_team][0];
+                            let party = draft.pool.iter().position(|party| party.contains(&pick));
+                            if captain != message_component.user.id {
+                                Err(format!("It's {}'s turn to pick", captain.mention()))
+                            } else if let Some(idx) = party {
+                                if draft.can_pick(picking_team, idx) {
+                                    let party = draft.pool.remove(idx);
+                                    draft.teams[picking_team].extend(party);
+                                    // The last party has nowhere else to go
+                                    if draft.pool.len() == 1 {
+                                        let picking_team = draft.picking_team();
+                                        let last_pick = draft.pool.remove(0);
+                                        draft.teams[picking_team].extend(last_pick);
+                                    }
+                                    draft.pick_started = Instant::now();
+                                    let next_captain = draft.teams[draft.picking_team()][0];
+                                    Ok((
+                                        draft.content(),
+                                        draft.components(queue_id),
+                                        next_captain,
+                                        draft.pool.is_empty(),
+                                    ))
+                                } else {
+                                    Err("That party wouldn't leave room on the teams for everyone else"
+                                        .to_string())
+                                }
+                            } else {
+                                Err("That player has already been picked".to_string())
+                            }
+                        }
+                    }
+                };
+                match response {
+                    Ok((content, components, next_captain, complete)) => {
+                        message_component
+                            .create_response(
+                                ctx.http(),
+                                CreateInteractionResponse::UpdateMessage(
+                                    CreateInteractionResponseMessage::new()
+                                        .content(content)
+                                        .components(components)
+                                        .allowed_mentions(
+                                            CreateAllowedMentions::new().users(vec![next_captain]),
+                                        ),
+                                ),
+                            )
+                            .await?;
+                        if complete {
+                            matchmake(&data, &queue_id);
+                        }
+                    }
+                    Err(response) => {
+                        message_component
+                            .create_response(
+                                ctx.http(),
+                                CreateInteractionResponse::Message(
+                                    CreateInteractionResponseMessage::new()
+                                        .content(response)
+                                        .ephemeral(true),
+                                ),
+                            )
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+            ButtonData::MapReroll => {
+                let match_number = {
+                    let match_channels = data.match_channels.lock().unwrap();
+                    match_channels.get(&message_component.channel_id).cloned()
+                };
+                let Some(match_number) = match_number else {
+                    return Err("Invalid state for map reroll interaction".into());
+                };
+                let Some((queue_id, members)) = ({
+                    let match_data = data.match_data.lock().unwrap();
+                    match_data
+                        .get(&match_number)
+                        .map(|match_data| (match_data.queue, match_data.members.clone()))
+                }) else {
+                    return Ok(());
+                };
+                let (max_map_rerolls, map_pool) = {
+                    let config = data.configuration.get(&queue_id).unwrap();
+                    (
+                        config.max_map_rerolls,
+                        match_map_pool(&data, &config, &queue_id, &members)
+                            .into_iter()
+                            .cloned()
+                            .collect_vec(),
+                    )
+                };
+                let response = {
+                    let mut match_data = data.match_data.lock().unwrap();
+                    let Some(match_data) = match_data.get_mut(&match_number) else {
+                        return Ok(());
+                    };
+                    let required_votes = match_data.members.iter().flatten().count() / 2 + 1;
+                    if !match_data
+                        .members
+                        .iter()
+                        .flatten()
+                        .contains(&message_component.user.id)
+                    {
+                        Err("Only players in this match can vote to reroll the map.")
+                    } else if match_data.map_rerolls >= max_map_rerolls {
+                        Err("This map can no longer be rerolled.")
+                    } else {
+                        match_data
+                            .map_reroll_votes
+                            .insert(message_component.user.id);
+                        if match_data.map_reroll_votes.len() >= required_votes {
+                            let new_map = map_pool
+                                .iter()
+                                .filter(|map| Some(*map) != match_data.map.as_ref())
+                                .collect_vec()
+                                .choose(&mut rand::thread_rng())
+                                .map(|map| map.to_string());
+                            if new_map.is_some() {
+                                match_data.map = new_map;
+                            }
+                            match_data.map_rerolls += 1;
+                            match_data.map_reroll_votes.clear();
+                        }
+                        let mut content =
+                            format!("# Map: {}", match_data.map.clone().unwrap_or_default());
+                        if match_data.map_rerolls > 0 {
+                            content += format!(
+                                "\nRerolled {}/{} times",
+                                match_data.map_rerolls, max_map_rerolls
+                            )
+                            .as_str();
+                        }
+                        if !match_data.map_reroll_votes.is_empty() {
+                            content += format!(
+                                "\nReroll votes: {}/{}",
+                                match_data.map_reroll_votes.len(),
+                                required_votes
+                            )
+                            .as_str();
+                        }
+                        let mut message = CreateInteractionResponseMessage::new().content(content);
+                        if match_data.map_rerolls >= max_map_rerolls {
+                            message = message.components(vec![]);
+                        }
+                        Ok(message)
+                    }
+                };
+                match response {
+                    Ok(message) => {
+                        message_component
+                            .create_response(ctx, CreateInteractionResponse::UpdateMessage(message))
+                            .await?;
+                    }
+                    Err(reason) => {
+                        message_component
+                            .create_response(
+                                ctx,
+                                CreateInteractionResponse::Message(
+                                    CreateInteractionResponseMessage::new()
+                                        .content(reason)
+                                        .ephemeral(true),
+                                ),
+                            )
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+            ButtonData::Surrender(team_idx, leaver) => {
+                let match_number = {
+                    let match_channels = data.match_channels.lock().unwrap();
+                    match_channels.get(&message_component.channel_id).cloned()
+                };
+                let Some(match_number) = match_number else {
+                    return Err("Invalid state for surrender interaction".into());
+                };
+                let response = {
+                    let mut match_data = data.match_data.lock().unwrap();
+                    let Some(match_data) = match_data.get_mut(&match_number) else {
+                        return Ok(());
+                    };
+                    let (team_names, surrender_votes_required) = {
+                        let config = data.configuration.get(&match_data.queue).unwrap();
+                        (
+                            config.team_labels(match_data.members.len()),
+                            config.surrender_votes_required,
+                        )
+                    };
+                    let team = match_data
+                        .members
+                        .get(team_idx)
+                        .cloned()
+                        .unwrap_or_default();
+                    if match_data.resolved {
+                        Err("This match is already over.")
+                    } else if message_component.user.id == leaver
+                        || !team.contains(&message_component.user.id)
+                    {
+                        Err("Only players left on this team can surrender.")
+                    } else {
+                        match_data.surrender_votes.insert(message_component.user.id);
+                        let required_votes = surrender_votes_required
+                            .min(team.len().saturating_sub(1) as u32)
+                            .max(1) as usize;
+                        let votes = team
+                            .iter()
+                            .filter(|player| match_data.surrender_votes.contains(player))
+                            .count();
+                        if votes >= required_votes {
+                            match_data.surrender_votes.clear();
+                            let result = surrender_result(team_idx, match_data.members.len());
+                            Ok((
+                                Some(result.clone()),
+                                CreateInteractionResponseMessage::new()
+                                    .content(format!(
+                                        "# {} surrendered\nResolving game as {}",
+                                        team_names[team_idx],
+                                        result.label(&team_names)
+                                    ))
+                                    .components(vec![]),
+                            ))
+                        } else {
+                            Ok((
+                                None,
+                                CreateInteractionResponseMessage::new().content(
+                                    surrender_offer_content(
+                                        &team_names[team_idx],
+                                        leaver,
+                                        votes,
+                                        required_votes,
+                                    ),
+                                ),
+                            ))
+                        }
+                    }
+                };
+                let result = match response {
+                    Ok((result, message)) => {
+                        message_component
+                            .create_response(ctx, CreateInteractionResponse::UpdateMessage(message))
+                            .await?;
+                        result
+                    }
+                    Err(reason) => {
+                        message_component
+                            .create_response(
+                                ctx,
+                                CreateInteractionResponse::Message(
+                                    CreateInteractionResponseMessage::new()
+                                        .content(reason)
+                                        .ephemeral(true),
+                                ),
+                            )
+                            .await?;
+                        None
+                    }
+                };
+                let Some(result) = result else {
+                    return Ok(());
+                };
+                record_game_result(
+                    data,
+                    ctx.http.clone(),
+                    Some(ctx.cache.clone()),
+                    message_component.guild_id.unwrap(),
+                    match_number,
+                    message_component.channel_id,
+                    result,
+                )
+                .await
+            }
             ButtonData::ResultVote(result) => {
                 let match_number = {
                     let match_channels = data.match_channels.lock().unwrap();
@@ -1317,14 +2580,60 @@ impl ButtonData {
                 let Some(match_number) = match_number else {
                     return Err("Invalid state for volunteer host interaction".into());
                 };
-                let (vote_result, content) = {
+                let cancel_cooldown_end = if result == MatchResult::Cancel {
+                    let queue = data
+                        .match_data
+                        .lock()
+                        .unwrap()
+                        .get(&match_number)
+                        .map(|match_data| match_data.queue);
+                    queue.and_then(|queue| {
+                        let cooldown_minutes = data
+                            .configuration
+                            .get(&queue)
+                            .unwrap()
+                            .cancel_vote_cooldown_minutes;
+                        let cooldown = cooldown_minutes as u64 * 60;
+                        let now = std::time::UNIX_EPOCH.elapsed().unwrap().as_secs();
+                        data.player_data
+                            .get(&queue)
+                            .unwrap()
+                            .get(&message_component.user.id)
+                            .and_then(|player| player.last_cancel_vote)
+                            .map(|last_cancel_vote| last_cancel_vote + cooldown)
+                            .filter(|cooldown_end| cooldown > 0 && *cooldown_end > now)
+                    })
+                } else {
+                    None
+                };
+                if let Some(cancel_cooldown_end) = cancel_cooldown_end {
+                    message_component
+                        .create_response(
+                            ctx.http(),
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content(format!(
+                                        "You voted to cancel a match recently, you can vote to cancel again <t:{}:R>",
+                                        cancel_cooldown_end
+                                    ))
+                                    .ephemeral(true),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+                let (vote_result, content, awaiting_proof_since) = {
                     let mut match_data = data.match_data.lock().unwrap();
                     let Some(match_data) = match_data.get_mut(&match_number) else {
                         return Ok(());
                     };
-                    let required_votes = {
+                    let (team_names, show_rosters, min_match_duration) = {
                         let config = data.configuration.get(&match_data.queue).unwrap();
-                        config.team_count * config.team_size / 2 + 1
+                        (
+                            config.team_labels(match_data.members.len()),
+                            config.show_rosters_in_result_vote,
+                            config.min_match_duration,
+                        )
                     };
                     match_data
                         .result_votes
@@ -1332,28 +2641,22 @@ impl ButtonData {
                     if match_data.resolved {
                         return Ok(());
                     }
-                    let votes = match_data
-                        .result_votes
-                        .iter()
-                        .map(|(_, vote)| vote)
-                        .counts()
-                        .into_iter()
-                        .sorted_by_key(|(_, count)| *count)
-                        .rev()
-                        .collect_vec();
-                    let content = votes
-                        .iter()
-                        .map(|(vote_type, count)| format!("{}: {}\n", vote_type, count))
-                        .join("");
-                    (
-                        votes
-                            .into_iter()
-                            .next()
-                            .filter(|(_, count)| *count >= required_votes as usize)
-                            .map(|(vote_type, _)| vote_type.clone()),
-                        content,
-                    )
+                    let lock_in_time = result_lock_in_time(match_data, min_match_duration);
+                    let (mut vote_result, mut content) =
+                        tally_result_votes(match_data, &team_names, show_rosters, lock_in_time);
+                    let awaiting_proof_since =
+                        hold_result_for_proof(&data, match_data, &mut vote_result, &mut content);
+                    (vote_result, content, awaiting_proof_since)
                 };
+                if let Some(since) = awaiting_proof_since {
+                    tokio::spawn(await_result_proof(
+                        data.clone(),
+                        ctx.http.clone(),
+                        match_number,
+                        message_component.channel_id,
+                        since,
+                    ));
+                }
                 let Some(vote_result) = vote_result else {
                     ctx.http
                         .clone()
@@ -1363,171 +2666,394 @@ impl ButtonData {
                         .await?;
                     return Ok(());
                 };
-                let (channels, players, queue_id, post_match_channel) = {
-                    let mut match_data = data.match_data.lock().unwrap();
-                    let Some(match_data) = match_data.get_mut(&match_number) else {
-                        return Ok(());
-                    };
-                    let post_match_channel = data
-                        .configuration
-                        .get(&match_data.queue)
-                        .unwrap()
-                        .post_match_channel
-                        .clone();
-                    match_data.resolved = true;
-                    log_match_results(data.clone(), &vote_result, &match_data);
-                    (
-                        match_data.channels.clone(),
-                        match_data.members.clone(),
-                        match_data.queue.clone(),
-                        post_match_channel,
-                    )
-                };
-                apply_match_results(data.clone(), vote_result.clone(), &players, queue_id);
-
-                let guild_id = message_component.guild_id.unwrap();
-                for player in players.iter().flat_map(|t| t) {
-                    data.global_player_data
-                        .lock()
-                        .unwrap()
-                        .get_mut(player)
-                        .unwrap()
-                        .queue_state = QueueState::None;
-                }
-                data.message_edit_notify
-                    .get_mut(&queue_id)
-                    .unwrap()
-                    .notify_one();
-                if let Some(post_match_channel) = post_match_channel {
-                    future::join_all(
-                        players
-                            .iter()
-                            .flat_map(|t| t)
-                            .filter(|player| {
-                                if let Some(Some(current_vc)) = guild_id
-                                    .to_guild_cached(&ctx.cache)
-                                    .unwrap()
-                                    .voice_states
-                                    .get(player)
-                                    .map(|p| p.channel_id)
-                                {
-                                    channels.contains(&current_vc)
-                                } else {
-                                    false
-                                }
-                            })
-                            .map(|player| async {
-                                ctx.http
-                                    .get_member(guild_id, *player)
-                                    .await?
-                                    .edit(
-                                        ctx.http.clone(),
-                                        EditMember::new().voice_channel(post_match_channel),
-                                    )
-                                    .await?;
-                                Ok::<(), Error>(())
-                            }),
+                // Later games of a series get a fresh vote message
+                ctx.http
+                    .clone()
+                    .get_message(message_component.channel_id, message_component.message.id)
+                    .await?
+                    .edit(
+                        ctx.http.clone(),
+                        EditMessage::new().content(content).components(vec![]),
                     )
                     .await
-                    .into_iter()
-                    .collect::<Result<(), _>>()
                     .ok();
-                }
-                {
-                    let mut match_channels = data.match_channels.lock().unwrap();
-                    for channel in channels.iter() {
-                        match_channels.remove(&channel);
-                    }
-                }
-                future::join_all(
-                    channels
-                        .iter()
-                        .map(|channel| ctx.http.delete_channel(*channel, None)),
+                record_game_result(
+                    data,
+                    ctx.http.clone(),
+                    Some(ctx.cache.clone()),
+                    message_component.guild_id.unwrap(),
+                    match_number,
+                    message_component.channel_id,
+                    vote_result,
                 )
-                .await;
-                {
-                    let mut match_data = data.match_data.lock().unwrap();
-                    let finished_match = match_data.remove(&match_number);
-                    if let Some(mut finished_match) = finished_match {
-                        finished_match.match_end_time =
-                            Some(std::time::UNIX_EPOCH.elapsed().unwrap().as_secs());
-                        let mut user_data =
-                            data.player_data.get_mut(&finished_match.queue).unwrap();
-                        for user in finished_match.members.iter().flat_map(|team| team.iter()) {
-                            user_data
-                                .entry(*user)
-                                .or_default()
-                                .game_history
-                                .push(match_number);
-                        }
-                        data.historical_match_data
-                            .lock()
-                            .unwrap()
-                            .insert(match_number, finished_match);
-                    }
-                }
-                Ok(())
+                .await
             }
         }
     }
 }
 
-async fn handler(
-    ctx: &serenity::Context,
-    event: &serenity::FullEvent,
-    _framework: poise::FrameworkContext<'_, Arc<Data>, Error>,
-    data: Arc<Data>,
-) -> Result<(), Error> {
-    match event {
-        serenity::FullEvent::Ready { .. } => {
-            println!("Ready");
-            let notifies = data
-                .message_edit_notify
-                .iter()
-                .map(|p| (p.key().clone(), p.value().clone()))
-                .collect_vec();
-            for (queue, notify) in notifies {
-                let http = ctx.http.clone();
-                let data = data.clone();
-                tokio::spawn(async move {
-                    loop {
-                        notify.notified().await;
-                        update_queue_messages(data.clone(), http.clone(), &queue)
-                            .await
-                            .ok();
-                        tokio::time::sleep(Duration::from_secs_f32(1.0)).await;
-                    }
-                });
-            }
-        }
-        serenity::FullEvent::VoiceStateUpdate { old, new } => {
-            let guild_queues = data
-                .guild_data
-                .lock()
-                .unwrap()
-                .entry(new.guild_id.unwrap().clone())
-                .or_default()
-                .queues
-                .clone();
-            if let Some(VoiceState {
-                channel_id: Some(channel_id),
-                user_id,
-                ..
-            }) = old
-            {
-                guild_queues
-                    .iter()
-                    .filter(|queue| {
-                        data.configuration
-                            .get(&queue)
-                            .unwrap()
-                            .queue_channels
-                            .clone()
+/// Creates a map vote, with an embed for each candidate map that has an image
+fn create_map_vote_message(
+    content: String,
+    vote_maps: &[String],
+    select_menu: bool,
+    maps: &HashMap<String, MapData>,
+) -> CreateMessage {
+    // Messages fit at most 10 embeds
+    let embeds = vote_maps
+        .iter()
+        .filter_map(|map| {
+            let image_url = maps.get(map)?.image_url.as_ref()?;
+            Some(CreateEmbed::new().title(map).image(image_url))
+        })
+        .take(10)
+        .collect_vec();
+    let map_vote_message = CreateMessage::default().content(content).embeds(embeds);
+    if select_menu {
+        return map_vote_message.components(vec![CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                ButtonData::MapVoteSelect.get_id(),
+                serenity::CreateSelectMenuKind::String {
+                    options: vote_maps
+                        .iter()
+                        .take(25)
+                        .map(|map| CreateSelectMenuOption::new(map.clone(), map.clone()))
+                        .collect(),
+                },
+            )
+            .placeholder("Vote for a map"),
+        )]);
+    }
+    vote_maps
+        .iter()
+        .fold(map_vote_message, |map_vote_message, map| {
+            map_vote_message.button(ButtonData::MapVote(map.clone()).get_button())
+        })
+}
+
+/// Tallies the map vote into its message content, picking the map once a majority agrees
+fn tally_map_votes(match_data: &mut MatchData) -> (Option<String>, String) {
+    let required_votes = match_data.members.iter().flatten().count() / 2 + 1;
+    let votes = match_data
+        .map_votes
+        .values()
+        .counts()
+        .into_iter()
+        .sorted_by_key(|(_, count)| *count)
+        .rev()
+        .collect_vec();
+    let content = format!(
+        "# Map Vote{}{}",
+        match_data
+            .map_vote_end_time
+            .map(|map_vote_end_time| format!("\nEnds <t:{}:R>", map_vote_end_time))
+            .unwrap_or("".to_string()),
+        votes
+            .iter()
+            .map(|(vote_type, count)| format!("\n{}: {}", vote_type, count))
+            .join("")
+    );
+    let vote_result = votes
+        .into_iter()
+        .next()
+        .filter(|(_, count)| *count >= required_votes)
+        .map(|(vote_type, _)| vote_type.clone());
+    if vote_result.is_some() {
+        match_data.map = vote_result.clone();
+        match_data.map_vote_message = None;
+    }
+    (vote_result, content)
+}
+
+/// When result votes start counting towards a result other than cancel, if that's still ahead
+fn result_lock_in_time(match_data: &MatchData, min_match_duration: u32) -> Option<u64> {
+    let lock_in_time = match_data.match_start_time? + min_match_duration as u64;
+    (lock_in_time > std::time::UNIX_EPOCH.elapsed().unwrap().as_secs()).then_some(lock_in_time)
+}
+
+/// Tallies the result vote into its message content, along with the result once a majority agrees.
+/// Before `lock_in_time` only a cancel can be agreed on.
+fn tally_result_votes(
+    match_data: &MatchData,
+    team_names: &[String],
+    show_rosters: bool,
+    lock_in_time: Option<u64>,
+) -> (Option<MatchResult>, String) {
+    let required_votes = match_data.members.iter().flatten().count() / 2 + 1;
+    let votes = match_data
+        .result_votes
+        .values()
+        .counts()
+        .into_iter()
+        .sorted_by_key(|(_, count)| *count)
+        .rev()
+        .collect_vec();
+    let mut content = if show_rosters {
+        get_team_rosters(&match_data.members, team_names)
+    } else {
+        String::new()
+    };
+    content += votes
+        .iter()
+        .map(|(vote_type, count)| format!("{}: {}\n", vote_type.label(team_names), count))
+        .join("")
+        .as_str();
+    if let Some(lock_in_time) = lock_in_time {
+        content += format!("Results lock in <t:{}:R>\n", lock_in_time).as_str();
+    }
+    (
+        votes
+            .into_iter()
+            .next()
+            .filter(|(vote_type, count)| {
+                *count >= required_votes
+                    && (lock_in_time.is_none() || matches!(vote_type, MatchResult::Cancel))
+            })
+            .map(|(vote_type, _)| vote_type.clone()),
+        content,
+    )
+}
+
+/// Whether a match's current game needs proof before it can resolve to `result`
+fn needs_result_proof(data: &Data, match_data: &MatchData, result: &MatchResult) -> bool {
+    *result != MatchResult::Cancel
+        && data
+            .configuration
+            .get(&match_data.queue)
+            .unwrap()
+            .require_result_proof
+        && !match_data
+            .result_proof
+            .iter()
+            .any(|proof| proof.game == match_data.series_games.len())
+}
+
+/// Holds back an agreed result that still needs proof, noting it in the vote's content. Returns
+/// when the match started waiting for proof if it just did.
+fn hold_result_for_proof(
+    data: &Data,
+    match_data: &mut MatchData,
+    vote_result: &mut Option<MatchResult>,
+    content: &mut String,
+) -> Option<u64> {
+    if !vote_result
+        .as_ref()
+        .is_some_and(|result| needs_result_proof(data, match_data, result))
+    {
+        return None;
+    }
+    *vote_result = None;
+    *content += "Waiting for proof of the result, post a screenshot or link in this channel\n";
+    if match_data.awaiting_proof_since.is_some() {
+        return None;
+    }
+    let now = std::time::UNIX_EPOCH.elapsed().unwrap().as_secs();
+    match_data.awaiting_proof_since = Some(now);
+    Some(now)
+}
+
+/// Alerts admins if a match has still been waiting for proof of its result `since` once the
+/// queue's proof timeout passes
+async fn await_result_proof(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    match_number: MatchUuid,
+    match_channel: ChannelId,
+    since: u64,
+) {
+    let timeout_minutes = {
+        let match_data = data.match_data.lock().unwrap();
+        let Some(match_data) = match_data.get(&match_number) else {
+            return;
+        };
+        data.configuration
+            .get(&match_data.queue)
+            .unwrap()
+            .result_proof_timeout_minutes
+    };
+    if timeout_minutes == 0 {
+        return;
+    }
+    tokio::time::sleep(Duration::from_secs(timeout_minutes as u64 * 60)).await;
+    let still_waiting = data
+        .match_data
+        .lock()
+        .unwrap()
+        .get(&match_number)
+        .is_some_and(|match_data| {
+            !match_data.resolved && match_data.awaiting_proof_since == Some(since)
+        });
+    if still_waiting {
+        escalate_missing_result_proof(&data, &http, match_number, match_channel).await;
+    }
+}
+
+/// Asks admins to check a match with no proof of its result, in the audit channel if there is one
+async fn escalate_missing_result_proof(
+    data: &Data,
+    http: &Http,
+    match_number: MatchUuid,
+    match_channel: ChannelId,
+) {
+    let (name, audit_channel) = {
+        let match_data = data.match_data.lock().unwrap();
+        let Some(match_data) = match_data.get(&match_number) else {
+            return;
+        };
+        (
+            match_data.name.clone(),
+            data.configuration
+                .get(&match_data.queue)
+                .unwrap()
+                .audit_channel,
+        )
+    };
+    audit_channel
+        .unwrap_or(match_channel)
+        .send_message(
+            http,
+            CreateMessage::new().content(format!(
+                "Match {} ({}) has no proof of its result, check it and resolve it with `/force_outcome`",
+                name,
+                match_channel.mention()
+            )),
+        )
+        .await
+        .ok();
+}
+
+/// Records the attachments and links a match member posted in its channel as proof of the result.
+/// Returns whether there were any.
+fn record_result_proof(data: &Data, match_number: MatchUuid, message: &serenity::Message) -> bool {
+    let links = message
+        .attachments
+        .iter()
+        .map(|attachment| attachment.url.clone())
+        .chain(
+            message
+                .content
+                .split_whitespace()
+                .filter(|word| {
+                    reqwest::Url::parse(word)
+                        .is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+                })
+                .map(|word| word.to_string()),
+        )
+        .collect_vec();
+    let mut match_data = data.match_data.lock().unwrap();
+    let Some(match_data) = match_data.get_mut(&match_number) else {
+        return false;
+    };
+    if links.is_empty()
+        || match_data.resolved
+        || !match_data
+            .members
+            .iter()
+            .flatten()
+            .contains(&message.author.id)
+    {
+        return false;
+    }
+    let game = match_data.series_games.len();
+    match_data
+        .result_proof
+        .extend(links.into_iter().map(|url| ResultProof {
+            submitter: message.author.id,
+            url,
+            game,
+        }));
+    match_data.awaiting_proof_since = None;
+    true
+}
+
+/// Records a player's map vote and locks in the map once a majority agrees
+async fn record_map_vote(
+    message_component: &ComponentInteraction,
+    data: Arc<Data>,
+    ctx: &serenity::Context,
+    map: String,
+) -> Result<(), Error> {
+    let match_number = {
+        let match_channels = data.match_channels.lock().unwrap();
+        match_channels.get(&message_component.channel_id).cloned()
+    };
+    let Some(match_number) = match_number else {
+        return Err("Invalid state for map vote interaction".into());
+    };
+
+    let (vote_result, mut content) = {
+        let mut match_data = data.match_data.lock().unwrap();
+        let Some(match_data) = match_data.get_mut(&match_number) else {
+            return Ok(());
+        };
+        match_data
+            .map_votes
+            .insert(message_component.user.id, map.to_string());
+        tally_map_votes(match_data)
+    };
+    if let Some(vote_result) = vote_result {
+        ctx.http
+            .clone()
+            .get_message(message_component.channel_id, message_component.message.id)
+            .await?
+            .edit(ctx.http.clone(), EditMessage::new().components(vec![]))
+            .await?;
+        content = format!("# Map: {}", vote_result);
+    }
+    ctx.http
+        .clone()
+        .get_message(message_component.channel_id, message_component.message.id)
+        .await?
+        .edit(ctx.http.clone(), EditMessage::new().content(content))
+        .await?;
+    Ok(())
+}
+
+async fn handler(
+    ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    _framework: poise::FrameworkContext<'_, Arc<Data>, Error>,
+    data: Arc<Data>,
+) -> Result<(), Error> {
+    match event {
+        serenity::FullEvent::Ready { .. } => {
+            println!("Ready");
+            *data.connected_at.lock().unwrap() = Some(Instant::now());
+        }
+        serenity::FullEvent::Resume { .. } => {
+            println!("Resumed");
+            *data.connected_at.lock().unwrap() = Some(Instant::now());
+        }
+        serenity::FullEvent::VoiceStateUpdate { old, new } => {
+            let guild_queues = data
+                .guild_data
+                .lock()
+                .unwrap()
+                .entry(new.guild_id.unwrap().clone())
+                .or_default()
+                .queues
+                .clone();
+            if let Some(VoiceState {
+                channel_id: Some(channel_id),
+                user_id,
+                ..
+            }) = old
+            {
+                for queue in guild_queues
+                    .iter()
+                    .filter(|queue| {
+                        data.configuration
+                            .get(&queue)
+                            .unwrap()
+                            .queue_channels
+                            .clone()
                             .contains(channel_id)
                     })
-                    .for_each(|queue| {
-                        player_leave_queue(data.clone(), user_id.clone(), true, &queue);
-                    });
+                    // Disconnects right after reconnecting are likely caused by the outage
+                    .filter(|queue| new.channel_id.is_some() || !in_reconnect_grace(&data, queue))
+                {
+                    party_leave_queue(data.clone(), ctx, *user_id, queue).await;
+                }
             }
             for queue in guild_queues.iter().filter(|queue| {
                 let config = data.configuration.get(&queue).unwrap();
@@ -1551,13 +3077,7 @@ async fn handler(
                             .get_mut(&queue)
                             .unwrap()
                             .notify_one();
-                        matchmake(
-                            data.clone(),
-                            ctx.http.clone(),
-                            new.guild_id.unwrap(),
-                            &queue,
-                        )
-                        .await?;
+                        matchmake(&data, queue);
                     }
                     Err(reason) => {
                         new.user_id
@@ -1567,11 +3087,14 @@ async fn handler(
                 }
             }
         }
+        serenity::FullEvent::GuildMemberRemoval { guild_id, user, .. } => {
+            remove_departed_member(data, ctx.http.clone(), *guild_id, user.id).await?;
+        }
         serenity::FullEvent::InteractionCreate { interaction } => {
             if let Some(message_component) = interaction.as_message_component() {
                 log_interaction(message_component);
-                let Ok(message_component_data): Result<ButtonData, ron::de::SpannedError> =
-                    ron::de::from_str(message_component.data.custom_id.as_str())
+                let Some(message_component_data) =
+                    ButtonData::from_id(message_component.data.custom_id.as_str())
                 else {
                     eprintln!("Invalid button data: {}", message_component.data.custom_id);
                     return Ok(());
@@ -1598,7 +3121,37 @@ async fn handler(
                 .get(&match_id)
                 .unwrap()
                 .queue;
-            if !data.configuration.get(&queue_id).unwrap().log_chats {
+            let (log_chats, require_result_proof, min_match_duration) = {
+                let config = data.configuration.get(&queue_id).unwrap();
+                (
+                    config.log_chats,
+                    config.require_result_proof,
+                    config.min_match_duration,
+                )
+            };
+            if require_result_proof && record_result_proof(&data, match_id, new_message) {
+                new_message.react(ctx, '✅').await.ok();
+                let locked_in = {
+                    let match_data = data.match_data.lock().unwrap();
+                    match_data.get(&match_id).is_some_and(|match_data| {
+                        result_lock_in_time(match_data, min_match_duration).is_none()
+                    })
+                };
+                // Before then the vote is resolved once it locks in
+                if locked_in {
+                    lock_in_result_vote(
+                        data.clone(),
+                        ctx.http.clone(),
+                        Some(ctx.cache.clone()),
+                        new_message.guild_id.unwrap(),
+                        match_id,
+                        new_message.channel_id,
+                        0,
+                    )
+                    .await;
+                }
+            }
+            if !log_chats {
                 return Ok(());
             }
             fs::create_dir_all("match_logs")?;
@@ -1609,7 +3162,8 @@ async fn handler(
                 .unwrap();
             if let Err(e) = writeln!(
                 file,
-                "{}:{}",
+                "[{}] {}:{}",
+                log_time(),
                 new_message.author.mention(),
                 new_message.content.clone(),
             ) {
@@ -1617,13 +3171,80 @@ async fn handler(
             }
         }
         serenity::FullEvent::Ratelimit { .. } => {
-            println!("Rate limited")
+            println!("Rate limited");
+            metrics::record_ratelimit();
         }
         _ => {}
     }
     Ok(())
 }
 
+/// Whether the bot (re)connected too recently for players leaving voice to be trusted
+fn in_reconnect_grace(data: &Data, queue_id: &QueueUuid) -> bool {
+    let grace = data
+        .configuration
+        .get(queue_id)
+        .map(|config| config.reconnect_grace_seconds)
+        .unwrap_or_default();
+    data.connected_at
+        .lock()
+        .unwrap()
+        .is_some_and(|connected_at| connected_at.elapsed() < Duration::from_secs(grace as u64))
+}
+
+/// Removes a player who left the server from queues and parties so they can't block matches
+async fn remove_departed_member(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    guild_id: GuildId,
+    user: UserId,
+) -> Result<(), Error> {
+    let guild_queues = data
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .map(|guild_data| guild_data.queues.clone())
+        .unwrap_or_default();
+    for queue in guild_queues.iter() {
+        let removed = data.queued_players.get_mut(queue).unwrap().remove(&user);
+        if let Some(mut subscribers) = data.queue_notify_subscribers.get_mut(queue) {
+            subscribers.remove(&user);
+        }
+        if removed {
+            data.message_edit_notify.get(queue).unwrap().notify_one();
+        }
+    }
+    let old_party = {
+        let mut global_player_data = data.global_player_data.lock().unwrap();
+        let Some(player_data) = global_player_data
+            .get_mut(&guild_id)
+            .and_then(|guild_players| guild_players.get_mut(&user))
+        else {
+            return Ok(());
+        };
+        player_data.queue_state = QueueState::None;
+        player_data.party.take()
+    };
+    for party in data.group_data.lock().unwrap().values_mut() {
+        party.pending_invites.remove(&user);
+    }
+    if let Some(old_party) = old_party {
+        leave_party(data.clone(), &user, http, old_party).await?;
+    }
+    Ok(())
+}
+
+/// Guild the queue belongs to, or none for queues orphaned by a config edit
+fn queue_guild(data: &Data, queue_id: &QueueUuid) -> Option<GuildId> {
+    data.guild_data
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(_, guild_data)| guild_data.queues.contains(queue_id))
+        .map(|(guild_id, _)| *guild_id)
+}
+
 fn get_queue(data: Arc<Data>, message_component: &ComponentInteraction) -> Option<QueueUuid> {
     let queues = data
         .guild_data
@@ -1652,45 +3273,182 @@ fn get_queue(data: Arc<Data>, message_component: &ComponentInteraction) -> Optio
     queue
 }
 
+/// Players in the queue's running matches
+fn in_game_player_count(data: &Data, queue: &QueueUuid) -> usize {
+    let current_games = data.current_games.get(queue).unwrap().clone();
+    let match_data = data.match_data.lock().unwrap();
+    // Short-handed matches have fewer players than a full lobby
+    current_games
+        .iter()
+        .filter_map(|match_id| match_data.get(match_id))
+        .map(|match_data| match_data.members.iter().flatten().count())
+        .sum()
+}
+
 async fn update_queue_messages(
     data: Arc<Data>,
     http: Arc<Http>,
     queue: &QueueUuid,
 ) -> Result<(), Error> {
-    let in_game_player_count = data.current_games.get(queue).unwrap().len() * {
-        let config = data.configuration.get(queue).unwrap();
-        (config.team_count * config.team_size) as usize
-    };
-    let response = {
+    let in_game_player_count = in_game_player_count(&data, queue);
+    let eta = estimate_match_interval(&data, queue)
+        .map(|interval| format!("about {} minutes", interval.div_ceil(60)))
+        .unwrap_or("unknown".to_string());
+    let mut response = {
         let queued_players = data.queued_players.get(queue).unwrap();
-        format!(
-            "## Matchmaking Queue\n### {} people are playing right now\nThere are {} queued players: {}",
-            queued_players.len() + in_game_player_count,
+        metrics::set_queued_players(queue, queued_players.len());
+        let config = data.configuration.get(queue).unwrap();
+        render_queue_template(
+            format!(
+                "{}\n{}",
+                config.queue_message_header, config.queue_message_body
+            )
+            .as_str(),
             queued_players.len(),
-            queued_players.iter().map(|c| c.mention()).join(", ")
+            in_game_player_count,
+            queued_players
+                .iter()
+                .map(|c| c.mention())
+                .join(", ")
+                .as_str(),
+            eta.as_str(),
         )
     };
+    let max_concurrent_matches = data
+        .configuration
+        .get(queue)
+        .unwrap()
+        .max_concurrent_matches;
+    if max_concurrent_matches > 0
+        && data.current_games.get(queue).unwrap().len() >= max_concurrent_matches as usize
+    {
+        response += format!(
+            "\n**Match limit reached:** {} matches are running. New matches start once one finishes.",
+            max_concurrent_matches
+        )
+        .as_str();
+    }
+    if let Some(reason) = data.matchmaking_paused.get(queue) {
+        response += format!(
+            "\n**Matchmaking paused:** {}. It will resume when the queue changes.",
+            reason.value()
+        )
+        .as_str();
+    }
     let queue_messages = data
         .configuration
         .get(queue)
         .unwrap()
         .queue_messages
         .clone();
+    let mut stale_messages = vec![];
     for (message_channel, queue_message, _) in queue_messages
         .iter()
         .filter(|(_, _, message_type)| matches!(message_type, QueueMessageType::Queue))
     {
-        message_channel
+        if let Err(error) = message_channel
             .edit_message(
                 http.clone(),
                 queue_message,
                 EditMessage::new().content(&response),
             )
-            .await?;
+            .await
+        {
+            if !is_not_found(&error) {
+                return Err(error.into());
+            }
+            eprintln!(
+                "Queue message {} in {} no longer exists, removing it",
+                queue_message, message_channel
+            );
+            stale_messages.push((*message_channel, *queue_message));
+        }
+    }
+    if !stale_messages.is_empty() {
+        data.configuration
+            .get_mut(queue)
+            .unwrap()
+            .queue_messages
+            .retain(|(channel, message, _)| !stale_messages.contains(&(*channel, *message)));
     }
     Ok(())
 }
 
+/// Fills in a queue message template. `{queued}` is the number of queued players, `{in_game}` the
+/// number of players in matches, `{total}` both together, `{players}` mentions the queued players
+/// and `{eta}` is roughly how often matches start.
+fn render_queue_template(
+    template: &str,
+    queued: usize,
+    in_game: usize,
+    players: &str,
+    eta: &str,
+) -> String {
+    template
+        .replace("{queued}", queued.to_string().as_str())
+        .replace("{in_game}", in_game.to_string().as_str())
+        .replace("{total}", (queued + in_game).to_string().as_str())
+        .replace("{eta}", eta)
+        .replace("{players}", players)
+}
+
+/// Matches `estimate_match_interval` averages over
+const SAMPLE_MATCHES: usize = 10;
+
+/// Average seconds between the starts of the queue's last matches
+fn estimate_match_interval(data: &Data, queue: &QueueUuid) -> Option<u64> {
+    let start_times = data.recent_match_starts.get(queue)?;
+    let (Some(earliest), Some(latest)) = (start_times.front(), start_times.back()) else {
+        return None;
+    };
+    if start_times.len() < 2 {
+        return None;
+    }
+    Some((latest - earliest) / (start_times.len() as u64 - 1))
+}
+
+/// Remembers a match start for `estimate_match_interval`, forgetting ones it no longer samples
+fn record_match_start(data: &Data, queue: &QueueUuid, start_time: u64) {
+    let mut start_times = data.recent_match_starts.entry(*queue).or_default();
+    start_times.push_back(start_time);
+    while start_times.len() > SAMPLE_MATCHES {
+        start_times.pop_front();
+    }
+}
+
+fn is_not_found(error: &serenity::Error) -> bool {
+    matches!(
+        error,
+        serenity::Error::Http(http_error)
+            if http_error.status_code().map(|code| code.as_u16()) == Some(404)
+    )
+}
+
+/// Offset log files are written in, from `LOG_UTC_OFFSET` (e.g. `+02:00`), defaulting to UTC
+static LOG_OFFSET: LazyLock<FixedOffset> = LazyLock::new(|| {
+    std::env::var("LOG_UTC_OFFSET")
+        .ok()
+        .and_then(|offset| offset.parse().ok())
+        .unwrap_or(FixedOffset::east_opt(0).unwrap())
+});
+
+/// Formats a time for JSON output as ISO-8601 in UTC
+fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Formats a unix timestamp for JSON output as ISO-8601 in UTC
+fn format_unix_timestamp(seconds: u64) -> Option<String> {
+    DateTime::from_timestamp(seconds as i64, 0).map(format_timestamp)
+}
+
+/// Formats the current time for log files as ISO-8601 in the configured log offset
+fn log_time() -> String {
+    Utc::now()
+        .with_timezone(&*LOG_OFFSET)
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
 fn log_match_results(_data: Arc<Data>, result: &MatchResult, match_data: &MatchData) {
     let mut file = OpenOptions::new()
         .append(true)
@@ -1699,8 +3457,11 @@ fn log_match_results(_data: Arc<Data>, result: &MatchResult, match_data: &MatchD
         .unwrap();
     if let Err(e) = writeln!(
         file,
-        "match {}:{:?}\nresult:{}",
-        match_data.name, match_data, result
+        "[{}] match {}:{:?}\nresult:{}",
+        log_time(),
+        match_data.name,
+        match_data,
+        result
     ) {
         eprintln!("Couldn't write to file: {}", e);
     }
@@ -1714,7 +3475,8 @@ fn log_command(ctx: &Context) {
         .unwrap();
     if let Err(e) = writeln!(
         file,
-        "{}: {}",
+        "[{}] {}: {}",
+        log_time(),
         ctx.author().mention(),
         ctx.invocation_string(),
     ) {
@@ -1730,7 +3492,8 @@ fn log_interaction(interaction: &ComponentInteraction) {
         .unwrap();
     if let Err(e) = writeln!(
         file,
-        "{}: {}",
+        "[{}] {}: {}",
+        log_time(),
         interaction.user.mention(),
         interaction.data.custom_id,
     ) {
@@ -1738,15 +3501,16 @@ fn log_interaction(interaction: &ComponentInteraction) {
     }
 }
 
+/// Applies the result to player ratings and stats, returning each player's rating change
 fn apply_match_results(
     data: Arc<Data>,
     result: MatchResult,
     players: &Vec<Vec<UserId>>,
     queue_id: QueueUuid,
-) {
+) -> Vec<(UserId, f64)> {
     let rating_config: WengLinConfig = WengLinConfig::default();
     if matches!(result, MatchResult::Cancel) {
-        return;
+        return vec![];
     }
     let system = <WengLin as MultiTeamRatingSystem>::new(rating_config);
     let mut player_data = data.player_data.get_mut(&queue_id).unwrap();
@@ -1782,17 +3546,18 @@ fn apply_match_results(
             .collect_vec()
             .as_slice(),
     );
+    let mut rating_changes = vec![];
     for (team_idx, team) in players.iter().enumerate() {
-        for (player_idx, player) in team.iter().enumerate() {
-            let player = player_data.get_mut(player).unwrap();
-            player.rating = Some(
-                rating_result
-                    .get(team_idx)
-                    .unwrap()
-                    .get(player_idx)
-                    .unwrap()
-                    .clone(),
-            );
+        for (player_idx, player_id) in team.iter().enumerate() {
+            let player = player_data.get_mut(player_id).unwrap();
+            let new_rating = *rating_result
+                .get(team_idx)
+                .unwrap()
+                .get(player_idx)
+                .unwrap();
+            let old_rating = player.rating.unwrap_or(config.default_player_data.rating);
+            rating_changes.push((*player_id, new_rating.rating - old_rating.rating));
+            player.rating = Some(new_rating);
             match result {
                 MatchResult::Team(idx) if idx == team_idx as u32 => player.stats.wins += 1,
                 MatchResult::Team(_) => player.stats.losses += 1,
@@ -1801,71 +3566,1272 @@ fn apply_match_results(
             }
         }
     }
+    rating_changes
 }
 
-async fn matchmake(
-    data: Arc<Data>,
-    http: Arc<Http>,
-    guild_id: GuildId,
-    queue_id: &QueueUuid,
+/// Posts the outcome of a match to the queue's post match channel if enabled
+async fn send_match_summary(
+    data: &Data,
+    http: &serenity::Http,
+    match_data: &MatchData,
+    result: &MatchResult,
+    rating_changes: &[(UserId, f64)],
 ) -> Result<(), Error> {
-    {
-        let mut guard = data.is_matchmaking.get_mut(&queue_id).unwrap();
-
-        if guard.is_some() {
-            // If already running, return
+    let (channel, hide_mmr, team_names) = {
+        let config = data.configuration.get(&match_data.queue).unwrap();
+        if !config.post_match_summary {
             return Ok(());
         }
+        let Some(channel) = config.post_match_channel else {
+            return Ok(());
+        };
+        (
+            channel,
+            config.hide_mmr,
+            config.team_labels(match_data.members.len()),
+        )
+    };
+    let mut content = format!(
+        "# {} result: {}\n",
+        match_data.name,
+        result.label(&team_names)
+    );
+    if let Some(map) = &match_data.map {
+        content += format!("Map: {}\n", map).as_str();
+    }
+    content += get_team_rosters(&match_data.members, &team_names).as_str();
+    if !hide_mmr {
+        let changes = rating_changes
+            .iter()
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .collect_vec();
+        if let Some((player, change)) = changes.first().filter(|(_, change)| *change > 0.0) {
+            content += format!("Biggest gain: {} (+{:.1})\n", player.mention(), change).as_str();
+        }
+        if let Some((player, change)) = changes.last().filter(|(_, change)| *change < 0.0) {
+            content += format!("Biggest loss: {} ({:.1})\n", player.mention(), change).as_str();
+        }
+    }
+    channel
+        .send_message(
+            http,
+            CreateMessage::new()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new().empty_users().empty_roles()),
+        )
+        .await?;
+    Ok(())
+}
 
-        // Mark as running
-        *guard = Some(());
-    }
-
-    loop {
-        // Actual task execution
-        let result = try_matchmaking(data.clone(), http.clone(), guild_id, queue_id).await?;
+/// Announces a newly formed match in the queue's announce channel if one is set
+async fn send_match_announcement(
+    data: &Data,
+    http: &serenity::Http,
+    match_data: &MatchData,
+) -> Result<(), Error> {
+    let (channel, team_names) = {
+        let config = data.configuration.get(&match_data.queue).unwrap();
+        let Some(channel) = config.announce_channel else {
+            return Ok(());
+        };
+        (channel, config.team_labels(match_data.members.len()))
+    };
+    let mut content = format!("## Match {} started\n", match_data.name);
+    if let Some(map) = &match_data.map {
+        content += format!("Map: {}\n", map).as_str();
+    }
+    content += get_team_rosters(&match_data.members, &team_names).as_str();
+    channel
+        .send_message(
+            http,
+            CreateMessage::new()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new().empty_users().empty_roles()),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Counts a cancelled match against the players who voted to cancel it, alerting the audit
+/// channel about players that reach the cancel alert threshold
+async fn record_cancel_votes(data: &Data, http: &serenity::Http, finished_match: &MatchData) {
+    if finished_match.cancel_voters.is_empty() {
+        return;
+    }
+    let (count_cancels, cancel_alert_threshold, audit_channel) = {
+        let config = data.configuration.get(&finished_match.queue).unwrap();
+        (
+            config.count_cancels,
+            config.cancel_alert_threshold,
+            config.audit_channel,
+        )
+    };
+    let now = std::time::UNIX_EPOCH.elapsed().unwrap().as_secs();
+    let mut alerts = vec![];
+    {
+        let mut player_data = data.player_data.get_mut(&finished_match.queue).unwrap();
+        for voter in finished_match.cancel_voters.iter() {
+            let player = player_data.entry(*voter).or_default();
+            player.last_cancel_vote = Some(now);
+            if !count_cancels {
+                continue;
+            }
+            player.cancel_count += 1;
+            if cancel_alert_threshold > 0 && player.cancel_count % cancel_alert_threshold == 0 {
+                alerts.push((*voter, player.cancel_count));
+            }
+        }
+    }
+    let Some(audit_channel) = audit_channel else {
+        return;
+    };
+    for (voter, cancel_count) in alerts {
+        audit_channel
+            .send_message(
+                http,
+                CreateMessage::new()
+                    .content(format!(
+                        "{} has voted to cancel {} matches, most recently match {}",
+                        voter.mention(),
+                        cancel_count,
+                        finished_match.name
+                    ))
+                    .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
+            )
+            .await
+            .ok();
+    }
+}
+
+/// Starts a captains draft for a lobby in the queue channel, with the highest rated players of
+/// different parties as captains. Returns false if the queue has no channel to draft in or there
+/// is nobody left to pick once the captains' parties join them.
+async fn start_draft(
+    data: &Data,
+    http: &serenity::Http,
+    guild_id: GuildId,
+    queue_id: &QueueUuid,
+    members: &[Vec<UserId>],
+) -> Result<bool, Error> {
+    let (queue_channel, default_rating, team_names) = {
+        let config = data.configuration.get(queue_id).unwrap();
+        (
+            config.queue_channel(),
+            config.default_player_data.rating,
+            config.team_labels(members.len()),
+        )
+    };
+    let Some(queue_channel) = queue_channel else {
+        return Ok(false);
+    };
+    let players = {
+        let player_data = data.player_data.get(queue_id).unwrap();
+        members
+            .iter()
+            .flatten()
+            .map(|player| {
+                let rating = player_data
+                    .get(player)
+                    .and_then(|player| player.rating)
+                    .unwrap_or(default_rating);
+                (*player, rating.rating)
+            })
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .map(|(player, _)| player)
+            .collect_vec()
+    };
+    // Parties stay together, so they're picked as one and join their captain
+    let mut parties: Vec<Vec<UserId>> = vec![];
+    for player in players.iter() {
+        if parties.iter().any(|party| party.contains(player)) {
+            continue;
+        }
+        // Captains are the first member of their team
+        parties.push(
+            std::iter::once(*player)
+                .chain(
+                    get_party_members(data, guild_id, *player)
+                        .into_iter()
+                        .filter(|member| member != player && players.contains(member)),
+                )
+                .collect(),
+        );
+    }
+    if parties.len() <= members.len() {
+        return Ok(false);
+    }
+    let pool = parties.split_off(members.len());
+    let team_size = members.iter().map(Vec::len).max().unwrap_or_default();
+    let mut names = HashMap::new();
+    for player in pool.iter().flatten() {
+        if let Ok(member) = http.get_member(guild_id, *player).await {
+            names.insert(*player, member.display_name().to_string());
+        }
+    }
+    let mut draft = Draft {
+        teams: parties,
+        pool,
+        team_size,
+        names,
+        team_names,
+        message: None,
+        pick_started: Instant::now(),
+    };
+    // Captains' parties may leave no way to fit everyone else
+    if !(0..draft.pool.len()).any(|party| draft.can_pick(draft.picking_team(), party)) {
+        return Ok(false);
+    }
+    let message = queue_channel
+        .send_message(
+            http,
+            CreateMessage::new()
+                .content(draft.content())
+                .components(draft.components(*queue_id))
+                .allowed_mentions(
+                    CreateAllowedMentions::new().users(vec![draft.teams[draft.picking_team()][0]]),
+                ),
+        )
+        .await?;
+    draft.message = Some((queue_channel, message.id));
+    data.drafts.insert(*queue_id, draft);
+    Ok(true)
+}
+
+/// Keeps a winning team together as a party and requeues it if every winner opted in. Streak
+/// parties from the match that lost, or hit `max_streak_requeues`, are disbanded.
+async fn requeue_winners(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    guild_id: GuildId,
+    queue_id: QueueUuid,
+    players: &[Vec<UserId>],
+    result: &MatchResult,
+) {
+    let (enabled, max_streak_requeues) = {
+        let config = data.configuration.get(&queue_id).unwrap();
+        (config.requeue_winners_together, config.max_streak_requeues)
+    };
+    let winners = match result {
+        MatchResult::Team(team) if enabled => {
+            players.get(*team as usize).cloned().unwrap_or_default()
+        }
+        _ => vec![],
+    };
+    let opted_in = !winners.is_empty() && {
+        let player_data = data.player_data.get(&queue_id).unwrap();
+        winners.iter().all(|winner| {
+            player_data
+                .get(winner)
+                .is_some_and(|player| player.requeue_after_win)
+        })
+    };
+    let snoozed = {
+        let global_player_data = data.global_player_data.lock().unwrap();
+        let guild_players = global_player_data.get(&guild_id);
+        winners.iter().any(|winner| {
+            guild_players
+                .and_then(|players| players.get(winner))
+                .is_some_and(GlobalPlayerData::is_snoozed)
+        })
+    };
+    let requeue = {
+        let mut global_player_data = data.global_player_data.lock().unwrap();
+        let guild_players = global_player_data.entry(guild_id).or_default();
+        let mut group_data = data.group_data.lock().unwrap();
+        let winner_parties = winners
+            .iter()
+            .map(|winner| guild_players.get(winner).and_then(|player| player.party))
+            .unique()
+            .collect_vec();
+        // Winners keep streaking if they're all unpartied or still exactly the same streak party
+        let streak = match winner_parties.as_slice() {
+            [None] => Some(1),
+            [Some(party)] => group_data
+                .get(party)
+                .filter(|group| group.players.len() == winners.len())
+                .and_then(|group| group.streak_requeues)
+                .map(|streak| streak + 1),
+            _ => None,
+        }
+        .filter(|streak| opted_in && !snoozed && *streak <= max_streak_requeues);
+        let kept_party = streak.and_then(|_| winner_parties[0]);
+        let ended_parties = players
+            .iter()
+            .flatten()
+            .filter_map(|player| guild_players.get(player).and_then(|player| player.party))
+            .filter(|party| Some(*party) != kept_party)
+            .filter(|party| {
+                group_data
+                    .get(party)
+                    .is_some_and(|group| group.streak_requeues.is_some())
+            })
+            .unique()
+            .collect_vec();
+        for party in ended_parties {
+            let Some(group) = group_data.remove(&party) else {
+                continue;
+            };
+            for member in group.players {
+                if let Some(member) = guild_players.get_mut(&member) {
+                    member.party = None;
+                }
+            }
+        }
+        if let Some(streak) = streak {
+            let party = kept_party.unwrap_or_else(GroupUuid::new);
+            let group = group_data.entry(party).or_insert(QueueGroup {
+                guild: guild_id,
+                players: winners.iter().cloned().collect(),
+                pending_invites: HashSet::new(),
+                invite_link: None,
+                last_active: Utc::now(),
+                streak_requeues: None,
+            });
+            group.streak_requeues = Some(streak);
+            group.last_active = Utc::now();
+            for winner in winners.iter() {
+                guild_players.entry(*winner).or_default().party = Some(party);
+            }
+        }
+        streak.is_some()
+    };
+    if !requeue {
+        return;
+    }
+    if let Err(err) = try_queue_player(
+        data.clone(),
+        &queue_id,
+        winners[0],
+        http,
+        guild_id,
+        true,
+        false,
+    )
+    .await
+    {
+        eprintln!("Failed to requeue winning team: {}", err);
+        return;
+    }
+    data.message_edit_notify
+        .get(&queue_id)
+        .unwrap()
+        .notify_one();
+    matchmake(&data, &queue_id);
+}
+
+/// Deletes a resolved match's channels. With `keep_match_channels` the text channel is renamed
+/// and kept for post-game discussion or records, while the voice channels are still deleted.
+async fn close_match_channels(
+    data: &Data,
+    http: &Http,
+    queue_id: &QueueUuid,
+    channels: &[ChannelId],
+    vote_messages: &[MessageId],
+) {
+    {
+        let mut match_channels = data.match_channels.lock().unwrap();
+        for channel in channels {
+            match_channels.remove(channel);
+        }
+    }
+    let (keep_match_channels, clean_up_match_messages, lock_kept_match_channels) = {
+        let config = data.configuration.get(queue_id).unwrap();
+        (
+            config.keep_match_channels,
+            config.clean_up_match_messages,
+            config.lock_kept_match_channels,
+        )
+    };
+    if !keep_match_channels {
+        future::join_all(
+            channels
+                .iter()
+                .map(|channel| http.delete_channel(*channel, None)),
+        )
+        .await;
+        return;
+    }
+    for channel in channels {
+        let Ok(serenity::Channel::Guild(mut guild_channel)) = channel.to_channel(http).await else {
+            continue;
+        };
+        if guild_channel.kind == ChannelType::Voice {
+            http.delete_channel(*channel, None).await.ok();
+            continue;
+        }
+        if clean_up_match_messages {
+            match vote_messages {
+                [] => {}
+                [message] => {
+                    channel.delete_message(http, *message).await.ok();
+                }
+                _ => {
+                    channel.delete_messages(http, vote_messages).await.ok();
+                }
+            }
+        }
+        if lock_kept_match_channels {
+            // Players only have view overwrites, so denying sending to everyone makes it read only
+            channel
+                .create_permission(
+                    http,
+                    PermissionOverwrite {
+                        allow: Permissions::empty(),
+                        deny: Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+                        kind: PermissionOverwriteType::Role(guild_channel.guild_id.everyone_role()),
+                    },
+                )
+                .await
+                .ok();
+        }
+        let name = format!("done-{}", guild_channel.name);
+        guild_channel
+            .edit(http, serenity::EditChannel::new().name(name))
+            .await
+            .ok();
+    }
+}
+
+/// Picks each team's MVP, who needs votes from a majority of their teammates
+fn team_mvps(match_data: &MatchData) -> Vec<UserId> {
+    match_data
+        .members
+        .iter()
+        .filter(|team| team.len() > 1)
+        .filter_map(|team| {
+            let required_votes = (team.len() - 1) / 2 + 1;
+            match_data
+                .mvp_votes
+                .iter()
+                .filter(|(voter, vote)| team.contains(voter) && team.contains(vote))
+                .map(|(_, vote)| *vote)
+                .counts()
+                .into_iter()
+                .find(|(_, count)| *count >= required_votes)
+                .map(|(vote, _)| vote)
+        })
+        .collect()
+}
+
+/// Finishes a match with its result: applies ratings for `rated_result`, notifies integrations,
+/// requeues winners, moves players to the post match channel and closes the match channels.
+/// Returns false if the match was already resolved.
+async fn resolve_match(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    cache: Option<&serenity::Cache>,
+    guild_id: GuildId,
+    match_number: MatchUuid,
+    vote_result: MatchResult,
+    rated_result: Option<MatchResult>,
+) -> Result<bool, Error> {
+    let (channels, players, queue_id, post_match_channel, finished_match) = {
+        let mut match_data = data.match_data.lock().unwrap();
+        let Some(match_data) = match_data.get_mut(&match_number) else {
+            return Ok(false);
+        };
+        if match_data.resolved {
+            return Ok(false);
+        }
+        let post_match_channel = data
+            .configuration
+            .get(&match_data.queue)
+            .unwrap()
+            .post_match_channel;
+        match_data.resolved = true;
+        match_data.final_result = Some(vote_result.clone());
+        match_data.mvps = team_mvps(match_data);
+        if vote_result == MatchResult::Cancel {
+            match_data.cancel_voters = match_data
+                .result_votes
+                .iter()
+                .filter(|(_, vote)| **vote == MatchResult::Cancel)
+                .map(|(voter, _)| *voter)
+                .sorted()
+                .collect();
+        }
+        log_match_results(data.clone(), &vote_result, match_data);
+        (
+            match_data.channels.clone(),
+            match_data.members.clone(),
+            match_data.queue,
+            post_match_channel,
+            match_data.clone(),
+        )
+    };
+    let rating_changes = rated_result
+        .map(|rated_result| apply_match_results(data.clone(), rated_result, &players, queue_id))
+        .unwrap_or_default();
+    if !matches!(vote_result, MatchResult::Cancel) {
+        let mut player_data = data.player_data.get_mut(&queue_id).unwrap();
+        for mvp in finished_match.mvps.iter() {
+            player_data.entry(*mvp).or_default().mvp_count += 1;
+        }
+    }
+    record_cancel_votes(&data, &http, &finished_match).await;
+    metrics::record_match_resolved(&vote_result);
+    webhook::send_match_webhook(&data, match_number, &finished_match, Some(&vote_result));
+    send_match_summary(&data, &http, &finished_match, &vote_result, &rating_changes)
+        .await
+        .ok();
+
+    for player in players.iter().flatten() {
+        data.global_player_data
+            .lock()
+            .unwrap()
+            .get_mut(&guild_id)
+            .unwrap()
+            .get_mut(player)
+            .unwrap()
+            .queue_state = QueueState::None;
+    }
+    data.message_edit_notify
+        .get_mut(&queue_id)
+        .unwrap()
+        .notify_one();
+    requeue_winners(
+        data.clone(),
+        http.clone(),
+        guild_id,
+        queue_id,
+        &players,
+        &vote_result,
+    )
+    .await;
+    // Without the cache there's no way to tell who is still in the match voice channels
+    if let (Some(post_match_channel), Some(cache)) = (post_match_channel, cache) {
+        future::join_all(
+            players
+                .iter()
+                .flatten()
+                .filter(|player| {
+                    if let Some(Some(current_vc)) = guild_id
+                        .to_guild_cached(cache)
+                        .unwrap()
+                        .voice_states
+                        .get(player)
+                        .map(|p| p.channel_id)
+                    {
+                        channels.contains(&current_vc)
+                    } else {
+                        false
+                    }
+                })
+                .map(|player| async {
+                    http.get_member(guild_id, *player)
+                        .await?
+                        .edit(&http, EditMember::new().voice_channel(post_match_channel))
+                        .await?;
+                    Ok::<(), Error>(())
+                }),
+        )
+        .await
+        .into_iter()
+        .collect::<Result<(), _>>()
+        .ok();
+    }
+    close_match_channels(
+        &data,
+        &http,
+        &queue_id,
+        &channels,
+        &finished_match.vote_messages,
+    )
+    .await;
+    {
+        let mut match_data = data.match_data.lock().unwrap();
+        let finished_match = match_data.remove(&match_number);
+        metrics::set_active_matches(match_data.len());
+        if let Some(mut current_games) = data.current_games.get_mut(&queue_id) {
+            current_games.remove(&match_number);
+        }
+        if let Some(mut finished_match) = finished_match {
+            finished_match.match_end_time =
+                Some(std::time::UNIX_EPOCH.elapsed().unwrap().as_secs());
+            let mut user_data = data.player_data.get_mut(&finished_match.queue).unwrap();
+            for user in finished_match.members.iter().flat_map(|team| team.iter()) {
+                user_data
+                    .entry(*user)
+                    .or_default()
+                    .game_history
+                    .push(match_number);
+            }
+            data.historical_match_data
+                .lock()
+                .unwrap()
+                .insert(match_number, finished_match);
+        }
+    }
+    // The finished match may have been holding back new ones
+    matchmake(&data, &queue_id);
+    data.message_edit_notify
+        .get(&queue_id)
+        .unwrap()
+        .notify_one();
+    Ok(true)
+}
+
+/// Resolves a game once its result vote time runs out, to the most voted result or a cancel if
+/// nobody voted or the most voted results are tied. `series_game` is the index of the game in the series the timer was started for.
+fn expire_result_vote(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    cache: Option<Arc<serenity::Cache>>,
+    guild_id: GuildId,
+    match_number: MatchUuid,
+    match_channel: ChannelId,
+    series_game: usize,
+) -> future::BoxFuture<'static, ()> {
+    // Boxed since this and record_game_result spawn or await each other
+    Box::pin(async move {
+        let result_vote_time = {
+            let match_data = data.match_data.lock().unwrap();
+            let Some(match_data) = match_data.get(&match_number) else {
+                return;
+            };
+            data.configuration
+                .get(&match_data.queue)
+                .unwrap()
+                .result_vote_time
+        };
+        tokio::time::sleep(Duration::from_secs(result_vote_time as u64)).await;
+        let (vote_result, missing_proof) = {
+            let match_data = data.match_data.lock().unwrap();
+            let Some(match_data) = match_data.get(&match_number) else {
+                return;
+            };
+            if match_data.resolved || match_data.series_games.len() != series_game {
+                return;
+            }
+            let vote_counts = match_data.result_votes.values().counts();
+            let most_votes = vote_counts.values().max().cloned().unwrap_or_default();
+            // A tied plurality doesn't say which side to rate, so it cancels like no votes do
+            let vote_result = vote_counts
+                .into_iter()
+                .filter(|(_, count)| *count == most_votes)
+                .map(|(vote, _)| vote.clone())
+                .exactly_one()
+                .unwrap_or(MatchResult::Cancel);
+            let missing_proof = needs_result_proof(&data, match_data, &vote_result);
+            (vote_result, missing_proof)
+        };
+        if missing_proof {
+            match_channel
+                .send_message(
+                    &http,
+                    CreateMessage::new().content(format!(
+                        "Result vote timed out without proof of {}, admins have been asked to check it",
+                        vote_result
+                    )),
+                )
+                .await
+                .ok();
+            escalate_missing_result_proof(&data, &http, match_number, match_channel).await;
+            return;
+        }
+        match_channel
+            .send_message(
+                &http,
+                CreateMessage::new().content(format!(
+                    "Result vote timed out, resolving game as {}",
+                    vote_result
+                )),
+            )
+            .await
+            .ok();
+        if let Err(err) = record_game_result(
+            data,
+            http,
+            cache,
+            guild_id,
+            match_number,
+            match_channel,
+            vote_result,
+        )
+        .await
+        {
+            eprintln!("Failed to resolve timed out match: {}", err);
+        }
+    })
+}
+
+/// Resolves a match whose result vote agreed on a result before `min_match_duration` had passed or
+/// its result proof was posted, dropping the lock in notice from the vote otherwise
+fn lock_in_result_vote(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    cache: Option<Arc<serenity::Cache>>,
+    guild_id: GuildId,
+    match_number: MatchUuid,
+    match_channel: ChannelId,
+    min_match_duration: u32,
+) -> future::BoxFuture<'static, ()> {
+    // Boxed since this and record_game_result spawn or await each other
+    Box::pin(async move {
+        tokio::time::sleep(Duration::from_secs(min_match_duration as u64)).await;
+        let (vote_result, content, message, awaiting_proof_since) = {
+            let mut match_data = data.match_data.lock().unwrap();
+            let Some(match_data) = match_data.get_mut(&match_number) else {
+                return;
+            };
+            if match_data.resolved {
+                return;
+            }
+            let Some(message) = match_data.result_vote_message else {
+                return;
+            };
+            let (team_names, show_rosters) = {
+                let config = data.configuration.get(&match_data.queue).unwrap();
+                (
+                    config.team_labels(match_data.members.len()),
+                    config.show_rosters_in_result_vote,
+                )
+            };
+            let (mut vote_result, mut content) =
+                tally_result_votes(match_data, &team_names, show_rosters, None);
+            let awaiting_proof_since =
+                hold_result_for_proof(&data, match_data, &mut vote_result, &mut content);
+            (vote_result, content, message, awaiting_proof_since)
+        };
+        if let Some(since) = awaiting_proof_since {
+            tokio::spawn(await_result_proof(
+                data.clone(),
+                http.clone(),
+                match_number,
+                match_channel,
+                since,
+            ));
+        }
+        let mut edit = EditMessage::new().content(content);
+        if vote_result.is_some() {
+            edit = edit.components(vec![]);
+        }
+        match_channel.edit_message(&http, message, edit).await.ok();
+        let Some(vote_result) = vote_result else {
+            return;
+        };
+        if let Err(err) = record_game_result(
+            data,
+            http,
+            cache,
+            guild_id,
+            match_number,
+            match_channel,
+            vote_result,
+        )
+        .await
+        {
+            eprintln!("Failed to resolve locked in match: {}", err);
+        }
+    })
+}
+
+fn create_result_vote_message(team_names: &[String]) -> CreateMessage {
+    let mut result_message = CreateMessage::default()
+        .allowed_mentions(CreateAllowedMentions::new().empty_roles().empty_users());
+    for (i, team_name) in team_names.iter().enumerate() {
+        result_message = result_message.button(
+            ButtonData::ResultVote(MatchResult::Team(i as u32))
+                .get_button()
+                .label(team_name),
+        )
+    }
+    result_message
+        .button(ButtonData::ResultVote(MatchResult::Tie).get_button())
+        .button(ButtonData::ResultVote(MatchResult::Cancel).get_button())
+}
+
+/// Counts a game towards a best-of series and starts the next game, or resolves the match once
+/// the series is decided. Matches that aren't a series are resolved right away.
+async fn record_game_result(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    cache: Option<Arc<serenity::Cache>>,
+    guild_id: GuildId,
+    match_number: MatchUuid,
+    match_channel: ChannelId,
+    game_result: MatchResult,
+) -> Result<(), Error> {
+    let next_game = {
+        let mut match_data = data.match_data.lock().unwrap();
+        let Some(match_data) = match_data.get_mut(&match_number) else {
+            return Ok(());
+        };
+        if match_data.resolved {
+            return Ok(());
+        }
+        let config = data.configuration.get(&match_data.queue).unwrap();
+        if config.series_length <= 1 || game_result == MatchResult::Cancel {
+            None
+        } else {
+            match_data.series_games.push(game_result.clone());
+            match_data.result_votes.clear();
+            match_data.awaiting_proof_since = None;
+            let wins = (0..match_data.members.len() as u32)
+                .map(|team| {
+                    match_data
+                        .series_games
+                        .iter()
+                        .filter(|game| **game == MatchResult::Team(team))
+                        .count()
+                })
+                .collect_vec();
+            let required_wins = config.series_length as usize / 2 + 1;
+            let most_wins = wins.iter().max().cloned().unwrap_or_default();
+            let series_result = if most_wins >= required_wins
+                || match_data.series_games.len() >= config.series_length as usize
+            {
+                // Ties can use up every game without anyone reaching a majority of wins
+                match wins
+                    .iter()
+                    .positions(|team_wins| *team_wins == most_wins)
+                    .exactly_one()
+                {
+                    Ok(team) => Some(MatchResult::Team(team as u32)),
+                    Err(_) => Some(MatchResult::Tie),
+                }
+            } else {
+                None
+            };
+            let next_map = if series_result.is_none() && !config.maps.is_empty() {
+                let map = config
+                    .maps
+                    .keys()
+                    .filter(|map| config.maps.len() == 1 || match_data.map.as_ref() != Some(*map))
+                    .collect_vec()
+                    .choose(&mut rand::thread_rng())
+                    .map(|map| map.to_string());
+                match_data.map = map.clone();
+                match_data.map_votes.clear();
+                match_data.map_reroll_votes.clear();
+                match_data.map_rerolls = 0;
+                map
+            } else {
+                None
+            };
+            Some((
+                series_result,
+                config.rate_series_games,
+                match_data.members.clone(),
+                match_data.queue,
+                wins,
+                match_data.series_games.len(),
+                next_map,
+                config.max_map_rerolls > 0 && config.maps.len() > 1,
+                config.result_vote_time,
+                config.team_labels(match_data.members.len()),
+            ))
+        }
+    };
+    let Some((
+        series_result,
+        rate_series_games,
+        members,
+        queue_id,
+        wins,
+        games_played,
+        next_map,
+        allow_reroll,
+        result_vote_time,
+        team_names,
+    )) = next_game
+    else {
+        return resolve_match(
+            data,
+            http,
+            cache.as_deref(),
+            guild_id,
+            match_number,
+            game_result.clone(),
+            Some(game_result),
+        )
+        .await
+        .map(|_| ());
+    };
+    if let Some(series_result) = series_result {
+        // Games of a series rated one at a time only have the last game left to rate
+        let rated_result = if rate_series_games {
+            game_result
+        } else {
+            series_result.clone()
+        };
+        return resolve_match(
+            data,
+            http,
+            cache.as_deref(),
+            guild_id,
+            match_number,
+            series_result,
+            Some(rated_result),
+        )
+        .await
+        .map(|_| ());
+    }
+    if rate_series_games {
+        apply_match_results(data.clone(), game_result.clone(), &members, queue_id);
+    }
+    let mut content = format!(
+        "# Game {}\nLast game: {}\nScore: {}\n",
+        games_played + 1,
+        game_result.label(&team_names),
+        wins.iter()
+            .zip(team_names.iter())
+            .map(|(wins, team_name)| format!("{}: {}", team_name, wins))
+            .join(", ")
+    );
+    if let Some(map) = &next_map {
+        if allow_reroll {
+            // Rerolls rewrite their whole message, so they get their own to leave the result vote
+            let map_message = CreateMessage::default()
+                .content(format!("# Map: {}", map))
+                .button(ButtonData::MapReroll.get_button());
+            let map_message = match_channel.send_message(&http, map_message).await?;
+            if let Some(match_data) = data.match_data.lock().unwrap().get_mut(&match_number) {
+                match_data.vote_messages.push(map_message.id);
+            }
+        } else {
+            content += format!("Map: {}\n", map).as_str();
+        }
+    }
+    let next_game_message = create_result_vote_message(&team_names).content(content);
+    let next_game_message = match_channel.send_message(&http, next_game_message).await?;
+    if let Some(match_data) = data.match_data.lock().unwrap().get_mut(&match_number) {
+        match_data.result_vote_message = Some(next_game_message.id);
+        match_data.vote_messages.push(next_game_message.id);
+    }
+    if result_vote_time > 0 {
+        tokio::spawn(expire_result_vote(
+            data.clone(),
+            http.clone(),
+            cache,
+            guild_id,
+            match_number,
+            match_channel,
+            games_played,
+        ));
+    }
+    Ok(())
+}
+
+/// Everyone in the player's party including them, or just them if they aren't in one
+fn get_party_members(data: &Data, guild_id: GuildId, user_id: UserId) -> Vec<UserId> {
+    let party = data
+        .global_player_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .and_then(|players| players.get(&user_id))
+        .and_then(|player| player.party);
+    party
+        .and_then(|party| {
+            data.group_data
+                .lock()
+                .unwrap()
+                .get(&party)
+                .map(|group| group.players.iter().cloned().sorted().collect_vec())
+        })
+        .unwrap_or_else(|| vec![user_id])
+}
+
+/// Queues the interacting player, and their party if they have one, then reports who was queued
+/// in the deferred interaction response
+async fn join_queue_from_interaction(
+    message_component: &ComponentInteraction,
+    data: Arc<Data>,
+    ctx: &serenity::Context,
+    queue: QueueUuid,
+    with_party: bool,
+) -> Result<(), Error> {
+    let guild_id = message_component.guild_id.unwrap();
+    let response = match try_queue_player(
+        data.clone(),
+        &queue,
+        message_component.user.id,
+        ctx.http.clone(),
+        guild_id,
+        true,
+        false,
+    )
+    .await
+    {
+        Ok(()) => {
+            data.message_edit_notify
+                .get_mut(&queue)
+                .unwrap()
+                .notify_one();
+            matchmake(&data, &queue);
+            if with_party {
+                let members = get_party_members(&data, guild_id, message_component.user.id)
+                    .iter()
+                    .map(|member| member.mention())
+                    .join(", ");
+                locale::text(&data, &queue, Message::JoinedQueueWithParty(&members))
+            } else {
+                locale::text(&data, &queue, Message::JoinedQueue)
+            }
+        }
+        Err(reason) => reason,
+    };
+    message_component
+        .edit_response(
+            ctx.http(),
+            EditInteractionResponse::new()
+                .content(response)
+                .components(vec![])
+                .allowed_mentions(CreateAllowedMentions::new().empty_users()),
+        )
+        .await?;
+    Ok(())
+}
 
-        if let Some(delay) = result {
-            // Task failed, clear running state and retry after delay
-            *data.is_matchmaking.get_mut(&queue_id).unwrap() = None;
-            tokio::time::sleep(Duration::from_secs_f32(delay)).await;
-            let mut guard = data.is_matchmaking.get_mut(&queue_id).unwrap();
+/// Requests a matchmaking pass from the queue's matchmaking worker
+fn matchmake(data: &Data, queue_id: &QueueUuid) {
+    metrics::record_matchmaking_request();
+    if let Some(notify) = data.matchmaking_notify.get(queue_id) {
+        notify.notify_one();
+    }
+}
 
-            // If re-executed during sleep, exit loop
-            if guard.is_some() {
-                break;
-            }
+/// Runs matchmaking passes for a queue until no more matches can form. Requests made while a pass
+/// is running are kept as a `Notify` permit, so every join is followed by at least one more pass.
+async fn matchmaking_worker(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    cache: Arc<serenity::Cache>,
+    queue_id: QueueUuid,
+) {
+    run_matchmaking_passes(data.clone(), queue_id, move |guild_id| {
+        let (data, http, cache) = (data.clone(), http.clone(), cache.clone());
+        async move { try_matchmaking(data, http, cache, guild_id, &queue_id, false).await }
+    })
+    .await
+}
 
-            // Mark as running again
-            *guard = Some(());
-        } else {
+/// The loop behind `matchmaking_worker`, with the matchmaking pass passed in
+async fn run_matchmaking_passes<F, Fut>(data: Arc<Data>, queue_id: QueueUuid, mut pass: F)
+where
+    F: FnMut(GuildId) -> Fut,
+    Fut: std::future::Future<Output = Result<MatchmakingOutcome, Error>>,
+{
+    let notify = data.matchmaking_notify.get(&queue_id).unwrap().clone();
+    loop {
+        notify.notified().await;
+        let mut failed_attempts = 0;
+        if data.matchmaking_paused.remove(&queue_id).is_some() {
             data.message_edit_notify
                 .get(&queue_id)
                 .unwrap()
                 .notify_one();
-            break;
+        }
+        // Orphaned queues have nowhere to create matches
+        while let Some(guild_id) = queue_guild(&data, &queue_id) {
+            {
+                let mut guard = data.is_matchmaking.entry(queue_id).or_default();
+                if guard.is_some() {
+                    // A forced match is running, check again once it's done
+                    drop(guard);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                *guard = Some(());
+            }
+            let result = pass(guild_id).await;
+            *data.is_matchmaking.get_mut(&queue_id).unwrap() = None;
+            match result {
+                Ok(MatchmakingOutcome::Created(_)) => {
+                    data.message_edit_notify
+                        .get(&queue_id)
+                        .unwrap()
+                        .notify_one();
+                }
+                Ok(MatchmakingOutcome::Retry(delay)) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs_f32(delay)) => {}
+                        _ = notify.notified() => failed_attempts = 0,
+                    }
+                }
+                Ok(MatchmakingOutcome::Unmatchable(reason)) => {
+                    let max_retries = data
+                        .configuration
+                        .get(&queue_id)
+                        .unwrap()
+                        .matchmaking_max_retries;
+                    if failed_attempts >= max_retries {
+                        println!("Pausing matchmaking until the queue changes: {}", reason);
+                        data.matchmaking_paused.insert(queue_id, reason);
+                        data.message_edit_notify
+                            .get(&queue_id)
+                            .unwrap()
+                            .notify_one();
+                        break;
+                    }
+                    let delay = 10.0 * 2f32.powi(failed_attempts.min(6) as i32);
+                    failed_attempts += 1;
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs_f32(delay)) => {}
+                        _ = notify.notified() => failed_attempts = 0,
+                    }
+                }
+                Ok(MatchmakingOutcome::NotEnoughPlayers) => break,
+                Err(err) => {
+                    eprintln!("Matchmaking failed: {}", err);
+                    break;
+                }
+            }
         }
     }
+}
 
-    // Clear running state when done
-    *data.is_matchmaking.get_mut(&queue_id).unwrap() = None;
-    Ok(())
+/// Starts the tasks that update queue messages and run matchmaking for a queue
+fn spawn_queue_workers(
+    data: Arc<Data>,
+    http: Arc<Http>,
+    cache: Arc<serenity::Cache>,
+    queue: QueueUuid,
+) {
+    let message_notify = data
+        .message_edit_notify
+        .entry(queue)
+        .or_insert_with(|| Arc::new(Notify::new()))
+        .clone();
+    data.matchmaking_notify
+        .entry(queue)
+        .or_insert_with(|| Arc::new(Notify::new()));
+    {
+        let data = data.clone();
+        let http = http.clone();
+        tokio::spawn(async move {
+            loop {
+                message_notify.notified().await;
+                update_queue_messages(data.clone(), http.clone(), &queue)
+                    .await
+                    .ok();
+                tokio::time::sleep(Duration::from_secs_f32(1.0)).await;
+            }
+        });
+    }
+    tokio::spawn(matchmaking_worker(data.clone(), http, cache, queue));
+    // Match anyone who was already queued before the worker started
+    matchmake(&data, &queue);
+}
+
+enum MatchmakingOutcome {
+    NotEnoughPlayers,
+    /// No acceptable match was found, try again after the delay in seconds
+    Retry(f32),
+    /// The queued players can't form a match no matter how long they wait
+    Unmatchable(String),
+    /// A match was created with the given cost
+    Created(f32),
+}
+
+/// Takes a new match's players out of every queue in the guild and puts them in the match. Returns
+/// false without changing anything if another queue already matched one of them.
+fn claim_match_players(
+    data: &Data,
+    guild_id: GuildId,
+    guild_queues: &[QueueUuid],
+    members: &[Vec<UserId>],
+    match_id: MatchUuid,
+) -> bool {
+    let mut global_data = data.global_player_data.lock().unwrap();
+    let global_data = global_data.get_mut(&guild_id).unwrap();
+    // Players can be queued in several queues, so another queue may have matched them already
+    if members.iter().flatten().any(|player| {
+        global_data
+            .get(player)
+            .is_some_and(|player| matches!(player.queue_state, QueueState::InGame(_)))
+    }) {
+        return false;
+    }
+    for team in members.iter() {
+        for player in team {
+            for queue in guild_queues.iter() {
+                if let Some(mut queued_players) = data.queued_players.get_mut(queue) {
+                    queued_players.remove(player);
+                }
+            }
+            let global_data = global_data.get_mut(player).unwrap();
+            global_data.queue_state = QueueState::InGame(match_id);
+        }
+    }
+    true
+}
+
+/// Queued players who can be matched, leaving out shadow banned players who still appear queued
+fn matchable_players(data: &Data, queue_id: &QueueUuid) -> HashSet<UserId> {
+    let mut queued_players = data.queued_players.get(queue_id).unwrap().clone();
+    let bans = data.player_bans.get(queue_id).unwrap();
+    queued_players.retain(|p| !bans.contains_key(p));
+    queued_players
 }
 
 async fn try_matchmaking(
     data: Arc<Data>,
     cache_http: Arc<Http>,
+    cache: Arc<serenity::Cache>,
     guild_id: GuildId,
     queue_id: &QueueUuid,
-) -> Result<Option<f32>, Error> {
-    let (team_count, total_player_count) = {
+    ignore_cost_limit: bool,
+) -> Result<MatchmakingOutcome, Error> {
+    // A running captains draft holds its lobby until every pick is made
+    let mut drafted_teams = None;
+    let mut draft_timed_out = false;
+    if let Some((draft_complete, draft_abandoned, draft_message, pick_time)) =
+        data.drafts.get(queue_id).map(|draft| {
+            let queued_players = data.queued_players.get(queue_id).unwrap();
+            (
+                draft.pool.is_empty(),
+                draft
+                    .players()
+                    .any(|player| !queued_players.contains(player)),
+                draft.message,
+                draft.pick_started.elapsed(),
+            )
+        })
+    {
+        draft_timed_out = !draft_complete && pick_time >= DRAFT_PICK_TIMEOUT;
+        if draft_abandoned || draft_timed_out {
+            data.drafts.remove(queue_id);
+            let content = if draft_abandoned {
+                "Draft cancelled since a player left the queue"
+            } else {
+                "Draft cancelled since the captain took too long to pick, teams will be picked automatically"
+            };
+            if let Some((channel, message)) = draft_message {
+                channel
+                    .edit_message(
+                        &cache_http,
+                        message,
+                        EditMessage::new().content(content).components(vec![]),
+                    )
+                    .await
+                    .ok();
+            }
+        } else if draft_complete {
+            drafted_teams = data.drafts.remove(queue_id).map(|(_, draft)| draft.teams);
+        } else {
+            return Ok(MatchmakingOutcome::Retry(
+                (DRAFT_PICK_TIMEOUT - pick_time).as_secs_f32().max(1.0),
+            ));
+        }
+    }
+    let (team_count, full_player_count, min_start_players) = {
         let configuration = data.configuration.get(&queue_id).unwrap();
         let queued_players = data.queued_players.get(&queue_id).unwrap();
-        let total_player_count = configuration.team_count * configuration.team_size;
-        if (queued_players.len() as u32) < total_player_count {
-            return Ok(None);
+        let full_player_count = configuration.team_count * configuration.team_size;
+        let min_start_players = match configuration.min_start_players {
+            0 => full_player_count,
+            min_start_players => min_start_players.min(full_player_count),
+        };
+        if (queued_players.len() as u32) < min_start_players {
+            return Ok(MatchmakingOutcome::NotEnoughPlayers);
         }
-        (configuration.team_count, total_player_count)
+        (
+            configuration.team_count,
+            full_player_count,
+            min_start_players,
+        )
     };
     let config = {
         let config = data.configuration.get(&queue_id).unwrap();
@@ -1874,18 +4840,97 @@ async fn try_matchmaking(
     let Some(category) = config.category else {
         return Err(Error::from("No category"));
     };
-    let mut queued_players = data.queued_players.get(&queue_id).unwrap().clone();
+    if config.max_concurrent_matches > 0
+        && !ignore_cost_limit
+        && data
+            .current_games
+            .get(queue_id)
+            .is_some_and(|games| games.len() >= config.max_concurrent_matches as usize)
     {
-        let bans = data.player_bans.get(&queue_id).unwrap();
-        queued_players.retain(|p| !bans.contains_key(p));
+        // Resolving a match requests another matchmaking pass
+        return Ok(MatchmakingOutcome::Retry(60.0));
+    }
+    let queued_players = matchable_players(&data, queue_id);
+    // Shadow banned players still count as queued, so there may be too few players left to match
+    if (queued_players.len() as u32) < min_start_players {
+        return Ok(MatchmakingOutcome::NotEnoughPlayers);
     }
+    let total_player_count = if (queued_players.len() as u32) < full_player_count {
+        let longest_wait = {
+            let global_player_data = data.global_player_data.lock().unwrap();
+            let now = Utc::now();
+            queued_players
+                .iter()
+                .filter_map(|player| {
+                    match global_player_data.get(&guild_id)?.get(player)?.queue_state {
+                        QueueState::Queued(_, enter_time) => Some(now - enter_time),
+                        _ => None,
+                    }
+                })
+                .max()
+                .unwrap_or_default()
+        };
+        let required_wait = chrono::Duration::minutes(config.min_start_wait_minutes as i64);
+        if longest_wait < required_wait && !ignore_cost_limit {
+            let remaining_wait = (required_wait - longest_wait).num_seconds() as f32;
+            return Ok(MatchmakingOutcome::Retry(remaining_wait.max(1.0)));
+        }
+        queued_players.len() as u32
+    } else {
+        full_player_count
+    };
     println!("Trying matchmaking");
-    let members = greedy_matchmaking(data.clone(), queued_players, queue_id);
-    let Some(members) = members else {
+    let drafted = drafted_teams.is_some();
+    let members = drafted_teams.or_else(|| {
+        greedy_matchmaking(
+            data.clone(),
+            queued_players,
+            guild_id,
+            queue_id,
+            total_player_count,
+        )
+    });
+    let Some(mut members) = members else {
         println!("Could not find valid matchmaking");
-        let delay = 10.0;
-        return Ok(Some(delay));
+        return Ok(MatchmakingOutcome::Unmatchable(
+            "No valid teams can be formed from the queued players".to_string(),
+        ));
     };
+    if config.balance_teams && !drafted {
+        members = balance_teams(data.clone(), members, guild_id, queue_id);
+    }
+    // Departures of cached members are handled by the member removal event, so only players the
+    // cache doesn't know about need to be fetched
+    let cached_players: HashSet<UserId> = cache
+        .guild(guild_id)
+        .map(|guild| {
+            members
+                .iter()
+                .flatten()
+                .filter(|player| guild.members.contains_key(player))
+                .copied()
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut departed_players = vec![];
+    for player in members
+        .iter()
+        .flatten()
+        .filter(|player| !is_fake_player(player) && !cached_players.contains(player))
+    {
+        if let Err(err) = guild_id.member(&cache_http, *player).await {
+            if is_not_found(&err) {
+                departed_players.push(*player);
+            }
+        }
+    }
+    if !departed_players.is_empty() {
+        for player in departed_players {
+            println!("Removing {} from queue as they left the server", player);
+            remove_departed_member(data.clone(), cache_http.clone(), guild_id, player).await?;
+        }
+        return Ok(MatchmakingOutcome::Retry(1.0));
+    }
     let player_game_data = {
         let player_data = data.player_data.get(&queue_id).unwrap();
         members
@@ -1899,6 +4944,7 @@ async fn try_matchmaking(
     };
     let global_player_data = {
         let player_data = data.global_player_data.lock().unwrap();
+        let player_data = player_data.get(&guild_id).unwrap();
         members
             .iter()
             .map(|team| {
@@ -1913,6 +4959,7 @@ async fn try_matchmaking(
         game_categories: match_categories,
         lobby_host: host,
         roles: game_roles,
+        ..
     } = evaluate_cost(
         data.clone(),
         &members,
@@ -1920,6 +4967,37 @@ async fn try_matchmaking(
         &global_player_data,
         queue_id,
     );
+    if ignore_cost_limit
+        && !config.role_combinations.is_empty()
+        && game_roles.iter().flatten().any(|role| role.is_empty())
+    {
+        println!("Could not find valid role assignment");
+        return Ok(MatchmakingOutcome::Unmatchable(
+            "No valid role assignment exists for the queued players".to_string(),
+        ));
+    }
+    let shot_callers = if config.shot_callers {
+        members
+            .iter()
+            .zip(player_game_data.iter())
+            .filter_map(|(team, team_data)| {
+                team.iter()
+                    .zip(team_data)
+                    .max_by(|(_, player), (_, other_player)| {
+                        let rating = |player: &DerivedPlayerData| {
+                            player
+                                .rating
+                                .unwrap_or(config.default_player_data.rating)
+                                .rating
+                        };
+                        rating(player).total_cmp(&rating(other_player))
+                    })
+                    .map(|(player, _)| *player)
+            })
+            .collect_vec()
+    } else {
+        vec![]
+    };
     let game_roles = game_roles
         .iter()
         .map(|team_roles| {
@@ -1935,30 +5013,54 @@ async fn try_matchmaking(
                 .collect_vec()
         })
         .collect_vec();
-    if cost_eval > config.maximum_queue_cost {
-        println!("Best option has cost of {}", cost_eval);
-        let delay = (cost_eval - config.maximum_queue_cost) / total_player_count as f32 + 1.0;
-        return Ok(Some(delay));
+    if cost_eval.is_infinite() {
+        return Ok(MatchmakingOutcome::Unmatchable(
+            "Every possible match contains a blacklisted pair".to_string(),
+        ));
+    }
+    if cost_eval > config.maximum_queue_cost && !ignore_cost_limit && !drafted {
+        let excess_cost = cost_eval - config.maximum_queue_cost;
+        let delay = if config.normalize_queue_cost {
+            println!("Best option has cost of {} per player", cost_eval);
+            excess_cost + 1.0
+        } else {
+            println!("Best option has cost of {}", cost_eval);
+            excess_cost / total_player_count as f32 + 1.0
+        };
+        return Ok(MatchmakingOutcome::Retry(delay));
+    }
+    if config.draft_mode
+        && !drafted
+        && !draft_timed_out
+        && !ignore_cost_limit
+        && start_draft(&data, &cache_http, guild_id, queue_id, &members).await?
+    {
+        return Ok(MatchmakingOutcome::Retry(DRAFT_PICK_TIMEOUT.as_secs_f32()));
+    }
+    let new_id = MatchUuid::new();
+    let guild_queues = data
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .map(|guild_data| guild_data.queues.clone())
+        .unwrap_or_default();
+    if !claim_match_players(&data, guild_id, &guild_queues, &members, new_id) {
+        return Ok(MatchmakingOutcome::Retry(1.0));
+    }
+    for queue in guild_queues.iter().filter(|queue| *queue != queue_id) {
+        if let Some(notify) = data.message_edit_notify.get(queue) {
+            notify.notify_one();
+        }
     }
     let new_idx = {
-        let mut queue_idx = data.queue_idx.get_mut(&queue_id).unwrap();
+        let mut queue_idx = data.queue_idx.get_mut(queue_id).unwrap();
         *queue_idx += 1;
         *queue_idx
     };
-    let new_id = MatchUuid::new();
-
-    {
-        let mut global_data = data.global_player_data.lock().unwrap();
-        for team in members.iter() {
-            for player in team {
-                data.queued_players
-                    .get_mut(&queue_id)
-                    .unwrap()
-                    .remove(player);
-                let global_data = global_data.get_mut(player).unwrap();
-                global_data.queue_state = QueueState::InGame;
-            }
-        }
+    // Subscribers still waiting want to hear about the next match
+    if let Some(mut subscribers) = data.queue_notify_subscribers.get_mut(queue_id) {
+        subscribers.retain(|subscriber| !members.iter().flatten().contains(subscriber));
     }
     let permissions = members
         .iter()
@@ -1993,13 +5095,14 @@ async fn try_matchmaking(
                 }),
         )
         .collect_vec();
+    let team_names = config.team_labels(team_count as usize);
     let (match_channel, vc_channels) = future::join(
         CreateChannel::new(format!("match-{}", new_idx))
             .category(category.clone())
             .permissions(permissions.clone())
             .execute(cache_http.clone(), guild_id),
         future::join_all((0..team_count).map(|i| {
-            CreateChannel::new(format!("Team {} - #{}", i + 1, new_idx))
+            CreateChannel::new(format!("{} - #{}", team_names[i as usize], new_idx))
                 .category(category.clone())
                 .permissions(permissions.clone())
                 .kind(ChannelType::Voice)
@@ -2016,6 +5119,13 @@ async fn try_matchmaking(
         async {
             let mut members_message = String::new();
             members_message += format!("# Queue#{}\n", new_idx).as_str();
+            if total_player_count < full_player_count {
+                members_message += format!(
+                    "**Short-handed match:** started with {} of {} players\n",
+                    total_player_count, full_player_count
+                )
+                .as_str();
+            }
             for (category_name, value) in match_categories {
                 members_message += format!(
                     "{}: {}\n",
@@ -2027,6 +5137,13 @@ async fn try_matchmaking(
             if let Some(previous_members) = host
                 .map(|host| get_previous_game_members(&data, queue_id, host))
                 .flatten()
+                // Lobbies can only be laid out like the previous one if the teams are the same size
+                .filter(|previous_members| {
+                    previous_members
+                        .iter()
+                        .map(Vec::len)
+                        .eq(members_copy.iter().map(Vec::len))
+                })
             {
                 let sorted_members: Vec<Vec<(UserId, String, bool)>> = previous_members
                     .iter()
@@ -2078,41 +5195,62 @@ async fn try_matchmaking(
                     })
                     .collect_vec();
 
-                for (team_idx, team) in sorted_members.iter().enumerate() {
-                    members_message += format!("## Team {}\n", team_idx + 1).as_str();
+                for (team, team_name) in sorted_members.iter().zip(team_names.iter()) {
+                    members_message += format!("## {}\n", team_name).as_str();
                     let team_copy = team.clone();
                     for (player, role, unchanged) in team_copy {
                         members_message += format!(
-                            "{} {} {}\n",
+                            "{} {} {}{}\n",
                             player.mention(),
                             role,
-                            if unchanged { "" } else { "*" }
+                            if unchanged { "" } else { "*" },
+                            shot_caller_label(&shot_callers, &player)
                         )
                         .as_str();
                     }
                 }
             } else {
-                for (team_idx, team) in members_copy
+                for (team, team_name) in members_copy
                     .iter()
                     .zip(game_roles.iter())
                     .map(|(members, roles)| members.iter().zip(roles.iter()))
-                    .enumerate()
+                    .zip(team_names.iter())
                 {
-                    members_message += format!("## Team {}\n", team_idx + 1).as_str();
+                    members_message += format!("## {}\n", team_name).as_str();
                     let team_copy = team.clone();
                     for (player, role) in team_copy {
-                        members_message += format!("{} {}\n", player.mention(), role).as_str();
+                        members_message += format!(
+                            "{} {}{}\n",
+                            player.mention(),
+                            role,
+                            shot_caller_label(&shot_callers, player)
+                        )
+                        .as_str();
                     }
                 }
             }
             if let Some(host) = host {
                 members_message += format!("## Host: {}\n", host.mention()).as_str();
             }
+            // Everyone is mentioned, but only players who opted in are pinged
+            let pinged_players = {
+                let player_data = data.player_data.get(queue_id).unwrap();
+                members_copy
+                    .iter()
+                    .flatten()
+                    .filter(|player| {
+                        player_data
+                            .get(player)
+                            .is_some_and(|player| player.ping_on_match_start)
+                    })
+                    .cloned()
+                    .collect_vec()
+            };
             let mut message = CreateMessage::default()
                 .allowed_mentions(
                     CreateAllowedMentions::default()
                         .all_roles(false)
-                        .all_users(true),
+                        .users(pinged_players),
                 )
                 .content(members_message);
             if host.is_none() {
@@ -2126,19 +5264,12 @@ async fn try_matchmaking(
                 .await
                 .ok();
             let mut map_vote_end_time = None;
-            let mut map_pool = config.maps.iter().collect_vec();
-            if config.prevent_recent_maps {
-                let previous_maps: HashSet<String> = members_copy
-                    .iter()
-                    .flat_map(|t| t)
-                    .flat_map(|member| get_previous_map(&data, queue_id, *member))
-                    .sorted()
-                    .dedup()
-                    .take(map_pool.len() - config.map_vote_count as usize)
-                    .collect();
-                map_pool.retain(|m| !previous_maps.contains(*m));
-            }
-            if config.map_vote_count > 0 {
+            let mut map_vote_message_id = None;
+            let mut chosen_map = None;
+            let mut vote_messages = vec![];
+            let map_pool = match_map_pool(&data, &config, queue_id, &members_copy);
+            let map_vote_count = config.effective_map_vote_count();
+            if map_vote_count > 0 {
                 let mut map_vote_message_content = "# Map Vote".to_string();
                 if config.map_vote_time > 0 {
                     map_vote_end_time = Some(
@@ -2148,34 +5279,38 @@ async fn try_matchmaking(
                     map_vote_message_content +=
                         format!("\nEnds <t:{}:R>", map_vote_end_time.unwrap()).as_str();
                 }
-                let mut map_vote_message =
-                    CreateMessage::default().content(map_vote_message_content);
                 let vote_maps = map_pool
-                    .choose_multiple(&mut rand::thread_rng(), config.map_vote_count as usize)
+                    .choose_multiple(&mut rand::thread_rng(), map_vote_count)
                     .map(|m| *m)
                     .cloned()
                     .collect_vec();
-                for rand_map in vote_maps.iter() {
-                    map_vote_message =
-                        map_vote_message.button(ButtonData::MapVote(rand_map.clone()).get_button());
-                }
+                let map_vote_message = create_map_vote_message(
+                    map_vote_message_content,
+                    &vote_maps,
+                    config.map_vote_select_menu,
+                    &config.maps,
+                );
                 let mut map_message = match_channel
                     .send_message(cache_http_copy.clone(), map_vote_message)
                     .await?;
+                map_vote_message_id = Some(map_message.id);
+                vote_messages.push(map_message.id);
                 if config.map_vote_time > 0 {
                     let ctx1 = Arc::clone(&cache_http_copy);
                     let data = data.clone();
                     tokio::spawn(async move {
                         tokio::time::sleep(Duration::from_secs(config.map_vote_time as u64)).await;
-                        if map_message.components.is_empty() {
-                            return;
-                        }
                         let vote_result = {
-                            let match_data = data.match_data.lock().unwrap();
-                            let Some(match_data) = match_data.get(&new_id) else {
+                            let mut match_data = data.match_data.lock().unwrap();
+                            let Some(match_data) = match_data.get_mut(&new_id) else {
                                 return;
                             };
-                            match_data
+                            // The vote was ended or restarted by an admin in the meantime
+                            if match_data.map_vote_message != Some(map_message.id) {
+                                return;
+                            }
+                            match_data.map_vote_message = None;
+                            let vote_result = match_data
                                 .map_votes
                                 .iter()
                                 .counts_by(|(_, vote)| vote)
@@ -2183,7 +5318,9 @@ async fn try_matchmaking(
                                 .max_by_key(|(_category, vote_count)| *vote_count)
                                 .map(|(category, _vote_count)| (*category).clone())
                                 .unwrap_or(vote_maps.first().unwrap().clone())
-                                .clone()
+                                .clone();
+                            match_data.map = Some(vote_result.clone());
+                            vote_result
                         };
                         let content = format!("# Map: {}", vote_result);
 
@@ -2197,31 +5334,75 @@ async fn try_matchmaking(
                     });
                 }
             } else if config.maps.len() > 0 {
-                let chosen_map = map_pool.choose(&mut rand::thread_rng()).unwrap();
-                let map_vote_message =
-                    CreateMessage::default().content(format!("# Map: {}", chosen_map));
-                match_channel
+                let map = map_pool.choose(&mut rand::thread_rng()).unwrap();
+                let mut map_vote_message =
+                    CreateMessage::default().content(format!("# Map: {}", map));
+                if config.max_map_rerolls > 0 && config.maps.len() > 1 {
+                    map_vote_message = map_vote_message.button(ButtonData::MapReroll.get_button());
+                }
+                let map_message = match_channel
                     .send_message(cache_http_copy.clone(), map_vote_message)
                     .await?;
+                vote_messages.push(map_message.id);
+                chosen_map = Some(map.to_string());
             }
-            let mut result_message = CreateMessage::default();
-            for i in 0..team_count {
-                result_message =
-                    result_message.button(ButtonData::ResultVote(MatchResult::Team(i)).get_button())
-            }
-            match_channel
-                .send_message(
-                    cache_http_copy.clone(),
-                    result_message
-                        .button(ButtonData::ResultVote(MatchResult::Tie).get_button())
-                        .button(ButtonData::ResultVote(MatchResult::Cancel).get_button()),
+            let match_start_time = std::time::UNIX_EPOCH.elapsed().unwrap().as_secs();
+            record_match_start(&data, queue_id, match_start_time);
+            let mut result_content = if config.show_rosters_in_result_vote {
+                get_team_rosters(&members_copy, &team_names)
+            } else {
+                String::new()
+            };
+            if config.min_match_duration > 0 {
+                result_content += format!(
+                    "Results lock in <t:{}:R>\n",
+                    match_start_time + config.min_match_duration as u64
                 )
+                .as_str();
+            }
+            let mut result_message = create_result_vote_message(&team_names);
+            if !result_content.is_empty() {
+                result_message = result_message.content(result_content);
+            }
+            let result_message = match_channel
+                .send_message(cache_http_copy.clone(), result_message)
                 .await?;
+            vote_messages.push(result_message.id);
+            if config.mvp_vote {
+                let mut mvp_selects = vec![];
+                // Messages fit at most 5 select menus of 25 options each
+                for (team_idx, team) in members_copy.iter().enumerate().take(5) {
+                    let mut options = vec![];
+                    for player in team.iter().take(25) {
+                        let name = match cache_http_copy.get_member(guild_id, *player).await {
+                            Ok(member) => member.display_name().to_string(),
+                            Err(_) => player.to_string(),
+                        };
+                        options.push(CreateSelectMenuOption::new(name, player.to_string()));
+                    }
+                    mvp_selects.push(CreateActionRow::SelectMenu(
+                        CreateSelectMenu::new(
+                            ButtonData::MvpVote(team_idx).get_id(),
+                            serenity::CreateSelectMenuKind::String { options },
+                        )
+                        .placeholder(format!("{} MVP", team_names[team_idx])),
+                    ));
+                }
+                let mvp_message = match_channel
+                    .send_message(
+                        cache_http_copy.clone(),
+                        CreateMessage::default()
+                            .content("# MVP Vote\nVote for the MVP of your team")
+                            .components(mvp_selects),
+                    )
+                    .await?;
+                vote_messages.push(mvp_message.id);
+            }
             {
                 let mut channels = data.match_channels.lock().unwrap();
                 channels.insert(match_channel.id, new_id);
             }
-            {
+            let new_match = {
                 let mut match_data = data.match_data.lock().unwrap();
                 let channels = vc_channels_copy
                     .iter()
@@ -2241,8 +5422,59 @@ async fn try_matchmaking(
                         resolved: false,
                         name: format!("#{}", new_idx),
                         queue: queue_id.clone(),
+                        map: chosen_map,
+                        map_reroll_votes: HashSet::new(),
+                        map_rerolls: 0,
+                        final_result: None,
+                        locked: false,
+                        leaver_reports: HashMap::new(),
+                        mvp_votes: HashMap::new(),
+                        mvps: vec![],
+                        roles: game_roles.clone(),
+                        match_start_time: Some(match_start_time),
+                        series_games: vec![],
+                        map_vote_message: map_vote_message_id,
+                        cancel_voters: vec![],
+                        result_vote_message: Some(result_message.id),
+                        surrender_votes: HashSet::new(),
+                        result_proof: vec![],
+                        awaiting_proof_since: None,
+                        shot_callers: shot_callers.clone(),
+                        vote_messages,
                     },
                 );
+                metrics::set_active_matches(match_data.len());
+                data.current_games
+                    .entry(*queue_id)
+                    .or_default()
+                    .insert(new_id);
+                webhook::send_match_webhook(&data, new_id, &match_data[&new_id], None);
+                match_data[&new_id].clone()
+            };
+            send_match_announcement(&data, &cache_http_copy, &new_match)
+                .await
+                .ok();
+            if config.result_vote_time > 0 {
+                tokio::spawn(expire_result_vote(
+                    data.clone(),
+                    cache_http_copy.clone(),
+                    Some(cache.clone()),
+                    guild_id,
+                    new_id,
+                    match_channel.id,
+                    0,
+                ));
+            }
+            if config.min_match_duration > 0 {
+                tokio::spawn(lock_in_result_vote(
+                    data.clone(),
+                    cache_http_copy.clone(),
+                    Some(cache.clone()),
+                    guild_id,
+                    new_id,
+                    match_channel.id,
+                    config.min_match_duration,
+                ));
             }
             Ok::<(), Error>(())
         },
@@ -2260,6 +5492,7 @@ async fn try_matchmaking(
                 .map(|(team_vc, team, http)| {
                     future::join_all(
                         team.into_iter()
+                            .filter(|player| !is_fake_player(player))
                             .map(|player| (team_vc, player, http.clone()))
                             .map(|(team_vc, player, http)| {
                                 guild_id.move_member(http, player, team_vc.id)
@@ -2270,7 +5503,31 @@ async fn try_matchmaking(
     )
     .await
     .0?;
-    Ok(None)
+    metrics::record_match_formed(queue_id, cost_eval);
+    Ok(MatchmakingOutcome::Created(cost_eval))
+}
+
+/// Marks a player in the match roster if they're their team's shot-caller
+fn shot_caller_label(shot_callers: &[UserId], player: &UserId) -> &'static str {
+    if shot_callers.contains(player) {
+        " (shot-caller)"
+    } else {
+        ""
+    }
+}
+
+fn get_team_rosters(members: &[Vec<UserId>], team_names: &[String]) -> String {
+    members
+        .iter()
+        .zip(team_names)
+        .map(|(team, team_name)| {
+            format!(
+                "{}: {}\n",
+                team_name,
+                team.iter().map(|player| player.mention()).join(", ")
+            )
+        })
+        .join("")
 }
 
 fn get_previous_game_members(
@@ -2287,17 +5544,43 @@ fn get_previous_game_members(
         .game_history
         .last()
         .cloned();
-    host_last_game.map(|host_last_game| {
+    host_last_game.and_then(|host_last_game| {
         data.historical_match_data
             .lock()
             .unwrap()
             .get(&host_last_game)
-            .unwrap()
-            .members
-            .clone()
+            .map(|last_game| last_game.members.clone())
     })
 }
 
+/// Maps a new match can be played on, leaving out the members' previous maps if the queue prevents
+/// recent maps
+fn match_map_pool<'a>(
+    data: &Arc<Data>,
+    config: &'a QueueConfiguration,
+    queue_id: &QueueUuid,
+    members: &[Vec<UserId>],
+) -> Vec<&'a String> {
+    let mut map_pool = config.maps.keys().collect_vec();
+    if config.prevent_recent_maps {
+        let previous_maps: HashSet<String> = members
+            .iter()
+            .flatten()
+            .flat_map(|member| get_previous_map(data, queue_id, *member))
+            .sorted()
+            .dedup()
+            // Enough maps are left for the ballot, or for the random pick without one
+            .take(
+                map_pool
+                    .len()
+                    .saturating_sub(config.effective_map_vote_count().max(1)),
+            )
+            .collect();
+        map_pool.retain(|m| !previous_maps.contains(*m));
+    }
+    map_pool
+}
+
 fn get_previous_map(data: &Arc<Data>, queue_id: &QueueUuid, player: UserId) -> Option<String> {
     let Some(player_last_game) = data
         .player_data
@@ -2311,11 +5594,13 @@ fn get_previous_map(data: &Arc<Data>, queue_id: &QueueUuid, player: UserId) -> O
     else {
         return None;
     };
-    data.historical_match_data
-        .lock()
-        .unwrap()
-        .get(&player_last_game)
-        .unwrap()
+    let historical_match_data = data.historical_match_data.lock().unwrap();
+    // Pruned matches may still be referenced for a moment
+    let last_game = historical_match_data.get(&player_last_game)?;
+    if let Some(map) = &last_game.map {
+        return Some(map.clone());
+    }
+    last_game
         .map_votes
         .iter()
         .into_group_map_by(|vote| vote.1)
@@ -2328,11 +5613,60 @@ fn get_previous_map(data: &Arc<Data>, queue_id: &QueueUuid, player: UserId) -> O
 
 struct LobbyEvaluation {
     cost: f32,
+    breakdown: CostBreakdown,
     game_categories: HashMap<String, usize>,
     lobby_host: Option<UserId>,
     roles: Vec<Vec<String>>,
 }
 
+/// Terms of a lobby's cost, before it's normalized by the lobby size
+struct CostBreakdown {
+    host: f32,
+    roles: f32,
+    blacklist: f32,
+    fake_mix: f32,
+    avoided_players: f32,
+    unregistered_mix: f32,
+    preferences: f32,
+    /// Rating and game category mismatches, weighted by `wait_vs_fairness`
+    fairness: f32,
+    /// Time spent in queue, weighted by `wait_vs_fairness`. Subtracted from the cost.
+    wait: f32,
+}
+
+impl CostBreakdown {
+    fn total(&self) -> f32 {
+        self.host
+            + self.roles
+            + self.blacklist
+            + self.fake_mix
+            + self.avoided_players
+            + self.unregistered_mix
+            + self.preferences
+            + self.fairness
+            - self.wait
+    }
+
+    fn terms(&self) -> [(&'static str, f32); 9] {
+        [
+            ("Lobby host", self.host),
+            ("Roles", self.roles),
+            ("Blacklisted pairs", self.blacklist),
+            ("Test players mixed with real players", self.fake_mix),
+            ("Avoided players", self.avoided_players),
+            ("Unregistered players mixed in", self.unregistered_mix),
+            ("Preferences", self.preferences),
+            ("Fairness", self.fairness),
+            ("Wait time", -self.wait),
+        ]
+    }
+}
+
+/// Orders a pair of players so each pair is only stored once
+fn blacklisted_pair(player: UserId, other_player: UserId) -> (UserId, UserId) {
+    (player.min(other_player), player.max(other_player))
+}
+
 fn evaluate_cost(
     data: Arc<Data>,
     player_ids: &Vec<Vec<UserId>>,
@@ -2347,6 +5681,15 @@ fn evaluate_cost(
         max_lobby_keep_time,
         role_combinations,
         incorrect_roles_cost,
+        wait_vs_fairness,
+        unregistered_mix_cost,
+        preferences,
+        designated_hosts,
+        avoided_player_cost,
+        max_avoided_players,
+        normalize_queue_cost,
+        party_coordination_mmr_bonus,
+        placement_games,
     ) = {
         let config = data.configuration.get(&queue_id).unwrap();
         (
@@ -2356,8 +5699,19 @@ fn evaluate_cost(
             config.max_lobby_keep_time.clone(),
             config.role_combinations.clone(),
             config.incorrect_roles_cost,
+            config.wait_vs_fairness.clamp(0.0, 1.0),
+            config.unregistered_mix_cost,
+            config.preferences.clone(),
+            config.designated_hosts.clone(),
+            config.avoided_player_cost,
+            config.max_avoided_players,
+            config.normalize_queue_cost,
+            config.party_coordination_mmr_bonus,
+            config.placement_games,
         )
     };
+    let fairness_weight = 2.0 * (1.0 - wait_vs_fairness);
+    let wait_weight = 2.0 * wait_vs_fairness;
 
     let (host_cost, lobby_host) = {
         let historical_matches = data.historical_match_data.lock().unwrap();
@@ -2431,22 +5785,49 @@ fn evaluate_cost(
         } else {
             0.0
         };
+        // New lobbies are hosted by a designated host when one is present, which costs the same as
+        // any other new lobby
+        let lobby_host = lobby_host.or_else(|| {
+            player_ids
+                .iter()
+                .flatten()
+                .filter(|player| designated_hosts.contains(player))
+                .min()
+                .cloned()
+        });
         (host_cost, lobby_host)
     };
-    let team_mmrs = player_data.iter().map(|team| {
-        team.iter()
-            .map(|player| player.rating.unwrap_or(default_player_data.rating).rating as f32)
-            .sum::<f32>()
-            / team_size as f32
-    });
-    let team_mmr_stds = player_data
+    let party_sizes = global_player_data
+        .iter()
+        .flatten()
+        .filter_map(|player| player.party)
+        .counts();
+    let effective_ratings = player_data
+        .iter()
+        .zip(global_player_data.iter())
+        .map(|(team, global_team)| {
+            team.iter()
+                .zip(global_team.iter())
+                .map(|(player, global_player)| {
+                    let party_size = global_player
+                        .party
+                        .map(|party| party_sizes[&party])
+                        .unwrap_or(1);
+                    player.rating.unwrap_or(default_player_data.rating).rating as f32
+                        + party_coordination_mmr_bonus * (party_size - 1) as f32
+                })
+                .collect_vec()
+        })
+        .collect_vec();
+    let team_mmrs = effective_ratings
+        .iter()
+        .map(|team| team.iter().sum::<f32>() / team_size as f32);
+    let team_mmr_stds = effective_ratings
         .iter()
         .zip(team_mmrs.clone())
         .map(|(team, team_mmr)| {
             team.iter()
-                .map(|player| {
-                    player.rating.unwrap_or(default_player_data.rating).rating as f32 - team_mmr
-                })
+                .map(|rating| rating - team_mmr)
                 .map(|rating| rating * rating)
                 .sum::<f32>()
                 / team_size as f32
@@ -2462,12 +5843,13 @@ fn evaluate_cost(
         MinMaxResult::OneElement(_) => 0.0,
         MinMaxResult::MinMax(min, max) => max - min,
     };
-    let mmr_range = player_data
+    // Players still in their placement games can be matched against any rating
+    let mmr_range = effective_ratings
         .iter()
-        .flat_map(|team| {
-            team.iter()
-                .map(|player| player.rating.unwrap_or(default_player_data.rating).rating as f32)
-        })
+        .flatten()
+        .zip(player_data.iter().flatten())
+        .filter(|(_, player)| player.stats.games_played() >= placement_games)
+        .map(|(rating, _)| rating)
         .minmax();
     let mmr_range = match mmr_range {
         MinMaxResult::NoElements => 0.0,
@@ -2486,8 +5868,13 @@ fn evaluate_cost(
                 category_name.clone(),
                 player_categories
                     .iter()
-                    .map(|player_categories| player_categories[category_name].clone())
-                    .flatten()
+                    .flat_map(|player_categories| {
+                        // Players queued before the category was added have no entry for it
+                        player_categories
+                            .get(category_name)
+                            .cloned()
+                            .unwrap_or_default()
+                    })
                     .counts()
                     .into_iter()
                     .max_by_key(|&(_category, count)| count)
@@ -2552,41 +5939,136 @@ fn evaluate_cost(
         })
         .collect_vec();
 
-    let now = chrono::offset::Utc::now();
-    let cost = host_cost
-        + role_cost
-        + player_data
-            .iter()
-            .flat_map(|team| team.iter())
-            .zip(global_player_data.iter().flat_map(|team| team.iter()))
-            .zip(player_categories.iter())
-            .map(|((player, global_player), player_categories)| {
-                let queue_config = player
-                    .player_queueing_config
-                    .derive(&default_player_data.player_queueing_config);
-                let time_in_queue = match global_player.queue_state {
-                    QueueState::None | QueueState::InGame => 0,
-                    QueueState::Queued(_, queue_time) => (now - queue_time).num_seconds(),
-                };
-                (mmr_differential - queue_config.acceptable_mmr_differential).max(0.0)
-                    * queue_config.cost_per_avg_mmr_differential
-                    + (mmr_std_differential - queue_config.acceptable_mmr_std_differential).max(0.0)
-                        * queue_config.cost_per_mmr_std_differential
-                    + (mmr_range - queue_config.acceptable_mmr_range).max(0.0)
-                        * queue_config.cost_per_mmr_range
-                    + queue_config
-                        .wrong_game_category_cost
+    let (unregistered_count, registered_count) = player_data
+        .iter()
+        .map(|team| {
+            let unregistered = team.iter().filter(|player| player.rating.is_none()).count();
+            (unregistered, team.len() - unregistered)
+        })
+        .fold((0, 0), |(unregistered, registered), team| {
+            (unregistered + team.0, registered + team.1)
+        });
+    let unregistered_cost = unregistered_mix_cost * unregistered_count.min(registered_count) as f32;
+
+    let preference_cost = preferences
+        .iter()
+        .map(|(dimension_name, dimension)| {
+            let player_preferences = player_data
+                .iter()
+                .flatten()
+                .filter_map(|player| player.preferences.get(dimension_name))
+                .collect_vec();
+            let most_accepted = dimension
+                .values
+                .iter()
+                .map(|value| {
+                    player_preferences
                         .iter()
-                        .filter(|(category, _)| {
-                            !player_categories[*category].contains(&game_categories[*category])
-                        })
-                        .map(|(_, cost)| cost)
-                        .sum::<f32>()
-                    - time_in_queue as f32
+                        .filter(|accepted| accepted.contains(value))
+                        .count()
+                })
+                .max()
+                .unwrap_or(0);
+            dimension.mismatch_cost * (player_preferences.len() - most_accepted) as f32
+        })
+        .sum::<f32>();
+
+    let lobby_players = player_ids.iter().flatten().collect::<HashSet<_>>();
+    let blacklist_cost = if data.blacklisted_pairs.get(queue_id).is_some_and(|pairs| {
+        pairs.iter().any(|(player, other_player)| {
+            lobby_players.contains(player) && lobby_players.contains(other_player)
+        })
+    }) {
+        f32::INFINITY
+    } else {
+        0.0
+    };
+    // Players added by `queue_many` are only ever matched with each other
+    let fake_mix_cost = if lobby_players.iter().any(|player| is_fake_player(player))
+        && !lobby_players.iter().all(|player| is_fake_player(player))
+    {
+        f32::INFINITY
+    } else {
+        0.0
+    };
+
+    // Lists can outgrow the limit if it's lowered, so only the allowed number count
+    let avoid_cost = avoided_player_cost
+        * player_data
+            .iter()
+            .flatten()
+            .map(|player| {
+                player
+                    .avoided_players
+                    .iter()
+                    .filter(|avoided| lobby_players.contains(avoided))
+                    .count()
+                    .min(max_avoided_players as usize)
             })
-            .sum::<f32>();
+            .sum::<usize>() as f32;
+
+    let now = chrono::offset::Utc::now();
+    let (fairness_cost, wait_cost) = player_data
+        .iter()
+        .flat_map(|team| team.iter())
+        .zip(global_player_data.iter().flat_map(|team| team.iter()))
+        .zip(player_categories.iter())
+        .map(|((player, global_player), player_categories)| {
+            let queue_config = player
+                .player_queueing_config
+                .derive(&default_player_data.player_queueing_config);
+            let time_in_queue = match global_player.queue_state {
+                QueueState::None | QueueState::InGame(_) => 0,
+                QueueState::Queued(_, queue_time) => (now - queue_time).num_seconds(),
+            };
+            let fairness_cost = (mmr_differential - queue_config.acceptable_mmr_differential)
+                .max(0.0)
+                * queue_config.cost_per_avg_mmr_differential
+                + (mmr_std_differential - queue_config.acceptable_mmr_std_differential).max(0.0)
+                    * queue_config.cost_per_mmr_std_differential
+                + (mmr_range - queue_config.acceptable_mmr_range).max(0.0)
+                    * queue_config.cost_per_mmr_range
+                + queue_config
+                    .wrong_game_category_cost
+                    .iter()
+                    .filter(|(category, _)| {
+                        game_categories.get(*category).is_some_and(|game_category| {
+                            !player_categories
+                                .get(*category)
+                                .is_some_and(|categories| categories.contains(game_category))
+                        })
+                    })
+                    .map(|(_, cost)| cost)
+                    .sum::<f32>();
+            (
+                fairness_weight * fairness_cost,
+                wait_weight * time_in_queue as f32,
+            )
+        })
+        .fold((0.0, 0.0), |(fairness, wait), player| {
+            (fairness + player.0, wait + player.1)
+        });
+    let breakdown = CostBreakdown {
+        host: host_cost,
+        roles: role_cost,
+        blacklist: blacklist_cost,
+        fake_mix: fake_mix_cost,
+        avoided_players: avoid_cost,
+        unregistered_mix: unregistered_cost,
+        preferences: preference_cost,
+        fairness: fairness_cost,
+        wait: wait_cost,
+    };
+    let cost = breakdown.total();
+    // Most terms are summed per player, so bigger lobbies cost more unless normalized
+    let cost = if normalize_queue_cost {
+        cost / lobby_players.len().max(1) as f32
+    } else {
+        cost
+    };
     LobbyEvaluation {
         cost,
+        breakdown,
         game_categories,
         lobby_host,
         roles,
@@ -2596,11 +6078,23 @@ fn evaluate_cost(
 fn greedy_matchmaking(
     data: Arc<Data>,
     pool: HashSet<UserId>,
+    guild_id: GuildId,
     queue_id: &QueueUuid,
+    total_players: u32,
 ) -> Option<Vec<Vec<UserId>>> {
-    let team_size = data.configuration.get(&queue_id).unwrap().team_size;
     let team_count = data.configuration.get(&queue_id).unwrap().team_count;
-    let total_players = team_size * team_count;
+    // Short-handed matches spread their players as evenly as possible
+    let team_size = data
+        .configuration
+        .get(queue_id)
+        .unwrap()
+        .team_size
+        .min(total_players.div_ceil(team_count));
+    let split_oversized_parties = data
+        .configuration
+        .get(queue_id)
+        .unwrap()
+        .split_oversized_parties;
     let mut players = pool.clone();
     let mut result = vec![vec![]; team_count as usize];
     let mut player_count = 0;
@@ -2611,70 +6105,67 @@ fn greedy_matchmaking(
         let mut best_next_result = vec![];
         let mut best_added_players = vec![];
         'additions_loop: for possible_addition in players.iter() {
-            for team_idx in 0..team_count as usize {
-                if result[team_idx].len() >= team_size as usize {
-                    continue;
-                }
-                let mut result_copy = result.clone();
-                let mut added_players = vec![];
-                if let Some(party) = data
-                    .global_player_data
-                    .lock()
-                    .unwrap()
-                    .get(possible_addition)
-                    .unwrap()
-                    .party
-                {
-                    for player in data
-                        .group_data
+            let party_members = data
+                .global_player_data
+                .lock()
+                .unwrap()
+                .get(&guild_id)
+                .unwrap()
+                .get(possible_addition)
+                .unwrap()
+                .party
+                .map(|party| {
+                    data.group_data
                         .lock()
                         .unwrap()
                         .get(&party)
                         .unwrap()
                         .players
+                        .clone()
+                });
+            let mut addition = match party_members {
+                Some(party_members) => {
+                    let placed = |player: &UserId| result.iter().flatten().contains(player);
+                    if party_members
                         .iter()
+                        .any(|player| !players.contains(player) && !placed(player))
                     {
-                        if !players.contains(player) {
-                            continue 'additions_loop;
-                        }
-                        added_players.push(player.clone());
-                        result_copy[team_idx].push(player.clone());
+                        continue 'additions_loop;
                     }
-                } else {
-                    added_players.push(possible_addition.clone());
-                    result_copy[team_idx].push(possible_addition.clone());
+                    if party_members.iter().any(placed) {
+                        // The party has already been split so the rest join individually
+                        vec![*possible_addition]
+                    } else {
+                        party_members.into_iter().collect_vec()
+                    }
+                }
+                None => vec![*possible_addition],
+            };
+            let most_open_slots = result
+                .iter()
+                .map(|team| team_size as usize - team.len())
+                .max()
+                .unwrap_or(0);
+            if addition.len() > most_open_slots {
+                if !split_oversized_parties {
+                    println!(
+                        "Party of {} does not fit in the {} open slots of any team",
+                        addition.len(),
+                        most_open_slots
+                    );
+                    continue 'additions_loop;
+                }
+                addition = vec![*possible_addition];
+            }
+            for team_idx in 0..team_count as usize {
+                if result[team_idx].len() + addition.len() > team_size as usize {
+                    continue;
                 }
+                let mut result_copy = result.clone();
+                let added_players = addition.clone();
+                result_copy[team_idx].extend(addition.iter().cloned());
 
-                let player_game_data = {
-                    let mut player_data = data.player_data.get_mut(&queue_id).unwrap();
-                    result_copy
-                        .iter()
-                        .map(|team| {
-                            team.iter()
-                                .map(|player| player_data.entry(*player).or_default().clone())
-                                .collect_vec()
-                        })
-                        .collect_vec()
-                };
-                let global_player_data = {
-                    let player_data = data.global_player_data.lock().unwrap();
-                    result_copy
-                        .iter()
-                        .map(|team| {
-                            team.iter()
-                                .map(|player| player_data.get(player).unwrap().clone())
-                                .collect_vec()
-                        })
-                        .collect_vec()
-                };
-                let cost = evaluate_cost(
-                    data.clone(),
-                    &result_copy,
-                    &player_game_data,
-                    &global_player_data,
-                    queue_id,
-                )
-                .cost;
+                let cost = lobby_cost(data.clone(), &result_copy, guild_id, queue_id);
                 if cost < min_cost {
                     min_cost = cost;
                     best_next_result = result_copy;
@@ -2696,6 +6187,123 @@ fn greedy_matchmaking(
     Some(result)
 }
 
+/// Evaluates the cost of a possibly partial lobby
+fn lobby_cost(
+    data: Arc<Data>,
+    teams: &Vec<Vec<UserId>>,
+    guild_id: GuildId,
+    queue_id: &QueueUuid,
+) -> f32 {
+    let player_game_data = {
+        let mut player_data = data.player_data.get_mut(queue_id).unwrap();
+        teams
+            .iter()
+            .map(|team| {
+                team.iter()
+                    .map(|player| player_data.entry(*player).or_default().clone())
+                    .collect_vec()
+            })
+            .collect_vec()
+    };
+    let global_player_data = {
+        let player_data = data.global_player_data.lock().unwrap();
+        let player_data = player_data.get(&guild_id).unwrap();
+        teams
+            .iter()
+            .map(|team| {
+                team.iter()
+                    .map(|player| player_data.get(player).unwrap().clone())
+                    .collect_vec()
+            })
+            .collect_vec()
+    };
+    evaluate_cost(
+        data.clone(),
+        teams,
+        &player_game_data,
+        &global_player_data,
+        queue_id,
+    )
+    .cost
+}
+
+/// Most swaps `balance_teams` tries, since it runs on the async runtime
+const MAX_BALANCE_SWAPS: usize = 1000;
+
+/// Rebalances the chosen players between teams by swapping equally sized groups of players while
+/// that lowers the lobby cost. Party members on the same team are always moved together.
+fn balance_teams(
+    data: Arc<Data>,
+    teams: Vec<Vec<UserId>>,
+    guild_id: GuildId,
+    queue_id: &QueueUuid,
+) -> Vec<Vec<UserId>> {
+    let mut swaps_left = MAX_BALANCE_SWAPS;
+    let mut team_units = {
+        let global_player_data = data.global_player_data.lock().unwrap();
+        let global_player_data = global_player_data.get(&guild_id).unwrap();
+        teams
+            .iter()
+            .map(|team| {
+                let mut units: Vec<Vec<UserId>> = vec![];
+                let mut party_units: HashMap<GroupUuid, usize> = HashMap::new();
+                for player in team {
+                    match global_player_data.get(player).and_then(|p| p.party) {
+                        Some(party) => match party_units.get(&party) {
+                            Some(unit_idx) => units[*unit_idx].push(*player),
+                            None => {
+                                party_units.insert(party, units.len());
+                                units.push(vec![*player]);
+                            }
+                        },
+                        None => units.push(vec![*player]),
+                    }
+                }
+                units
+            })
+            .collect_vec()
+    };
+    let flatten = |team_units: &Vec<Vec<Vec<UserId>>>| {
+        team_units
+            .iter()
+            .map(|units| units.iter().flatten().cloned().collect_vec())
+            .collect_vec()
+    };
+    let mut best_cost = lobby_cost(data.clone(), &flatten(&team_units), guild_id, queue_id);
+    'passes: loop {
+        let mut improved = false;
+        for team_a in 0..team_units.len() {
+            for team_b in (team_a + 1)..team_units.len() {
+                for unit_a in 0..team_units[team_a].len() {
+                    for unit_b in 0..team_units[team_b].len() {
+                        if team_units[team_a][unit_a].len() != team_units[team_b][unit_b].len() {
+                            continue;
+                        }
+                        if swaps_left == 0 {
+                            println!("Team balancing stopped early");
+                            break 'passes;
+                        }
+                        swaps_left -= 1;
+                        let mut swapped = team_units.clone();
+                        swapped[team_a][unit_a] = team_units[team_b][unit_b].clone();
+                        swapped[team_b][unit_b] = team_units[team_a][unit_a].clone();
+                        let cost = lobby_cost(data.clone(), &flatten(&swapped), guild_id, queue_id);
+                        if cost < best_cost {
+                            best_cost = cost;
+                            team_units = swapped;
+                            improved = true;
+                        }
+                    }
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+    flatten(&team_units)
+}
+
 /// Exports configuration
 #[poise::command(
     slash_command,
@@ -2769,13 +6377,7 @@ async fn queue(ctx: Context<'_>) -> Result<(), Error> {
                 .get(queue)
                 .unwrap()
                 .notify_one();
-            matchmake(
-                ctx.data().clone(),
-                ctx.serenity_context().http.clone(),
-                ctx.guild_id().unwrap(),
-                queue,
-            )
-            .await?;
+            matchmake(ctx.data(), queue);
             Ok(())
         }
         Err(reason) => {
@@ -2792,7 +6394,7 @@ async fn queue(ctx: Context<'_>) -> Result<(), Error> {
     prefix_command,
     default_member_permissions = "MANAGE_CHANNELS"
 )]
-async fn queue_many(ctx: Context<'_>, count: u32) -> Result<(), Error> {
+async fn queue_many(ctx: Context<'_>, #[max = 1000] count: u32) -> Result<(), Error> {
     let queues = ctx
         .data()
         .guild_data
@@ -2811,6 +6413,22 @@ async fn queue_many(ctx: Context<'_>, count: u32) -> Result<(), Error> {
         .await?;
         return Ok(());
     };
+    if !ctx
+        .data()
+        .queued_players
+        .get(queue)
+        .unwrap()
+        .iter()
+        .all(is_fake_player)
+    {
+        ctx.send(
+            CreateReply::default()
+                .content("Can only add test players to a queue without real players in it")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
     for i in 0..count {
         match try_queue_player(
             ctx.data().clone(),
@@ -2831,6 +6449,14 @@ async fn queue_many(ctx: Context<'_>, count: u32) -> Result<(), Error> {
             }
         }
     }
+    audit(
+        &ctx,
+        queue,
+        AuditAction::QueueMany,
+        AuditTarget::Queue,
+        format!("Queued {} test players", count),
+    )
+    .await?;
     let response = {
         let data_lock = ctx.data().queued_players.get(queue).unwrap();
         format!(
@@ -2848,17 +6474,249 @@ async fn queue_many(ctx: Context<'_>, count: u32) -> Result<(), Error> {
     Ok(())
 }
 
+/// Id of the first player added by `queue_test_report`, well above the ones `queue_many` uses
+const TEST_REPORT_FIRST_PLAYER: u64 = 1 << 21;
+
+/// Check that a queue can form matches, using fake players
+///
+/// The fake players are never queued and no channels are created, so real players aren't affected.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    default_member_permissions = "MANAGE_CHANNELS"
+)]
+async fn queue_test_report(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_id = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_id) => queue_id,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let data = ctx.data().clone();
+    let guild_id = ctx.guild_id().unwrap();
+    let config = data.configuration.get(&queue_id).unwrap().clone();
+    let full_player_count = config.team_count * config.team_size;
+    let fake_players = (0..full_player_count as u64)
+        .map(|i| UserId::new(TEST_REPORT_FIRST_PLAYER + i))
+        .collect::<HashSet<_>>();
+    {
+        let mut player_data = data.player_data.get_mut(&queue_id).unwrap();
+        if player_data.contains_key(&UserId::new(TEST_REPORT_FIRST_PLAYER)) {
+            drop(player_data);
+            ctx.send(
+                CreateReply::default()
+                    .content("A test report is already running for this queue.")
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+        let game_categories: HashMap<String, Vec<usize>> = config
+            .game_categories
+            .keys()
+            .map(|category_name| (category_name.clone(), vec![]))
+            .collect();
+        for player in fake_players.iter() {
+            player_data.insert(
+                *player,
+                DerivedPlayerData {
+                    game_categories: game_categories.clone(),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    {
+        let mut global_player_data = data.global_player_data.lock().unwrap();
+        let global_player_data = global_player_data.entry(guild_id).or_default();
+        for player in fake_players.iter() {
+            global_player_data.insert(*player, GlobalPlayerData::default());
+        }
+    }
+    // Everything is evaluated before any await so the fake players are always removed
+    let members = greedy_matchmaking(
+        data.clone(),
+        fake_players.clone(),
+        guild_id,
+        &queue_id,
+        full_player_count,
+    )
+    .map(|members| {
+        if config.balance_teams {
+            balance_teams(data.clone(), members, guild_id, &queue_id)
+        } else {
+            members
+        }
+    });
+    let evaluation = members.as_ref().map(|members| {
+        let player_game_data = {
+            let player_data = data.player_data.get(&queue_id).unwrap();
+            members
+                .iter()
+                .map(|team| {
+                    team.iter()
+                        .map(|player| player_data[player].clone())
+                        .collect()
+                })
+                .collect()
+        };
+        let global_player_data = {
+            let player_data = data.global_player_data.lock().unwrap();
+            let player_data = player_data.get(&guild_id).unwrap();
+            members
+                .iter()
+                .map(|team| {
+                    team.iter()
+                        .map(|player| player_data[player].clone())
+                        .collect()
+                })
+                .collect()
+        };
+        evaluate_cost(
+            data.clone(),
+            members,
+            &player_game_data,
+            &global_player_data,
+            &queue_id,
+        )
+    });
+    {
+        let mut player_data = data.player_data.get_mut(&queue_id).unwrap();
+        player_data.retain(|player, _| !fake_players.contains(player));
+    }
+    {
+        let mut global_player_data = data.global_player_data.lock().unwrap();
+        if let Some(global_player_data) = global_player_data.get_mut(&guild_id) {
+            global_player_data.retain(|player, _| !fake_players.contains(player));
+        }
+    }
+
+    let mut problems = vec![];
+    if config.category.is_none() {
+        problems.push("No category is set, so match channels can't be created".to_string());
+    }
+    let mut report = format!(
+        "# Test report for {}\nMatched {} fake players into {} teams of {}\n",
+        config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("queue `{}`", queue_id.0)),
+        full_player_count,
+        config.team_count,
+        config.team_size
+    );
+    match (&members, &evaluation) {
+        (Some(members), Some(evaluation)) => {
+            let team_names = config.team_labels(config.team_count as usize);
+            report += "## Teams\n";
+            for (team_idx, team) in members.iter().enumerate() {
+                let roles = if config.role_combinations.is_empty() {
+                    format!("{} players", team.len())
+                } else {
+                    evaluation.roles[team_idx]
+                        .iter()
+                        .map(|role| match config.roles.get(role) {
+                            Some(role) => role.name.clone(),
+                            None => "*unassigned*".to_string(),
+                        })
+                        .join(", ")
+                };
+                report += format!("{}: {}\n", team_names[team_idx], roles).as_str();
+            }
+            if !config.role_combinations.is_empty()
+                && evaluation
+                    .roles
+                    .iter()
+                    .flatten()
+                    .any(|role| role.is_empty())
+            {
+                problems.push("No valid role assignment was found".to_string());
+            }
+            if !evaluation.game_categories.is_empty() {
+                report += "## Game categories\n";
+                for (category_name, value) in evaluation.game_categories.iter().sorted() {
+                    match config.game_categories[category_name].get(*value) {
+                        Some(role) => {
+                            report += format!("{}: {}\n", category_name, role.mention()).as_str()
+                        }
+                        None => {
+                            report += format!("{}: *none*\n", category_name).as_str();
+                            problems.push(format!("Game category {} has no values", category_name));
+                        }
+                    }
+                }
+            }
+            report += "## Cost\n";
+            for (term, cost) in evaluation.breakdown.terms() {
+                if cost != 0.0 {
+                    report += format!("{}: {}\n", term, cost).as_str();
+                }
+            }
+            if config.normalize_queue_cost {
+                report += format!(
+                    "Divided by {} players for a total of **{}**",
+                    full_player_count, evaluation.cost
+                )
+                .as_str();
+            } else {
+                report += format!("Total: **{}**", evaluation.cost).as_str();
+            }
+            report += format!(" (maximum queue cost is {})\n", config.maximum_queue_cost).as_str();
+            if evaluation.cost.is_infinite() {
+                problems.push("The lobby cost is infinite".to_string());
+            } else if evaluation.cost > config.maximum_queue_cost {
+                problems.push(
+                    "The lobby cost exceeds the maximum queue cost, so it would wait for players to queue longer"
+                        .to_string(),
+                );
+            }
+        }
+        _ => problems.push("No valid teams could be formed".to_string()),
+    }
+    report += "## Result\n";
+    if problems.is_empty() {
+        report += "A valid match formed.";
+    } else {
+        report += problems
+            .iter()
+            .map(|problem| format!("- {}", problem))
+            .join("\n")
+            .as_str();
+    }
+    ctx.send(CreateReply::default().content(report).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Whether a player was added by `queue_many`. Real user ids are snowflakes with a timestamp in
+/// the bits above 22, so they are never this small.
+fn is_fake_player(user: &UserId) -> bool {
+    user.get() < 1 << 22
+}
+
 fn player_leave_queue(
     data: Arc<Data>,
     user: UserId,
     queue_group: bool,
     queue: &QueueUuid,
 ) -> String {
+    let Some(guild_id) = queue_guild(&data, queue) else {
+        return locale::text(&data, queue, Message::NotQueued);
+    };
     if queue_group {
         let possible_party = data
             .global_player_data
             .lock()
             .unwrap()
+            .entry(guild_id)
+            .or_default()
             .entry(user.clone())
             .or_insert(GlobalPlayerData::default())
             .party
@@ -2873,7 +6731,7 @@ fn player_leave_queue(
             for user in party_members {
                 player_leave_queue(data.clone(), user, false, queue);
             }
-            return "Party left queue".to_string();
+            return locale::text(&data, queue, Message::PartyLeftQueue);
         }
     }
     let removed = {
@@ -2881,26 +6739,106 @@ fn player_leave_queue(
         queued_players.remove(&user)
     };
     if removed {
+        let other_queue = data
+            .guild_data
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .and_then(|guild_data| {
+                guild_data.queues.iter().cloned().find(|other_queue| {
+                    other_queue != queue
+                        && data
+                            .queued_players
+                            .get(other_queue)
+                            .is_some_and(|queued_players| queued_players.contains(&user))
+                })
+            });
+        // Players still queued elsewhere keep their wait time there
+        let other_queue_enter_time = other_queue.and_then(|other_queue| {
+            data.player_data
+                .get(&other_queue)?
+                .get(&user)?
+                .queue_enter_time
+        });
         {
             let mut player_data = data.global_player_data.lock().unwrap();
             let global_player_data = player_data
+                .entry(guild_id)
+                .or_default()
                 .entry(user.clone())
                 .or_insert(GlobalPlayerData::default());
-            global_player_data.queue_state = QueueState::None;
+            global_player_data.queue_state = match (&global_player_data.queue_state, other_queue) {
+                (QueueState::Queued(_, enter_time), Some(other_queue)) => {
+                    QueueState::Queued(other_queue, other_queue_enter_time.unwrap_or(*enter_time))
+                }
+                _ => QueueState::None,
+            };
         }
         data.message_edit_notify
             .get_mut(queue)
             .unwrap()
             .notify_one();
-        "You are no longer queueing!".to_string()
+        locale::text(&data, queue, Message::LeftQueue)
     } else {
-        "You weren't queued!".to_string()
+        locale::text(&data, queue, Message::NotQueued)
     }
 }
 
-/// Join queue
+/// Removes a player and their whole party from a queue, since parties are only matched together,
+/// and tells the rest of the party why they were removed
+async fn party_leave_queue(
+    data: Arc<Data>,
+    http: impl CacheHttp,
+    user: UserId,
+    queue: &QueueUuid,
+) -> String {
+    let Some(guild_id) = queue_guild(&data, queue) else {
+        return locale::text(&data, queue, Message::NotQueued);
+    };
+    let queued_party_members = {
+        let queued_players = data.queued_players.get(queue).unwrap();
+        get_party_members(&data, guild_id, user)
+            .into_iter()
+            .filter(|member| *member != user && queued_players.contains(member))
+            .collect_vec()
+    };
+    let response = player_leave_queue(data.clone(), user, true, queue);
+    if !queued_party_members.is_empty() {
+        let content = locale::text(
+            &data,
+            queue,
+            Message::PartyMemberLeftQueue(&user.mention().to_string()),
+        );
+        for member in queued_party_members {
+            member
+                .direct_message(&http, CreateMessage::new().content(content.clone()))
+                .await
+                .ok();
+        }
+    }
+    response
+}
+
+/// Who leaves the queue with `/leave_queue`
+#[derive(poise::ChoiceParameter, Clone, Copy, PartialEq, Eq)]
+enum LeaveScope {
+    /// Just you, leaving your party so the rest of it stays queued
+    #[name = "solo"]
+    Solo,
+    /// You and your whole party
+    #[name = "party"]
+    Party,
+}
+
+/// Leave queue
+///
+/// Parties are only matched together, so leaving with your party removes all of it. Leaving solo
+/// also leaves your party, keeping the rest of it queued.
 #[poise::command(slash_command, prefix_command)]
-async fn leave_queue(ctx: Context<'_>) -> Result<(), Error> {
+async fn leave_queue(
+    ctx: Context<'_>,
+    #[description = "Leave alone or with your party, defaults to party"] scope: Option<LeaveScope>,
+) -> Result<(), Error> {
     let queues = ctx
         .data()
         .guild_data
@@ -2910,8 +6848,41 @@ async fn leave_queue(ctx: Context<'_>) -> Result<(), Error> {
         .unwrap()
         .queues
         .clone();
+    let solo_party = if scope == Some(LeaveScope::Solo) {
+        let mut global_player_data = ctx.data().global_player_data.lock().unwrap();
+        global_player_data
+            .entry(ctx.guild_id().unwrap())
+            .or_default()
+            .entry(ctx.author().id)
+            .or_default()
+            .party
+            .take()
+    } else {
+        None
+    };
+    if let Some(old_party) = solo_party {
+        leave_party(
+            ctx.data().clone(),
+            &ctx.author().id,
+            Arc::new(ctx.http()),
+            old_party,
+        )
+        .await?;
+        if let Some(queue) = queues.first() {
+            ctx.send(
+                CreateReply::default()
+                    .content(locale::text(ctx.data(), queue, Message::LeftPartySolo))
+                    .ephemeral(true),
+            )
+            .await?;
+        }
+    }
     for queue in queues {
-        let response = player_leave_queue(ctx.data().clone(), ctx.author().id, true, &queue);
+        let response = if scope == Some(LeaveScope::Solo) {
+            player_leave_queue(ctx.data().clone(), ctx.author().id, false, &queue)
+        } else {
+            party_leave_queue(ctx.data().clone(), ctx, ctx.author().id, &queue).await
+        };
         ctx.send(CreateReply::default().content(response).ephemeral(true))
             .await?;
     }
@@ -2952,6 +6923,10 @@ async fn stats(
     #[description = "User to get stats for"] user: Option<serenity::UserId>,
 ) -> Result<(), Error> {
     let user = user.unwrap_or(ctx.author().id);
+    let permissions = ctx
+        .author_member()
+        .await
+        .and_then(|member| member.permissions);
     let queues = ctx
         .data()
         .guild_data
@@ -2962,28 +6937,249 @@ async fn stats(
         .queues
         .clone();
     for queue in queues {
-        let (stats, rating) = {
+        let (stats, rating, rank, hide_mmr, mvp_count, cancel_count) = {
             let mut player_data = ctx.data().player_data.get_mut(&queue).unwrap();
             let config = ctx.data().configuration.get(&queue).unwrap();
-            let player_data = player_data
+            let default_rating = config.default_player_data.rating;
+            let rating = player_data
                 .entry(user)
-                .or_insert(DerivedPlayerData::default());
+                .or_insert(DerivedPlayerData::default())
+                .rating
+                .unwrap_or(default_rating);
+            let rank = 1 + player_data
+                .values()
+                .filter(|other| other.rating.unwrap_or(default_rating).rating > rating.rating)
+                .count();
+            let player = player_data.get(&user).unwrap();
             (
-                player_data.stats.clone(),
-                player_data
-                    .rating
-                    .unwrap_or(config.default_player_data.rating),
+                player.stats.clone(),
+                rating,
+                rank,
+                config.hide_mmr,
+                config.mvp_vote.then_some(player.mvp_count),
+                config.count_cancels.then_some(player.cancel_count),
             )
         };
-        let response = format!(
-            "{}'s mmr is {}, with uncertainty {}\nScore: {}-{}-{}",
-            user.mention(),
-            rating.rating,
-            rating.uncertainty,
-            stats.wins,
-            stats.losses,
-            stats.draws
+        let rating_display = match format_rating(&rating, hide_mmr, permissions, true) {
+            Some(rating_display) => format!("{}'s mmr is {}", user.mention(), rating_display),
+            None => format!("{} is ranked #{}", user.mention(), rank),
+        };
+        let mut response = format!(
+            "{}\nScore: {}-{}-{}",
+            rating_display, stats.wins, stats.losses, stats.draws
         );
+        if let Some(mvp_count) = mvp_count {
+            response += format!("\nMVPs: {}", mvp_count).as_str();
+        }
+        if let Some(cancel_count) = cancel_count {
+            response += format!("\nCancels voted for: {}", cancel_count).as_str();
+        }
+        ctx.send(CreateReply::default().content(response).ephemeral(true))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Shows the state of the match for the current match channel
+#[poise::command(slash_command, prefix_command)]
+async fn match_info(ctx: Context<'_>) -> Result<(), Error> {
+    let match_number = {
+        let match_channels = ctx.data().match_channels.lock().unwrap();
+        match_channels.get(&ctx.channel_id()).cloned()
+    };
+    let Some(match_data) = match_number.and_then(|match_number| {
+        ctx.data()
+            .match_data
+            .lock()
+            .unwrap()
+            .get(&match_number)
+            .cloned()
+    }) else {
+        ctx.send(
+            CreateReply::default()
+                .content("This command can only be used in a match channel")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let team_names = ctx
+        .data()
+        .configuration
+        .get(&match_data.queue)
+        .unwrap()
+        .team_labels(match_data.members.len());
+    let mut response = format!("# Match {}\n", match_data.name);
+    for (team_idx, team) in match_data.members.iter().enumerate() {
+        let roles = match_data.roles.get(team_idx);
+        response += format!(
+            "{}: {}\n",
+            team_names[team_idx],
+            team.iter()
+                .enumerate()
+                .map(|(player_idx, player)| {
+                    match roles.and_then(|roles| roles.get(player_idx)) {
+                        Some(role) => format!("{} ({})", player.mention(), role),
+                        None => player.mention().to_string(),
+                    }
+                })
+                .join(", ")
+        )
+        .as_str();
+    }
+    if let Some(host) = match_data.host {
+        response += format!("Host: {}\n", host.mention()).as_str();
+    }
+    if !match_data.shot_callers.is_empty() {
+        response += format!(
+            "Shot-callers: {}\n",
+            match_data
+                .shot_callers
+                .iter()
+                .map(|player| player.mention())
+                .join(", ")
+        )
+        .as_str();
+    }
+    if let Some(map) = &match_data.map {
+        response += format!("Map: {}\n", map).as_str();
+    } else if !match_data.map_votes.is_empty() {
+        response += format!(
+            "Map votes: {}\n",
+            match_data
+                .map_votes
+                .values()
+                .counts()
+                .into_iter()
+                .sorted_by_key(|(_, count)| std::cmp::Reverse(*count))
+                .map(|(map, count)| format!("{}: {}", map, count))
+                .join(", ")
+        )
+        .as_str();
+    }
+    if !match_data.result_votes.is_empty() {
+        response += format!(
+            "Result votes: {}\n",
+            match_data
+                .result_votes
+                .values()
+                .map(|vote| vote.label(&team_names))
+                .counts()
+                .into_iter()
+                .sorted_by_key(|(_, count)| std::cmp::Reverse(*count))
+                .map(|(vote, count)| format!("{}: {}", vote, count))
+                .join(", ")
+        )
+        .as_str();
+    }
+    if !match_data.series_games.is_empty() {
+        response += format!(
+            "Series games: {}\n",
+            match_data
+                .series_games
+                .iter()
+                .map(|game| game.label(&team_names))
+                .join(", ")
+        )
+        .as_str();
+    }
+    if let Some(match_start_time) = match_data.match_start_time {
+        response += format!("Started <t:{}:R>\n", match_start_time).as_str();
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .allowed_mentions(CreateAllowedMentions::new().empty_users().empty_roles()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Shows the maps a player wins most on
+#[poise::command(slash_command, prefix_command)]
+async fn top_maps(
+    ctx: Context<'_>,
+    #[description = "User to get map stats for"] user: Option<serenity::UserId>,
+    #[description = "Minimum games on a map to be listed"]
+    #[min = 1]
+    min_games: Option<u32>,
+) -> Result<(), Error> {
+    let user = user.unwrap_or(ctx.author().id);
+    let min_games = min_games.unwrap_or(3) as usize;
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&ctx.guild_id().unwrap())
+        .unwrap()
+        .queues
+        .clone();
+    for queue in queues {
+        let game_history = ctx
+            .data()
+            .player_data
+            .get(&queue)
+            .unwrap()
+            .get(&user)
+            .map(|player| player.game_history.clone())
+            .unwrap_or_default();
+        let map_results = {
+            let historical_match_data = ctx.data().historical_match_data.lock().unwrap();
+            game_history
+                .iter()
+                .filter_map(|match_id| historical_match_data.get(match_id))
+                .filter_map(|match_data| {
+                    let map = match_data.map.clone()?;
+                    let team_idx = match_data
+                        .members
+                        .iter()
+                        .position(|team| team.contains(&user))?;
+                    match match_data.final_result.as_ref()? {
+                        MatchResult::Team(winner) => Some((map, *winner == team_idx as u32)),
+                        MatchResult::Tie => Some((map, false)),
+                        MatchResult::Cancel => None,
+                    }
+                })
+                .into_group_map()
+        };
+        let top_maps = map_results
+            .into_iter()
+            .filter(|(_, results)| results.len() >= min_games)
+            .map(|(map, results)| {
+                let wins = results.iter().filter(|won| **won).count();
+                (map, wins, results.len())
+            })
+            .sorted_by(|(_, wins_a, games_a), (_, wins_b, games_b)| {
+                (*wins_b as f32 / *games_b as f32)
+                    .partial_cmp(&(*wins_a as f32 / *games_a as f32))
+                    .unwrap()
+                    .then(games_b.cmp(games_a))
+            })
+            .collect_vec();
+        let response = if top_maps.is_empty() {
+            format!(
+                "{} has not played any map at least {} times",
+                user.mention(),
+                min_games
+            )
+        } else {
+            format!(
+                "## {}'s top maps\n{}",
+                user.mention(),
+                top_maps
+                    .iter()
+                    .take(10)
+                    .map(|(map, wins, games)| format!(
+                        "{}: {:.0}% ({}/{})\n",
+                        map,
+                        *wins as f32 / *games as f32 * 100.0,
+                        wins,
+                        games
+                    ))
+                    .join("")
+            )
+        };
         ctx.send(CreateReply::default().content(response).ephemeral(true))
             .await?;
     }
@@ -2993,6 +7189,10 @@ async fn stats(
 /// Displays a leaderboard
 #[poise::command(slash_command, prefix_command)]
 async fn leaderboard(ctx: Context<'_>) -> Result<(), Error> {
+    let permissions = ctx
+        .author_member()
+        .await
+        .and_then(|member| member.permissions);
     let queues = ctx
         .data()
         .guild_data
@@ -3012,23 +7212,73 @@ async fn leaderboard(ctx: Context<'_>) -> Result<(), Error> {
             .map(|(id, data)| {
                 (
                     id.mention(),
-                    data.rating
-                        .unwrap_or_else(|| {
-                            ctx.data()
-                                .configuration
-                                .get(&queue)
-                                .unwrap()
-                                .default_player_data
-                                .rating
-                        })
-                        .rating,
+                    data.rating.unwrap_or_else(|| {
+                        ctx.data()
+                            .configuration
+                            .get(&queue)
+                            .unwrap()
+                            .default_player_data
+                            .rating
+                    }),
                 )
             })
             .collect_vec();
-        player_data.sort_by(|(_, rating_a), (_, rating_b)| rating_b.partial_cmp(rating_a).unwrap());
+        player_data.sort_by(|(_, rating_a), (_, rating_b)| {
+            rating_b.rating.partial_cmp(&rating_a.rating).unwrap()
+        });
+        let hide_mmr = ctx.data().configuration.get(&queue).unwrap().hide_mmr;
         let mut response = "## Leaderboard\n".to_string();
         for (idx, (player, rating)) in player_data.iter().enumerate().take(10) {
-            response += format!("#{} {}: {}\n", idx + 1, player, rating).as_str();
+            match format_rating(rating, hide_mmr, permissions, false) {
+                Some(rating) => {
+                    response += format!("#{} {}: {}\n", idx + 1, player, rating).as_str()
+                }
+                None => response += format!("#{} {}\n", idx + 1, player).as_str(),
+            }
+        }
+        ctx.send(
+            CreateReply::default()
+                .content(response)
+                .ephemeral(true)
+                .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Displays the players voted MVP most often
+#[poise::command(slash_command, prefix_command)]
+async fn mvp_leaderboard(ctx: Context<'_>) -> Result<(), Error> {
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&ctx.guild_id().unwrap())
+        .unwrap()
+        .queues
+        .clone();
+    for queue in queues {
+        if !ctx.data().configuration.get(&queue).unwrap().mvp_vote {
+            continue;
+        }
+        let mvp_counts = ctx
+            .data()
+            .player_data
+            .get(&queue)
+            .unwrap()
+            .iter()
+            .filter(|(_, data)| data.mvp_count > 0)
+            .map(|(id, data)| (*id, data.mvp_count))
+            .sorted_by_key(|(_, mvp_count)| std::cmp::Reverse(*mvp_count))
+            .collect_vec();
+        let mut response = "## MVP Leaderboard\n".to_string();
+        if mvp_counts.is_empty() {
+            response += "No MVPs yet";
+        }
+        for (idx, (player, mvp_count)) in mvp_counts.iter().enumerate().take(10) {
+            response += format!("#{} {}: {}\n", idx + 1, player.mention(), mvp_count).as_str();
         }
         ctx.send(
             CreateReply::default()
@@ -3041,6 +7291,26 @@ async fn leaderboard(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Formats a rating for display, or returns `None` if the queue hides mmr and the viewer is not an admin
+fn format_rating(
+    rating: &WengLinRating,
+    hide_mmr: bool,
+    permissions: Option<Permissions>,
+    show_uncertainty: bool,
+) -> Option<String> {
+    if hide_mmr && !permissions.is_some_and(|permissions| permissions.manage_channels()) {
+        return None;
+    }
+    if show_uncertainty {
+        Some(format!(
+            "{}, with uncertainty {}",
+            rating.rating, rating.uncertainty
+        ))
+    } else {
+        Some(format!("{}", rating.rating))
+    }
+}
+
 fn update_bans(data: Arc<Data>, queue_id: &QueueUuid) {
     let now = chrono::offset::Utc::now();
     data.player_bans.get_mut(&queue_id).unwrap().retain(
@@ -3059,6 +7329,113 @@ fn update_bans(data: Arc<Data>, queue_id: &QueueUuid) {
     )
 }
 
+/// Removes one leave from each player per `leaver_decay_days` they've gone without leaving
+fn update_leaver_decay(data: &Data, queue_id: &QueueUuid) {
+    let leaver_decay_days = data.configuration.get(queue_id).unwrap().leaver_decay_days;
+    if leaver_decay_days == 0 {
+        return;
+    }
+    let decay_period = chrono::Duration::days(leaver_decay_days as i64);
+    let now = Utc::now();
+    let mut leaver_data = data.leaver_data.get_mut(queue_id).unwrap();
+    for record in leaver_data.values_mut() {
+        let last_leave = record.leaves.last().map(|(_, time)| *time);
+        let Some(clean_since) = last_leave.max(record.last_decay) else {
+            // Leaves from before timestamps were stored start their clean period now
+            record.last_decay = Some(now);
+            continue;
+        };
+        let periods = ((now - clean_since).num_seconds() / decay_period.num_seconds()) as u32;
+        if periods == 0 {
+            continue;
+        }
+        record.count = record.count.saturating_sub(periods);
+        record.last_decay = Some(clean_since + decay_period * periods as i32);
+        let decayed_leaves = record.leaves.len().saturating_sub(record.count as usize);
+        record.leaves.drain(..decayed_leaves);
+    }
+    leaver_data.retain(|_, record| record.count > 0);
+}
+
+/// Periodically applies leaver decay to every queue
+async fn decay_leaver_counts(data: Arc<Data>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+        let queues = data
+            .configuration
+            .iter()
+            .map(|config| *config.key())
+            .collect_vec();
+        for queue_id in queues {
+            update_leaver_decay(&data, &queue_id);
+        }
+    }
+}
+
+/// Removes a queue's finished matches older than `retention_days` from the match history and
+/// players' game histories. Ratings and stats were already applied, so they're unaffected. Returns
+/// how many matches were removed and roughly how many bytes of saved data that frees.
+fn prune_historical_matches(
+    data: &Data,
+    queue_id: &QueueUuid,
+    retention_days: u32,
+) -> (usize, usize) {
+    let cutoff = std::time::UNIX_EPOCH
+        .elapsed()
+        .unwrap()
+        .as_secs()
+        .saturating_sub(retention_days as u64 * 24 * 60 * 60);
+    let (pruned_matches, freed_bytes) = {
+        let mut historical_match_data = data.historical_match_data.lock().unwrap();
+        let pruned_matches = historical_match_data
+            .iter()
+            .filter(|(_, match_data)| {
+                match_data.queue == *queue_id
+                    && match_data
+                        .match_end_time
+                        .is_some_and(|end_time| end_time < cutoff)
+            })
+            .map(|(match_id, _)| *match_id)
+            .collect::<HashSet<_>>();
+        let mut freed_bytes = 0;
+        for match_id in pruned_matches.iter() {
+            if let Some(match_data) = historical_match_data.remove(match_id) {
+                freed_bytes += serde_json::to_vec(&match_data).map_or(0, |bytes| bytes.len());
+            }
+        }
+        (pruned_matches, freed_bytes)
+    };
+    if !pruned_matches.is_empty() {
+        if let Some(mut player_data) = data.player_data.get_mut(queue_id) {
+            for player in player_data.values_mut() {
+                player
+                    .game_history
+                    .retain(|match_id| !pruned_matches.contains(match_id));
+            }
+        }
+    }
+    (pruned_matches.len(), freed_bytes)
+}
+
+/// Periodically prunes the match history of queues with a history retention set
+async fn prune_match_history(data: Arc<Data>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+        let queues = data
+            .configuration
+            .iter()
+            .filter(|config| config.history_retention_days > 0)
+            .map(|config| (*config.key(), config.history_retention_days))
+            .collect_vec();
+        for (queue_id, retention_days) in queues {
+            let (pruned_count, _) = prune_historical_matches(&data, &queue_id, retention_days);
+            if pruned_count > 0 {
+                println!("Pruned {} old matches from history", pruned_count);
+            }
+        }
+    }
+}
+
 /// Marks a player as leaver
 #[poise::command(slash_command, prefix_command)]
 async fn mark_leaver(
@@ -3109,6 +7486,48 @@ async fn mark_leaver(
         .await?;
         return Ok(());
     }
+    if player == ctx.author().id {
+        ctx.send(
+            CreateReply::default()
+                .content("You can't report yourself as a leaver!")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let leaver_reports_required = ctx
+        .data()
+        .configuration
+        .get(&match_data.queue)
+        .unwrap()
+        .leaver_reports_required;
+    let report_count = {
+        let mut match_data = ctx.data().match_data.lock().unwrap();
+        let match_data = match_data
+            .get_mut(&match_number)
+            .ok_or("Could not get match data")?;
+        let reports = match_data.leaver_reports.entry(player).or_default();
+        reports.insert(ctx.author().id);
+        let report_count = reports.len() as u32;
+        if report_count >= leaver_reports_required {
+            match_data.leaver_reports.remove(&player);
+        }
+        report_count
+    };
+    if report_count < leaver_reports_required {
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "Report recorded, {} of {} reports needed to check if {} left.",
+                    report_count,
+                    leaver_reports_required,
+                    player.mention()
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
     let leaver_message_content = format!(
         "# Did you leave {}?\nEnds <t:{}:R>, otherwise user will be reported",
         player.mention(),
@@ -3143,25 +7562,123 @@ async fn mark_leaver(
                 return;
             };
             message.delete(ctx1.clone()).await.ok();
-            let Ok(mut member) = guild_id.member(ctx1.clone(), player).await else {
+            // Nobody could answer the check while the bot was disconnected
+            if in_reconnect_grace(&data, &match_data.queue) {
                 return;
-            };
-            member
-                .edit(ctx1, EditMember::new().disconnect_member())
+            }
+            let enforce_leaver_disconnect = data
+                .configuration
+                .get(&match_data.queue)
+                .unwrap()
+                .enforce_leaver_disconnect;
+            if enforce_leaver_disconnect {
+                if let Ok(mut member) = guild_id.member(ctx1.clone(), player).await {
+                    member
+                        .edit(ctx1.clone(), EditMember::new().disconnect_member())
+                        .await
+                        .ok();
+                }
+            }
+            {
+                let mut leaver_data = data.leaver_data.get_mut(&match_data.queue).unwrap();
+                let record = leaver_data.entry(player).or_default();
+                record.count += 1;
+                record.leaves.push((match_number, Utc::now()));
+            }
+            let appeal_message = CreateMessage::new()
+                .content("You were reported as a leaver. If this was a mistake you can appeal.")
+                .components(vec![CreateActionRow::Buttons(vec![
+                    ButtonData::LeaverAppeal(match_data.queue).get_button(),
+                ])]);
+            player
+                .direct_message(ctx1.clone(), appeal_message)
                 .await
                 .ok();
-            *data
-                .leaver_data
-                .get_mut(&match_data.queue)
+            let (leaver_surrender, surrender_delay_minutes, surrender_votes_required, team_names) = {
+                let config = data.configuration.get(&match_data.queue).unwrap();
+                (
+                    config.leaver_surrender,
+                    config.leaver_surrender_delay_minutes,
+                    config.surrender_votes_required,
+                    config.team_labels(match_data.members.len()),
+                )
+            };
+            if !leaver_surrender {
+                return;
+            }
+            let Some(team_idx) = match_data
+                .members
+                .iter()
+                .position(|team| team.contains(&player))
+            else {
+                return;
+            };
+            tokio::time::sleep(Duration::from_secs(surrender_delay_minutes as u64 * 60)).await;
+            let still_running = data
+                .match_data
+                .lock()
                 .unwrap()
-                .entry(player)
-                .or_insert(0) += 1;
+                .get(&match_number)
+                .is_some_and(|match_data| !match_data.resolved);
+            if !still_running {
+                return;
+            }
+            let required_votes = surrender_votes_required
+                .min(match_data.members[team_idx].len().saturating_sub(1) as u32)
+                .max(1) as usize;
+            let surrender_button = ButtonData::Surrender(team_idx, player).get_button();
+            let surrender_message = channel_id
+                .send_message(
+                    ctx1,
+                    CreateMessage::new()
+                        .content(surrender_offer_content(
+                            &team_names[team_idx],
+                            player,
+                            0,
+                            required_votes,
+                        ))
+                        .allowed_mentions(CreateAllowedMentions::new().empty_users())
+                        .components(vec![CreateActionRow::Buttons(vec![surrender_button])]),
+                )
+                .await;
+            if let Ok(surrender_message) = surrender_message {
+                if let Some(match_data) = data.match_data.lock().unwrap().get_mut(&match_number) {
+                    match_data.vote_messages.push(surrender_message.id);
+                }
+            }
         });
     }
 
     Ok(())
 }
 
+/// Result of a team surrendering, a win for the other team or a cancel when there are several
+fn surrender_result(team_idx: usize, team_count: usize) -> MatchResult {
+    if team_count == 2 {
+        MatchResult::Team(1 - team_idx as u32)
+    } else {
+        MatchResult::Cancel
+    }
+}
+
+fn surrender_offer_content(
+    team_name: &str,
+    leaver: UserId,
+    votes: usize,
+    required_votes: usize,
+) -> String {
+    let mut content = format!(
+        "# {} is down a player\n{} left, the rest of {} can surrender\n",
+        team_name,
+        leaver.mention(),
+        team_name
+    );
+    if required_votes > 1 {
+        content += format!("Surrender votes: {}/{}\n", votes, required_votes).as_str();
+    }
+    content
+}
+
 /// Pings players that haven't voted
 #[poise::command(slash_command, prefix_command)]
 async fn ping_non_voters(ctx: Context<'_>) -> Result<(), Error> {
@@ -3220,46 +7737,791 @@ async fn ping_non_voters(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-/// Sends a message without pinging
-#[poise::command(slash_command, prefix_command)]
-async fn no_ping(ctx: Context<'_>, #[rest] text: String) -> Result<(), Error> {
-    ctx.send(
-        CreateReply::default()
-            .content(format!("{}: {}", ctx.author().mention(), text))
-            .ephemeral(false)
-            .allowed_mentions(CreateAllowedMentions::default().empty_roles().empty_users()),
-    )
-    .await?;
+/// Sends a message without pinging
+#[poise::command(slash_command, prefix_command)]
+async fn no_ping(ctx: Context<'_>, #[rest] text: String) -> Result<(), Error> {
+    ctx.send(
+        CreateReply::default()
+            .content(format!("{}: {}", ctx.author().mention(), text))
+            .ephemeral(false)
+            .allowed_mentions(CreateAllowedMentions::default().empty_roles().empty_users()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Lists queues for this server
+#[poise::command(slash_command, prefix_command)]
+async fn list_queues(ctx: Context<'_>) -> Result<(), Error> {
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .entry(ctx.guild_id().unwrap())
+        .or_default()
+        .queues
+        .clone();
+    let response = queues
+        .iter()
+        .enumerate()
+        .map(|(idx, queue)| {
+            let name = ctx.data().configuration.get(queue).unwrap().name.clone();
+            match name {
+                Some(name) => format!("{}: {} (`{}`)", idx, name, queue.0),
+                None => format!("{}: `{}`", idx, queue.0),
+            }
+        })
+        .join("\n");
+    ctx.send(
+        CreateReply::default()
+            .content(format!("Queues:\n{}", response))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Shows your queue state across all queues for troubleshooting
+#[poise::command(slash_command, prefix_command)]
+async fn whoami_queue(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let user = ctx.author().id;
+    let global_player_data = ctx
+        .data()
+        .global_player_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .and_then(|players| players.get(&user).cloned())
+        .unwrap_or_default();
+    let queues = ctx
+        .data()
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .map(|guild_data| guild_data.queues.clone())
+        .unwrap_or_default();
+    let queue_state = match global_player_data.queue_state {
+        QueueState::None => "Not queued".to_string(),
+        QueueState::Queued(queue, queue_enter_time) => format!(
+            "Queued in queue {} since <t:{}:R>",
+            queues
+                .iter()
+                .position(|q| *q == queue)
+                .map(|idx| idx.to_string())
+                .unwrap_or(queue.0.to_string()),
+            queue_enter_time.timestamp()
+        ),
+        QueueState::InGame(match_id) => format!("In match {}", match_id),
+    };
+    let party = match global_player_data.party {
+        Some(party) => {
+            let members = ctx
+                .data()
+                .group_data
+                .lock()
+                .unwrap()
+                .get(&party)
+                .map(|party| party.players.iter().map(|p| p.mention()).join(", "))
+                .unwrap_or("missing party data".to_string());
+            format!("{} ({})", party, members)
+        }
+        None => "None".to_string(),
+    };
+    let mut response = format!("## Queue state\nState: {}\nParty: {}\n", queue_state, party);
+    for (queue_idx, queue) in queues.iter().enumerate() {
+        update_bans(ctx.data().clone(), queue);
+        let queued = ctx
+            .data()
+            .queued_players
+            .get(queue)
+            .unwrap()
+            .contains(&user);
+        let banned = ctx
+            .data()
+            .player_bans
+            .get(queue)
+            .unwrap()
+            .get(&user)
+            .filter(|ban| !ban.shadow_ban)
+            .map(|ban| {
+                ban.end_time
+                    .map(|end_time| format!("until <t:{}:f>", end_time.timestamp()))
+                    .unwrap_or("permanently".to_string())
+            })
+            .unwrap_or("no".to_string());
+        let leaver_count = ctx
+            .data()
+            .leaver_data
+            .get(queue)
+            .unwrap()
+            .get(&user)
+            .map(|record| record.count)
+            .unwrap_or(0);
+        response += format!(
+            "### Queue {}\nIn queue: {}\nBanned: {}\nLeaver count: {}\n",
+            queue_idx,
+            if queued { "yes" } else { "no" },
+            banned,
+            leaver_count
+        )
+        .as_str();
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new().all_users(false)),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Toggles a one-time DM notification for when a queue is about to pop
+#[poise::command(slash_command, prefix_command)]
+async fn queue_notify(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let subscribed = {
+        let mut subscribers = ctx
+            .data()
+            .queue_notify_subscribers
+            .entry(queue_uuid)
+            .or_default();
+        if subscribers.remove(&ctx.author().id) {
+            false
+        } else {
+            subscribers.insert(ctx.author().id);
+            true
+        }
+    };
+    let response = if subscribed {
+        "You will be messaged when this queue is about to pop."
+    } else {
+        "You will no longer be messaged about this queue."
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Pauses afk checks and requeueing after wins without leaving the queue
+#[poise::command(slash_command, prefix_command)]
+async fn pause_my_queue(
+    ctx: Context<'_>,
+    #[description = "Minutes to pause for, 0 to resume"]
+    #[min = 0]
+    #[max = 1440]
+    minutes: u32,
+) -> Result<(), Error> {
+    let snoozed_until =
+        (minutes > 0).then(|| Utc::now() + chrono::Duration::minutes(minutes as i64));
+    ctx.data()
+        .global_player_data
+        .lock()
+        .unwrap()
+        .entry(ctx.guild_id().unwrap())
+        .or_default()
+        .entry(ctx.author().id)
+        .or_default()
+        .snoozed_until = snoozed_until;
+    let response = match snoozed_until {
+        Some(snoozed_until) => format!(
+            "You won't get afk checks or be requeued after wins until <t:{}:t>. You'll still be placed in matches while queued.",
+            snoozed_until.timestamp()
+        ),
+        None => "Afk checks and requeueing after wins are resumed.".to_string(),
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Shows everything currently keeping you from queueing or limiting your time in queue
+#[poise::command(slash_command, prefix_command)]
+async fn queue_cooldown_status(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let user = ctx.author().id;
+    let mut restrictions = vec![];
+    update_bans(ctx.data().clone(), &queue_uuid);
+    // Shadow bans stay hidden from the banned player
+    if let Some(ban) = ctx
+        .data()
+        .player_bans
+        .get(&queue_uuid)
+        .unwrap()
+        .get(&user)
+        .filter(|ban| !ban.shadow_ban)
+    {
+        let mut restriction = match ban.end_time {
+            Some(end_time) => format!("Banned until <t:{}:f>", end_time.timestamp()),
+            None => "Banned indefinitely".to_string(),
+        };
+        if let Some(reason) = &ban.reason {
+            restriction += format!(" for {}", reason).as_str();
+        }
+        restrictions.push(restriction);
+    }
+    if let Some(member) = ctx.author_member().await {
+        if let Err(reason) = check_account_age(ctx.data(), &queue_uuid, &member) {
+            restrictions.push(reason);
+        }
+    }
+    let (in_game, snoozed_until) = {
+        let global_player_data = ctx.data().global_player_data.lock().unwrap();
+        let player = global_player_data
+            .get(&ctx.guild_id().unwrap())
+            .and_then(|players| players.get(&user));
+        (
+            player.is_some_and(|player| matches!(player.queue_state, QueueState::InGame(_))),
+            player
+                .filter(|player| player.is_snoozed())
+                .and_then(|player| player.snoozed_until),
+        )
+    };
+    if in_game {
+        restrictions.push("In a match, you can queue again once it's resolved".to_string());
+    }
+    if let Some(snoozed_until) = snoozed_until {
+        restrictions.push(format!(
+            "Afk checks and requeueing after wins are paused until <t:{}:t>",
+            snoozed_until.timestamp()
+        ));
+    }
+    update_leaver_decay(ctx.data(), &queue_uuid);
+    let leaver_decay_days = ctx
+        .data()
+        .configuration
+        .get(&queue_uuid)
+        .unwrap()
+        .leaver_decay_days;
+    if let Some(record) = ctx.data().leaver_data.get(&queue_uuid).unwrap().get(&user) {
+        let mut restriction = format!("Marked as a leaver {} time(s)", record.count);
+        let clean_since = record
+            .leaves
+            .last()
+            .map(|(_, time)| *time)
+            .max(record.last_decay)
+            .filter(|_| leaver_decay_days > 0);
+        if let Some(clean_since) = clean_since {
+            restriction += format!(
+                ", next one drops off <t:{}:R>",
+                (clean_since + chrono::Duration::days(leaver_decay_days as i64)).timestamp()
+            )
+            .as_str();
+        }
+        restrictions.push(restriction);
+    }
+    let response = if restrictions.is_empty() {
+        "Nothing is keeping you from queueing".to_string()
+    } else {
+        restrictions
+            .iter()
+            .map(|restriction| format!("* {}\n", restriction))
+            .join("")
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Adds or removes a player from your avoid list, or lists it
+#[poise::command(slash_command, prefix_command)]
+async fn avoid(
+    ctx: Context<'_>,
+    #[flag] remove: bool,
+    #[description = "Player to avoid"] player: Option<UserId>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let max_avoided_players = ctx
+        .data()
+        .configuration
+        .get(&queue_uuid)
+        .unwrap()
+        .max_avoided_players;
+    let response = {
+        let mut player_data = ctx.data().player_data.get_mut(&queue_uuid).unwrap();
+        let avoided_players = &mut player_data
+            .entry(ctx.author().id)
+            .or_default()
+            .avoided_players;
+        match player {
+            Some(player) if remove => {
+                if avoided_players.remove(&player) {
+                    format!("You are no longer avoiding {}.", player.mention())
+                } else {
+                    format!("You weren't avoiding {}.", player.mention())
+                }
+            }
+            Some(player) if player == ctx.author().id => "You can't avoid yourself.".to_string(),
+            Some(player) if avoided_players.contains(&player) => {
+                format!("You are already avoiding {}.", player.mention())
+            }
+            Some(_) if avoided_players.len() >= max_avoided_players as usize => format!(
+                "You can only avoid {} players, remove one first.",
+                max_avoided_players
+            ),
+            Some(player) => {
+                avoided_players.insert(player);
+                format!("You will be matched with {} less often.", player.mention())
+            }
+            None if avoided_players.is_empty() => "You aren't avoiding anyone.".to_string(),
+            None => format!(
+                "You are avoiding {}",
+                avoided_players
+                    .iter()
+                    .map(|player| player.mention())
+                    .join(", ")
+            ),
+        }
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(response)
+            .ephemeral(true)
+            .allowed_mentions(CreateAllowedMentions::new().empty_users()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Toggles being pinged in the match channel when your match starts
+#[poise::command(slash_command, prefix_command)]
+async fn match_start_ping(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let enabled = {
+        let mut player_data = ctx.data().player_data.get_mut(&queue_uuid).unwrap();
+        let player_data = player_data.entry(ctx.author().id).or_default();
+        player_data.ping_on_match_start = !player_data.ping_on_match_start;
+        player_data.ping_on_match_start
+    };
+    let response = if enabled {
+        "You will be pinged in the match channel when your match starts."
+    } else {
+        "You will no longer be pinged when your match starts."
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Toggles requeueing together with your team after a win
+#[poise::command(slash_command, prefix_command)]
+async fn requeue_after_win(
+    ctx: Context<'_>,
+    #[description = "Queue index"]
+    #[min = 0]
+    queue_idx: Option<u32>,
+) -> Result<(), Error> {
+    let queue_uuid = match get_queue_uuid(&ctx, queue_idx) {
+        Ok(queue_uuid) => queue_uuid,
+        Err(error) => {
+            ctx.send(CreateReply::default().content(error).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    let requeue_winners_together = ctx
+        .data()
+        .configuration
+        .get(&queue_uuid)
+        .unwrap()
+        .requeue_winners_together;
+    let enabled = {
+        let mut player_data = ctx.data().player_data.get_mut(&queue_uuid).unwrap();
+        let player_data = player_data.entry(ctx.author().id).or_default();
+        player_data.requeue_after_win = !player_data.requeue_after_win;
+        player_data.requeue_after_win
+    };
+    let response = if !enabled {
+        "You will no longer be requeued after a win."
+    } else if requeue_winners_together {
+        "If your whole team opts in, you will be requeued together after a win."
+    } else {
+        "You will be requeued with your team after a win once this queue enables it."
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Moves each queue's match counter past existing matches so channel names don't collide
+fn reconcile_queue_indices(data: &Data) {
+    let match_data = data.match_data.lock().unwrap();
+    let historical_match_data = data.historical_match_data.lock().unwrap();
+    for match_data in match_data.values().chain(historical_match_data.values()) {
+        let Some(match_idx) = match_data
+            .name
+            .strip_prefix('#')
+            .and_then(|idx| idx.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let mut queue_idx = data.queue_idx.entry(match_data.queue).or_insert(0);
+        if *queue_idx < match_idx {
+            *queue_idx = match_idx;
+        }
+    }
+}
+
+/// Collects each queue's latest match starts from the saved matches, so match intervals can be
+/// estimated without scanning the match history on every queue message update
+fn reconcile_recent_match_starts(data: &Data) {
+    let historical_match_data = data.historical_match_data.lock().unwrap();
+    let match_data = data.match_data.lock().unwrap();
+    let start_times = historical_match_data
+        .values()
+        .chain(match_data.values())
+        .filter_map(|match_data| Some((match_data.queue, match_data.match_start_time?)))
+        .sorted_by_key(|(_, start_time)| *start_time);
+    for (queue, start_time) in start_times {
+        record_match_start(data, &queue, start_time);
+    }
+}
+
+/// Rebuilds each queue's running matches from the saved matches, which older versions of the bot
+/// didn't keep track of
+fn reconcile_current_games(data: &Data) {
+    for mut current_games in data.current_games.iter_mut() {
+        current_games.clear();
+    }
+    let match_data = data.match_data.lock().unwrap();
+    for (match_id, match_data) in match_data
+        .iter()
+        .filter(|(_, match_data)| !match_data.resolved)
+    {
+        data.current_games
+            .entry(match_data.queue)
+            .or_default()
+            .insert(*match_id);
+    }
+}
+
+/// Upgrades config files written by older versions of the bot
+fn migrate_config(config: &mut serde_json::Value) {
+    migrate_guild_scoped_player_data(config);
+    migrate_in_game_match_ids(config);
+    migrate_leaver_counts(config);
+}
+
+/// Leaver data used to only store how many times each player left
+fn migrate_leaver_counts(config: &mut serde_json::Value) {
+    use serde_json::Value;
+    let Some(queues) = config.get_mut("leaver_data").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for players in queues.values_mut().filter_map(Value::as_object_mut) {
+        for record in players.values_mut() {
+            if let Some(count) = record.as_u64() {
+                *record = serde_json::json!({ "count": count });
+            }
+        }
+    }
+}
+
+/// `QueueState::InGame` used to not record which match the player is in
+fn migrate_in_game_match_ids(config: &mut serde_json::Value) {
+    use serde_json::Value;
+    let matches = config
+        .get("match_data")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    let Some(guilds) = config
+        .get_mut("global_player_data")
+        .and_then(Value::as_object_mut)
+    else {
+        return;
+    };
+    for players in guilds.values_mut().filter_map(Value::as_object_mut) {
+        for (user, player) in players.iter_mut() {
+            if player["queue_state"] != "InGame" {
+                continue;
+            }
+            let current_match = matches.iter().find(|(_, match_data)| {
+                match_data["members"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_array)
+                    .flatten()
+                    .any(|member| member.as_str() == Some(user.as_str()))
+            });
+            player["queue_state"] = match current_match {
+                Some((match_id, _)) => serde_json::json!({ "InGame": match_id }),
+                None => Value::String("None".to_string()),
+            };
+        }
+    }
+}
+
+/// Player state and parties used to be shared between every guild. Legacy entries are moved to the
+/// guild they can be traced back to, or copied to every guild with a queue they have player data
+/// in, and dropped if there isn't one.
+fn migrate_guild_scoped_player_data(config: &mut serde_json::Value) {
+    use serde_json::{Map, Value};
+    let Some(legacy_player_data) = config
+        .get("global_player_data")
+        .and_then(Value::as_object)
+        .filter(|players| {
+            players
+                .values()
+                .any(|player| player.get("queue_state").is_some())
+        })
+        .cloned()
+    else {
+        return;
+    };
+    let guilds = config
+        .get("guild_data")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    let queue_guilds: HashMap<&str, &String> = guilds
+        .iter()
+        .flat_map(|(guild, guild_data)| {
+            guild_data["queues"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(move |queue| (queue, guild))
+        })
+        .collect();
+    let single_guild = guilds.keys().exactly_one().ok();
+    let mut player_guilds: HashMap<&str, HashSet<&String>> = HashMap::new();
+    if let Some(player_data) = config.get("player_data").and_then(Value::as_object) {
+        for (queue, players) in player_data {
+            let Some(guild) = queue_guilds.get(queue.as_str()) else {
+                continue;
+            };
+            for user in players.as_object().into_iter().flat_map(Map::keys) {
+                player_guilds.entry(user).or_default().insert(guild);
+            }
+        }
+    }
+    let matches = config
+        .get("match_data")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
 
-    Ok(())
+    let mut scoped_player_data = Map::new();
+    for (user, player) in legacy_player_data {
+        let queue_state = &player["queue_state"];
+        let queue = if let Some(queued) = queue_state.get("Queued") {
+            queued[0].as_str()
+        } else if queue_state == "InGame" {
+            matches
+                .values()
+                .find(|match_data| {
+                    match_data["members"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(Value::as_array)
+                        .flatten()
+                        .any(|member| member.as_str() == Some(user.as_str()))
+                })
+                .and_then(|match_data| match_data["queue"].as_str())
+        } else {
+            None
+        };
+        let player_guilds = match queue
+            .and_then(|queue| queue_guilds.get(queue).copied())
+            .or(single_guild)
+        {
+            Some(guild) => vec![guild],
+            None => player_guilds
+                .get(user.as_str())
+                .map(|guilds| guilds.iter().copied().sorted().collect_vec())
+                .unwrap_or_default(),
+        };
+        for guild in player_guilds {
+            scoped_player_data
+                .entry(guild.clone())
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .unwrap()
+                .insert(user.clone(), player.clone());
+        }
+    }
+
+    let mut group_guilds = HashMap::new();
+    for (guild, players) in scoped_player_data.iter() {
+        for player in players.as_object().unwrap().values() {
+            if let Some(party) = player["party"].as_str() {
+                // Players copied to several guilds keep their party in the first one
+                group_guilds
+                    .entry(party.to_string())
+                    .or_insert_with(|| guild.clone());
+            }
+        }
+    }
+    if let Some(groups) = config.get_mut("group_data").and_then(Value::as_object_mut) {
+        groups.retain(|group_id, group| {
+            let Some(guild) = group_guilds.get(group_id) else {
+                return false;
+            };
+            group["guild"] = Value::String(guild.clone());
+            true
+        });
+    }
+    for (guild, players) in scoped_player_data.iter_mut() {
+        for player in players.as_object_mut().unwrap().values_mut() {
+            let party_exists = player["party"]
+                .as_str()
+                .is_some_and(|party| group_guilds.get(party) == Some(guild));
+            if !party_exists {
+                player["party"] = Value::Null;
+            }
+        }
+    }
+    config["global_player_data"] = Value::Object(scoped_player_data);
 }
 
-/// Lists queues for this server
-#[poise::command(slash_command, prefix_command)]
-async fn list_queues(ctx: Context<'_>) -> Result<(), Error> {
-    let queues = ctx
-        .data()
-        .guild_data
-        .lock()
-        .unwrap()
-        .entry(ctx.guild_id().unwrap())
-        .or_default()
-        .queues
-        .clone();
-    ctx.send(
-        CreateReply::default()
-            .content(format!("Queues: {:?}", queues))
-            .ephemeral(true),
-    )
-    .await?;
-    Ok(())
+/// Every command the bot registers
+fn commands() -> Vec<poise::Command<Arc<Data>, Error>> {
+    let mut commands = vec![
+        register(),
+        configure(),
+        backup(),
+        export_config(),
+        import_config(),
+        queue(),
+        queue_many(),
+        queue_test_report(),
+        leave_queue(),
+        list_queued(),
+        stats(),
+        party(),
+        list_parties(),
+        queue_as_premade(),
+        leaderboard(),
+        mvp_leaderboard(),
+        match_info(),
+        top_maps(),
+        queue_stats_graph(),
+        compare_queues(),
+        manage_player(),
+        search_player(),
+        mark_leaver(),
+        list_leavers(),
+        leaver_stats(),
+        blacklist_pair(),
+        approve_leaver_appeal(),
+        set_team_channels_private(),
+        fix_team_voice(),
+        set_command_role(),
+        force_outcome(),
+        force_match(),
+        edit_match_map(),
+        force_start_map_vote(),
+        end_map_vote(),
+        create_queue_message(),
+        create_roles_message(),
+        create_preference_message(),
+        create_register_message(),
+        prune_queue_messages(),
+        recreate_queue_messages(),
+        recount_votes(),
+        prune_history(),
+        no_ping(),
+        player_config(),
+        ping_non_voters(),
+        list_queues(),
+        queue_notify(),
+        requeue_after_win(),
+        match_start_ping(),
+        pause_my_queue(),
+        queue_cooldown_status(),
+        avoid(),
+        whoami_queue(),
+        create_queue(),
+        rename_queue(),
+        move_queue(),
+        set_queue_channel_prompt(),
+        set_map_image(),
+    ];
+    commands.iter_mut().for_each(move_permissions_to_check);
+    commands
 }
 
 #[tokio::main]
 async fn main() {
     let token = std::env::var("DISCORD_BOT_TOKEN").expect("missing DISCORD_BOT_TOKEN");
-    let intents =
-        serenity::GatewayIntents::non_privileged().union(serenity::GatewayIntents::MESSAGE_CONTENT);
+    let intents = serenity::GatewayIntents::non_privileged()
+        .union(serenity::GatewayIntents::MESSAGE_CONTENT)
+        .union(serenity::GatewayIntents::GUILD_MEMBERS);
+
+    let config_data: Option<Arc<Data>> = fs::read_to_string("config.json").ok().map(|read| {
+        let mut config: serde_json::Value =
+            serde_json::from_str(read.as_str()).expect("Failed to parse config file");
+        migrate_config(&mut config);
+        serde_json::from_value(config).expect("Failed to parse config file")
+    });
+    let data = match config_data {
+        Some(data) => {
+            for config in data.configuration.iter() {
+                data.message_edit_notify
+                    .insert(*config.key(), Arc::new(Notify::new()));
+            }
+            reconcile_queue_indices(&data);
+            reconcile_current_games(&data);
+            reconcile_recent_match_starts(&data);
+            data
+        }
+        None => Arc::new(Data::default()),
+    };
+    let framework_data = data.clone();
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
@@ -3271,56 +8533,697 @@ async fn main() {
                     log_command(&ctx);
                 })
             },
-            commands: vec![
-                register(),
-                configure(),
-                backup(),
-                export_config(),
-                import_config(),
-                queue(),
-                queue_many(),
-                leave_queue(),
-                list_queued(),
-                stats(),
-                party(),
-                list_parties(),
-                leaderboard(),
-                manage_player(),
-                mark_leaver(),
-                list_leavers(),
-                force_outcome(),
-                create_queue_message(),
-                create_roles_message(),
-                create_register_message(),
-                no_ping(),
-                player_config(),
-                ping_non_voters(),
-                list_queues(),
-                create_queue(),
-            ],
+            commands: commands(),
             on_error: |error| Box::pin(on_error(error)),
+            command_check: Some(|ctx| Box::pin(check_command_role(ctx))),
             ..Default::default()
         })
-        .setup(|_ctx, _ready, _framework| {
+        .setup(|ctx, _ready, _framework| {
+            let data = framework_data;
             Box::pin(async move {
-                let config_data: Option<Arc<Data>> =
-                    fs::read_to_string("config.json").ok().map(|read| {
-                        serde_json::from_str(read.as_str()).expect("Failed to parse config file")
-                    });
-                if let Some(data) = config_data {
-                    for config in data.configuration.iter() {
-                        data.message_edit_notify
-                            .insert(config.key().clone(), Arc::new(Notify::new()));
-                    }
-                    return Ok(data);
+                // Setup only runs on the first Ready, so reconnects don't start more workers
+                let queues = data
+                    .configuration
+                    .iter()
+                    .map(|config| *config.key())
+                    .collect_vec();
+                for queue in queues {
+                    spawn_queue_workers(data.clone(), ctx.http.clone(), ctx.cache.clone(), queue);
                 }
-                Ok(Arc::new(Data::default()))
+                tokio::spawn(prune_stale_parties(data.clone(), ctx.http.clone()));
+                tokio::spawn(decay_leaver_counts(data.clone()));
+                tokio::spawn(prune_match_history(data.clone()));
+                metrics::spawn_server();
+                api::spawn_server(data.clone());
+                Ok(data)
             })
         })
         .build();
 
-    let client = serenity::ClientBuilder::new(token, intents)
+    let mut client = serenity::ClientBuilder::new(token, intents)
         .framework(framework)
+        .await
+        .unwrap();
+    let shard_manager = client.shard_manager.clone();
+    tokio::select! {
+        result = client.start() => result.unwrap(),
+        _ = shutdown_signal() => {
+            println!("Shutting down...");
+            shard_manager.shutdown_all().await;
+            // Tasks still holding a lock can't hold up the shutdown forever. The save runs on its
+            // own thread since the runtime waits for blocking tasks when it's dropped.
+            let (saved_sender, saved) = std::sync::mpsc::channel();
+            std::thread::spawn(move || saved_sender.send(save_config(&data)).ok());
+            match saved.recv_timeout(Duration::from_secs(30)) {
+                Ok(Ok(())) => println!("Saved state to config.json"),
+                Ok(Err(err)) => eprintln!("Failed to save state: {}", err),
+                Err(_) => eprintln!("Timed out saving state"),
+            }
+        }
+    }
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on unix
+async fn shutdown_signal() {
+    let terminate = async {
+        #[cfg(unix)]
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                eprintln!("Failed to listen for SIGTERM: {}", err);
+                future::pending::<()>().await;
+            }
+        }
+        #[cfg(not(unix))]
+        future::pending::<()>().await;
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
+
+/// Writes the bot's state to `config.json` through a temporary file, so an interrupted write
+/// leaves the previous save intact
+fn save_config(data: &Data) -> Result<(), Error> {
+    let config = serde_json::to_string_pretty(data)?;
+    fs::write("config.json.tmp", config)?;
+    fs::rename("config.json.tmp", "config.json")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Discord rejects registration of groups with more than 25 subcommands or descriptions
+    /// longer than 100 characters
+    fn check_discord_limits(command: &poise::Command<Arc<Data>, Error>) {
+        assert!(
+            command.subcommands.len() <= 25,
+            "/{} has {} subcommands",
+            command.qualified_name,
+            command.subcommands.len()
+        );
+        if let Some(description) = &command.description {
+            assert!(
+                description.chars().count() <= 100,
+                "/{} has a description longer than 100 characters",
+                command.qualified_name
+            );
+        }
+        for subcommand in &command.subcommands {
+            check_discord_limits(subcommand);
+        }
+    }
+
+    /// Sets up a queue the way `create_queue` does, without spawning its workers
+    fn add_queue(data: &Data, guild_id: GuildId, config: QueueConfiguration) -> QueueUuid {
+        let queue_id = QueueUuid::new();
+        data.configuration.insert(queue_id, config);
+        data.current_games.insert(queue_id, HashSet::new());
+        data.is_matchmaking.insert(queue_id, None);
+        data.leaver_data.insert(queue_id, HashMap::new());
+        data.player_bans.insert(queue_id, HashMap::new());
+        data.player_data.insert(queue_id, HashMap::new());
+        data.queue_idx.insert(queue_id, 0);
+        data.queued_players.insert(queue_id, HashSet::new());
+        data.message_edit_notify
+            .insert(queue_id, Arc::new(Notify::new()));
+        data.matchmaking_notify
+            .insert(queue_id, Arc::new(Notify::new()));
+        data.guild_data
+            .lock()
+            .unwrap()
+            .entry(guild_id)
+            .or_default()
+            .queues
+            .push(queue_id);
+        queue_id
+    }
+
+    /// An unresolved match of `queue_id` between `members`
+    fn test_match(queue_id: QueueUuid, members: Vec<Vec<UserId>>) -> MatchData {
+        serde_json::from_value(serde_json::json!({
+            "result_votes": {},
+            "map_votes": {},
+            "channels": [],
+            "members": members,
+            "host": null,
+            "map_vote_end_time": null,
+            "match_end_time": null,
+            "resolved": false,
+            "name": "match",
+            "queue": queue_id,
+        }))
+        .unwrap()
+    }
+
+    /// Puts `players` in a party together
+    fn add_party(data: &Data, guild_id: GuildId, players: &[UserId]) -> GroupUuid {
+        let party = GroupUuid::new();
+        data.group_data.lock().unwrap().insert(
+            party,
+            QueueGroup {
+                guild: guild_id,
+                players: players.iter().cloned().collect(),
+                pending_invites: HashSet::new(),
+                invite_link: None,
+                last_active: Utc::now(),
+                streak_requeues: None,
+            },
+        );
+        let mut global_player_data = data.global_player_data.lock().unwrap();
+        for player in players {
+            global_player_data
+                .entry(guild_id)
+                .or_default()
+                .entry(*player)
+                .or_default()
+                .party = Some(party);
+        }
+        party
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_joins_queue_once() {
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let queue_id = add_queue(&data, guild_id, QueueConfiguration::default());
+        let user_id = UserId::new(1);
+        let join = || {
+            let data = data.clone();
+            tokio::spawn(async move {
+                let http = Arc::new(Http::new(""));
+                try_queue_player(data, &queue_id, user_id, http, guild_id, false, true).await
+            })
+        };
+        let (first, second) = tokio::join!(join(), join());
+        let results = [first.unwrap(), second.unwrap()];
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(results.contains(&Err(locale::text(&data, &queue_id, Message::AlreadyQueued))));
+        assert_eq!(
+            *data.queued_players.get(&queue_id).unwrap(),
+            HashSet::from([user_id])
+        );
+    }
+
+    #[test]
+    fn oversized_party_fails_cleanly() {
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let queue_id = add_queue(
+            &data,
+            guild_id,
+            QueueConfiguration {
+                team_count: 2,
+                team_size: 2,
+                ..Default::default()
+            },
+        );
+        let players = (1..=4).map(UserId::new).collect_vec();
+        add_party(&data, guild_id, &players[..3]);
+        data.global_player_data
+            .lock()
+            .unwrap()
+            .entry(guild_id)
+            .or_default()
+            .insert(players[3], GlobalPlayerData::default());
+        let pool: HashSet<UserId> = players.iter().cloned().collect();
+        assert!(greedy_matchmaking(data.clone(), pool.clone(), guild_id, &queue_id, 4).is_none());
+
+        data.configuration
+            .get_mut(&queue_id)
+            .unwrap()
+            .split_oversized_parties = true;
+        let teams = greedy_matchmaking(data.clone(), pool, guild_id, &queue_id, 4).unwrap();
+        assert!(teams.iter().all(|team| team.len() == 2));
+    }
+
+    #[tokio::test]
+    async fn shadow_banned_player_is_queued_but_never_matched() {
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let queue_id = add_queue(
+            &data,
+            guild_id,
+            QueueConfiguration {
+                team_count: 2,
+                team_size: 1,
+                ..Default::default()
+            },
+        );
+        let players = (1..=3).map(UserId::new).collect_vec();
+        let shadow_banned = players[0];
+        data.player_bans.get_mut(&queue_id).unwrap().insert(
+            shadow_banned,
+            BanData {
+                end_time: None,
+                reason: None,
+                shadow_ban: true,
+            },
+        );
+        for player in players.iter() {
+            let http = Arc::new(Http::new(""));
+            try_queue_player(
+                data.clone(),
+                &queue_id,
+                *player,
+                http,
+                guild_id,
+                false,
+                true,
+            )
+            .await
+            .unwrap();
+        }
+        assert!(data
+            .queued_players
+            .get(&queue_id)
+            .unwrap()
+            .contains(&shadow_banned));
+        assert!(matches!(
+            data.global_player_data.lock().unwrap()[&guild_id][&shadow_banned].queue_state,
+            QueueState::Queued(queue, _) if queue == queue_id
+        ));
+
+        let pool = matchable_players(&data, &queue_id);
+        assert!(!pool.contains(&shadow_banned));
+        let teams = greedy_matchmaking(data.clone(), pool, guild_id, &queue_id, 2).unwrap();
+        assert!(!teams.iter().flatten().contains(&shadow_banned));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rapid_joins_all_get_matched() {
+        const PLAYERS: u64 = 402;
+        const LOBBY_SIZE: usize = 4;
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let queue_id = add_queue(&data, guild_id, QueueConfiguration::default());
+        let lobbies = Arc::new(Mutex::new(vec![]));
+        let worker = {
+            let (data, lobbies) = (data.clone(), lobbies.clone());
+            tokio::spawn(run_matchmaking_passes(data.clone(), queue_id, move |_| {
+                let (data, lobbies) = (data.clone(), lobbies.clone());
+                async move {
+                    tokio::task::yield_now().await;
+                    let mut queued_players = data.queued_players.get_mut(&queue_id).unwrap();
+                    if queued_players.len() < LOBBY_SIZE {
+                        return Ok(MatchmakingOutcome::NotEnoughPlayers);
+                    }
+                    let lobby = queued_players
+                        .iter()
+                        .take(LOBBY_SIZE)
+                        .cloned()
+                        .collect_vec();
+                    for player in lobby.iter() {
+                        queued_players.remove(player);
+                    }
+                    lobbies.lock().unwrap().push(lobby);
+                    Ok(MatchmakingOutcome::Created(0.0))
+                }
+            }))
+        };
+        future::join_all((1..=PLAYERS).map(|player| {
+            let data = data.clone();
+            tokio::spawn(async move {
+                data.queued_players
+                    .get_mut(&queue_id)
+                    .unwrap()
+                    .insert(UserId::new(player));
+                matchmake(&data, &queue_id);
+            })
+        }))
         .await;
-    client.unwrap().start().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while data.queued_players.get(&queue_id).unwrap().len() >= LOBBY_SIZE {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("a matchmaking pass was dropped");
+        worker.abort();
+
+        let lobbies = lobbies.lock().unwrap();
+        assert_eq!(lobbies.len(), PLAYERS as usize / LOBBY_SIZE);
+        assert!(lobbies.iter().flatten().all_unique());
+    }
+
+    #[tokio::test]
+    async fn single_queue_policy_blocks_second_queue() {
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let single_queue = add_queue(
+            &data,
+            guild_id,
+            QueueConfiguration {
+                allow_multi_queue: false,
+                ..Default::default()
+            },
+        );
+        let multi_queue = add_queue(&data, guild_id, QueueConfiguration::default());
+        let user_id = UserId::new(1);
+        let http = Arc::new(Http::new(""));
+        try_queue_player(
+            data.clone(),
+            &single_queue,
+            user_id,
+            http.clone(),
+            guild_id,
+            false,
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            try_queue_player(
+                data.clone(),
+                &multi_queue,
+                user_id,
+                http.clone(),
+                guild_id,
+                false,
+                true,
+            )
+            .await,
+            Err(locale::text(
+                &data,
+                &multi_queue,
+                Message::QueuedElsewhere(0)
+            ))
+        );
+        assert!(!data
+            .queued_players
+            .get(&multi_queue)
+            .unwrap()
+            .contains(&user_id));
+
+        // The policy applies whichever queue was joined first
+        let user_id = UserId::new(2);
+        try_queue_player(
+            data.clone(),
+            &multi_queue,
+            user_id,
+            http.clone(),
+            guild_id,
+            false,
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            try_queue_player(
+                data.clone(),
+                &single_queue,
+                user_id,
+                http,
+                guild_id,
+                false,
+                true,
+            )
+            .await,
+            Err(locale::text(
+                &data,
+                &single_queue,
+                Message::QueuedElsewhere(1)
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn matched_player_leaves_other_queues() {
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let first_queue = add_queue(&data, guild_id, QueueConfiguration::default());
+        let second_queue = add_queue(&data, guild_id, QueueConfiguration::default());
+        let players = (1..=3).map(UserId::new).collect_vec();
+        let http = Arc::new(Http::new(""));
+        for (queue, player) in [
+            (first_queue, players[0]),
+            (first_queue, players[1]),
+            (second_queue, players[0]),
+            (second_queue, players[2]),
+        ] {
+            try_queue_player(
+                data.clone(),
+                &queue,
+                player,
+                http.clone(),
+                guild_id,
+                false,
+                true,
+            )
+            .await
+            .unwrap();
+        }
+        let guild_queues = [first_queue, second_queue];
+
+        let match_id = MatchUuid::new();
+        let members = vec![vec![players[0]], vec![players[1]]];
+        assert!(claim_match_players(
+            &data,
+            guild_id,
+            &guild_queues,
+            &members,
+            match_id
+        ));
+        assert!(data.queued_players.get(&first_queue).unwrap().is_empty());
+        assert_eq!(
+            *data.queued_players.get(&second_queue).unwrap(),
+            HashSet::from([players[2]])
+        );
+        assert!(matches!(
+            data.global_player_data.lock().unwrap()[&guild_id][&players[0]].queue_state,
+            QueueState::InGame(id) if id == match_id
+        ));
+
+        // A pass of the other queue that started before the match can't match them again
+        let members = vec![vec![players[0]], vec![players[2]]];
+        assert!(!claim_match_players(
+            &data,
+            guild_id,
+            &guild_queues,
+            &members,
+            MatchUuid::new()
+        ));
+        assert!(data
+            .queued_players
+            .get(&second_queue)
+            .unwrap()
+            .contains(&players[2]));
+    }
+
+    #[tokio::test]
+    async fn leaving_one_queue_keeps_the_others_wait_time() {
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let first_queue = add_queue(&data, guild_id, QueueConfiguration::default());
+        let second_queue = add_queue(&data, guild_id, QueueConfiguration::default());
+        let user_id = UserId::new(1);
+        let http = Arc::new(Http::new(""));
+        for queue in [first_queue, second_queue] {
+            try_queue_player(
+                data.clone(),
+                &queue,
+                user_id,
+                http.clone(),
+                guild_id,
+                false,
+                true,
+            )
+            .await
+            .unwrap();
+        }
+        let first_enter_time = data.player_data.get(&first_queue).unwrap()[&user_id]
+            .queue_enter_time
+            .unwrap();
+        player_leave_queue(data.clone(), user_id, false, &second_queue);
+        assert!(matches!(
+            data.global_player_data.lock().unwrap()[&guild_id][&user_id].queue_state,
+            QueueState::Queued(queue, enter_time)
+                if queue == first_queue && enter_time == first_enter_time
+        ));
+    }
+
+    #[test]
+    fn in_game_count_tracks_running_matches() {
+        let data = Data::default();
+        let guild_id = GuildId::new(1);
+        let queue_id = add_queue(&data, guild_id, QueueConfiguration::default());
+        let players = (1..=7).map(UserId::new).collect_vec();
+        let full_match = MatchUuid::new();
+        let short_handed_match = MatchUuid::new();
+        let resolved_match = MatchUuid::new();
+        {
+            let mut match_data = data.match_data.lock().unwrap();
+            match_data.insert(
+                full_match,
+                test_match(
+                    queue_id,
+                    vec![players[..2].to_vec(), players[2..4].to_vec()],
+                ),
+            );
+            match_data.insert(
+                short_handed_match,
+                test_match(queue_id, vec![players[4..6].to_vec(), vec![players[6]]]),
+            );
+            let mut finished = test_match(queue_id, vec![players[..2].to_vec()]);
+            finished.resolved = true;
+            match_data.insert(resolved_match, finished);
+        }
+        reconcile_current_games(&data);
+        assert_eq!(
+            *data.current_games.get(&queue_id).unwrap(),
+            HashSet::from([full_match, short_handed_match])
+        );
+        assert_eq!(in_game_player_count(&data, &queue_id), 7);
+    }
+
+    #[test]
+    fn map_vote_count_above_map_count_offers_every_map() {
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let mut config = QueueConfiguration {
+            maps: HashMap::from([
+                ("first".to_string(), MapData::default()),
+                ("second".to_string(), MapData::default()),
+            ]),
+            map_vote_count: 5,
+            prevent_recent_maps: true,
+            ..Default::default()
+        };
+        let queue_id = add_queue(&data, guild_id, config.clone());
+        let player = UserId::new(1);
+        let previous_match = MatchUuid::new();
+        let mut previous_match_data = test_match(queue_id, vec![vec![player]]);
+        previous_match_data.map = Some("first".to_string());
+        data.historical_match_data
+            .lock()
+            .unwrap()
+            .insert(previous_match, previous_match_data);
+        data.player_data
+            .get_mut(&queue_id)
+            .unwrap()
+            .entry(player)
+            .or_default()
+            .game_history
+            .push(previous_match);
+        let members = [vec![player]];
+
+        assert_eq!(config.effective_map_vote_count(), 2);
+        let map_pool = match_map_pool(&data, &config, &queue_id, &members);
+        let ballot = map_pool
+            .choose_multiple(&mut rand::thread_rng(), config.effective_map_vote_count())
+            .sorted()
+            .collect_vec();
+        assert_eq!(ballot, [&"first", &"second"]);
+
+        // With a smaller ballot there's room to leave out the previous map
+        config.map_vote_count = 1;
+        assert_eq!(
+            match_map_pool(&data, &config, &queue_id, &members),
+            [&"second"]
+        );
+    }
+
+    #[test]
+    fn previous_match_lookups_with_large_history() {
+        const PLAYERS: u64 = 1000;
+        const MATCHES_PER_PLAYER: usize = 50;
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let queue_id = add_queue(&data, guild_id, QueueConfiguration::default());
+        let players = (1..=PLAYERS).map(UserId::new).collect_vec();
+        let mut last_matches = HashMap::new();
+        for game in 0..MATCHES_PER_PLAYER {
+            let mut historical_match_data = data.historical_match_data.lock().unwrap();
+            let mut player_data = data.player_data.get_mut(&queue_id).unwrap();
+            for player in players.iter() {
+                let match_id = MatchUuid::new();
+                let mut match_data = test_match(queue_id, vec![vec![*player]]);
+                match_data.map = Some(format!("map {}", game));
+                match_data.resolved = true;
+                historical_match_data.insert(match_id, match_data);
+                player_data
+                    .entry(*player)
+                    .or_default()
+                    .game_history
+                    .push(match_id);
+                last_matches.insert(*player, match_id);
+            }
+        }
+        let last_map = format!("map {}", MATCHES_PER_PLAYER - 1);
+
+        for player in players.iter() {
+            assert_eq!(
+                get_previous_game_members(&data, &queue_id, *player),
+                Some(vec![vec![*player]])
+            );
+            assert_eq!(
+                get_previous_map(&data, &queue_id, *player).as_ref(),
+                Some(&last_map)
+            );
+        }
+
+        // Pruned matches may still be referenced from a player's history
+        data.historical_match_data
+            .lock()
+            .unwrap()
+            .remove(&last_matches[&players[0]]);
+        assert_eq!(
+            get_previous_game_members(&data, &queue_id, players[0]),
+            None
+        );
+        assert_eq!(get_previous_map(&data, &queue_id, players[0]), None);
+    }
+
+    #[tokio::test]
+    async fn party_leaves_queue_together() {
+        let data = Arc::new(Data::default());
+        let guild_id = GuildId::new(1);
+        let queue_id = add_queue(&data, guild_id, QueueConfiguration::default());
+        let party = (1..=3).map(UserId::new).collect_vec();
+        let solo_player = UserId::new(4);
+        add_party(&data, guild_id, &party);
+        let http = Arc::new(Http::new(""));
+        for (player, queue_party) in [(party[0], true), (solo_player, false)] {
+            try_queue_player(
+                data.clone(),
+                &queue_id,
+                player,
+                http.clone(),
+                guild_id,
+                queue_party,
+                true,
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(data.queued_players.get(&queue_id).unwrap().len(), 4);
+
+        assert_eq!(
+            player_leave_queue(data.clone(), party[1], true, &queue_id),
+            locale::text(&data, &queue_id, Message::PartyLeftQueue)
+        );
+        assert_eq!(
+            *data.queued_players.get(&queue_id).unwrap(),
+            HashSet::from([solo_player])
+        );
+        let global_player_data = data.global_player_data.lock().unwrap();
+        assert!(party.iter().all(|player| matches!(
+            global_player_data[&guild_id][player].queue_state,
+            QueueState::None
+        )));
+    }
+
+    #[test]
+    fn commands_fit_discord_limits() {
+        let commands = commands();
+        assert!(commands.len() <= 100);
+        for command in &commands {
+            check_discord_limits(command);
+        }
+    }
 }
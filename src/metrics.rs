@@ -0,0 +1,146 @@
+//! Prometheus metrics, served over HTTP when built with the `metrics` feature and the
+//! `METRICS_PORT` environment variable is set. Without the feature every recorder is a no-op.
+
+use crate::QueueUuid;
+
+#[cfg(feature = "metrics")]
+mod exporter {
+    use std::sync::LazyLock;
+
+    use prometheus::{
+        register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+        register_int_gauge_vec, Encoder, Histogram, IntCounter, IntCounterVec, IntGauge,
+        IntGaugeVec, TextEncoder,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    pub static QUEUED_PLAYERS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+        register_int_gauge_vec!(
+            "queued_players",
+            "Players waiting in each queue",
+            &["queue"]
+        )
+        .unwrap()
+    });
+    pub static ACTIVE_MATCHES: LazyLock<IntGauge> = LazyLock::new(|| {
+        register_int_gauge!("active_matches", "Matches that haven't been resolved").unwrap()
+    });
+    pub static MATCHMAKING_REQUESTS: LazyLock<IntCounter> = LazyLock::new(|| {
+        register_int_counter!(
+            "matchmaking_requests_total",
+            "Times matchmaking was requested"
+        )
+        .unwrap()
+    });
+    pub static MATCHES_FORMED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+        register_int_counter_vec!("matches_formed_total", "Matches created", &["queue"]).unwrap()
+    });
+    pub static MATCH_COST: LazyLock<Histogram> = LazyLock::new(|| {
+        register_histogram!(
+            "match_cost",
+            "Matchmaking cost of created matches",
+            vec![1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0]
+        )
+        .unwrap()
+    });
+    pub static MATCHES_RESOLVED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+        register_int_counter_vec!("matches_resolved_total", "Resolved matches", &["result"])
+            .unwrap()
+    });
+    pub static RATELIMITS: LazyLock<IntCounter> = LazyLock::new(|| {
+        register_int_counter!("ratelimits_total", "Discord rate limit events").unwrap()
+    });
+
+    pub async fn serve(port: u16) -> std::io::Result<()> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+        println!("Serving metrics on port {}", port);
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            tokio::spawn(async move {
+                // Every request gets the metrics, so the request itself only needs draining
+                let mut request = [0; 1024];
+                if stream.read(&mut request).await.is_err() {
+                    return;
+                }
+                let mut body = vec![];
+                TextEncoder::new()
+                    .encode(&prometheus::gather(), &mut body)
+                    .ok();
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(header.as_bytes()).await.ok();
+                stream.write_all(&body).await.ok();
+            });
+        }
+    }
+}
+
+/// Starts the metrics server if `METRICS_PORT` is set
+pub fn spawn_server() {
+    #[cfg(feature = "metrics")]
+    {
+        let Some(port) = std::env::var("METRICS_PORT")
+            .ok()
+            .and_then(|port| port.parse::<u16>().ok())
+        else {
+            return;
+        };
+        tokio::spawn(async move {
+            if let Err(err) = exporter::serve(port).await {
+                eprintln!("Metrics server failed: {}", err);
+            }
+        });
+    }
+}
+
+pub fn set_queued_players(queue_id: &QueueUuid, count: usize) {
+    #[cfg(feature = "metrics")]
+    exporter::QUEUED_PLAYERS
+        .with_label_values(&[&queue_id.0.to_string()])
+        .set(count as i64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (queue_id, count);
+}
+
+pub fn set_active_matches(count: usize) {
+    #[cfg(feature = "metrics")]
+    exporter::ACTIVE_MATCHES.set(count as i64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = count;
+}
+
+pub fn record_matchmaking_request() {
+    #[cfg(feature = "metrics")]
+    exporter::MATCHMAKING_REQUESTS.inc();
+}
+
+pub fn record_match_formed(queue_id: &QueueUuid, cost: f32) {
+    #[cfg(feature = "metrics")]
+    {
+        exporter::MATCHES_FORMED
+            .with_label_values(&[&queue_id.0.to_string()])
+            .inc();
+        exporter::MATCH_COST.observe(cost as f64);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (queue_id, cost);
+}
+
+pub fn record_match_resolved(result: &crate::MatchResult) {
+    #[cfg(feature = "metrics")]
+    exporter::MATCHES_RESOLVED
+        .with_label_values(&[&result.to_string()])
+        .inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = result;
+}
+
+pub fn record_ratelimit() {
+    #[cfg(feature = "metrics")]
+    exporter::RATELIMITS.inc();
+}
@@ -1,35 +1,209 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
+use chrono::{Duration, Utc};
 use itertools::Itertools;
 use poise::{
-    serenity_prelude::{CacheHttp, CreateMessage, Mentionable, UserId},
+    serenity_prelude::{
+        self as serenity, CacheHttp, ComponentInteraction, CreateActionRow,
+        CreateInteractionResponseMessage, CreateMessage, GuildId, Http, Mentionable, UserId,
+    },
     CreateReply,
 };
 
 use crate::{
-    ButtonData, Context, Data, Error, GlobalPlayerData, GroupUuid, QueueGroup, QueueState,
+    ButtonData, Context, Data, Error, GlobalPlayerData, GroupUuid, PartyInviteLink, QueueGroup,
+    QueueState,
 };
 
-/// Invites player to party
-#[poise::command(slash_command, prefix_command, rename = "invite")]
-async fn party_invite(
-    ctx: Context<'_>,
-    #[description = "Invite player to party"] user: UserId,
-) -> Result<(), Error> {
+/// Returns the author's party, creating one led by them if they aren't in one
+fn get_or_create_party(ctx: &Context<'_>) -> GroupUuid {
+    let guild_id = ctx.guild_id().unwrap();
+    let party = {
+        let mut user_data = ctx.data().global_player_data.lock().unwrap();
+        let user_data = user_data
+            .entry(guild_id)
+            .or_default()
+            .entry(ctx.author().id)
+            .or_default();
+        if user_data.party.is_none() {
+            user_data.party = Some(GroupUuid::new());
+        }
+        user_data.party.unwrap()
+    };
+    ctx.data()
+        .group_data
+        .lock()
+        .unwrap()
+        .entry(party)
+        .or_insert(QueueGroup {
+            guild: guild_id,
+            players: HashSet::from([ctx.author().id]),
+            pending_invites: HashSet::new(),
+            invite_link: None,
+            last_active: Utc::now(),
+            streak_requeues: None,
+        });
+    party
+}
+
+/// Largest party that can queue in any of the guild's queues
+fn max_party_size(data: &Data, guild_id: GuildId) -> Option<usize> {
+    let queues = data
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .map(|guild_data| guild_data.queues.clone())
+        .unwrap_or_default();
+    queues
+        .iter()
+        .map(|queue| {
+            let config = data.configuration.get(queue).unwrap();
+            config.max_party_size.unwrap_or(config.team_size) as usize
+        })
+        .max()
+}
+
+/// Periodically disbands parties that have been inactive too long and drops expired invites
+pub async fn prune_stale_parties(data: Arc<Data>, http: Arc<Http>) {
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(10 * 60)).await;
+        let now = Utc::now();
+        let guilds = data
+            .guild_data
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect_vec();
+        let expiry_settings = guilds
+            .into_iter()
+            .filter_map(|guild_id| {
+                party_expiry_settings(&data, guild_id).map(|settings| (guild_id, settings))
+            })
+            .collect::<HashMap<_, _>>();
+        let mut stale_parties = vec![];
+        {
+            let global_player_data = data.global_player_data.lock().unwrap();
+            let mut group_data = data.group_data.lock().unwrap();
+            for (party_uuid, party) in group_data.iter_mut() {
+                let Some((stale_party_hours, party_invite_expiry_minutes)) =
+                    expiry_settings.get(&party.guild).cloned()
+                else {
+                    continue;
+                };
+                let in_queue_or_game = party.players.iter().any(|player| {
+                    global_player_data
+                        .get(&party.guild)
+                        .and_then(|guild_players| guild_players.get(player))
+                        .is_some_and(|player| !matches!(player.queue_state, QueueState::None))
+                });
+                let open_invite_link = party
+                    .invite_link
+                    .is_some_and(|invite_link| invite_link.expires > now);
+                if in_queue_or_game || open_invite_link {
+                    party.last_active = now;
+                    continue;
+                }
+                let idle_time = now - party.last_active;
+                if idle_time > Duration::minutes(party_invite_expiry_minutes as i64) {
+                    party.pending_invites.clear();
+                }
+                let abandoned = party.players.len() <= 1 && party.pending_invites.is_empty();
+                if stale_party_hours > 0 && idle_time > Duration::hours(stale_party_hours as i64)
+                    || abandoned
+                        && idle_time > Duration::minutes(party_invite_expiry_minutes as i64)
+                {
+                    stale_parties.push((*party_uuid, party.guild, party.players.clone()));
+                }
+            }
+        }
+        for (party_uuid, guild_id, players) in stale_parties {
+            println!("Disbanding inactive party {:?}", party_uuid);
+            for player in players.iter() {
+                player
+                    .direct_message(
+                        http.clone(),
+                        CreateMessage::new()
+                            .content("Your party was disbanded because it has been inactive."),
+                    )
+                    .await
+                    .ok();
+            }
+            data.group_data.lock().unwrap().remove(&party_uuid);
+            let mut global_player_data = data.global_player_data.lock().unwrap();
+            let Some(guild_players) = global_player_data.get_mut(&guild_id) else {
+                continue;
+            };
+            for player in players {
+                if let Some(player_data) = guild_players.get_mut(&player) {
+                    if player_data.party == Some(party_uuid) {
+                        player_data.party = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Most lenient stale party hours and invite expiry minutes across the guild's queues
+fn party_expiry_settings(data: &Data, guild_id: GuildId) -> Option<(u32, u32)> {
+    let queues = data
+        .guild_data
+        .lock()
+        .unwrap()
+        .get(&guild_id)
+        .map(|guild_data| guild_data.queues.clone())
+        .unwrap_or_default();
+    let settings = queues
+        .iter()
+        .filter_map(|queue| data.configuration.get(queue))
+        .map(|config| (config.stale_party_hours, config.party_invite_expiry_minutes))
+        .collect_vec();
+    if settings.is_empty() {
+        return None;
+    }
+    // A queue with pruning disabled keeps all of the guild's parties
+    let stale_party_hours = if settings.iter().any(|(hours, _)| *hours == 0) {
+        0
+    } else {
+        settings.iter().map(|(hours, _)| *hours).max().unwrap()
+    };
+    let party_invite_expiry_minutes = settings.iter().map(|(_, minutes)| *minutes).max().unwrap();
+    Some((stale_party_hours, party_invite_expiry_minutes))
+}
+
+/// Returns why the author can't change their party right now, if they can't
+fn party_change_blocked(ctx: &Context<'_>) -> Option<String> {
     let queue_state = ctx
         .data()
         .global_player_data
         .lock()
         .unwrap()
+        .entry(ctx.guild_id().unwrap())
+        .or_default()
         .entry(ctx.author().id)
         .or_default()
         .queue_state
         .clone();
-    if let Some(failure_message) = match queue_state {
-        QueueState::Queued(..) => Some(format!("Cannot invite players to party while in queue")),
-        QueueState::InGame => Some(format!("Cannot invite players to party while in game")),
+    match queue_state {
+        QueueState::Queued(..) => Some("Cannot invite players to party while in queue".to_string()),
+        QueueState::InGame(_) => Some("Cannot invite players to party while in game".to_string()),
         QueueState::None => None,
-    } {
+    }
+}
+
+/// Invites player to party
+#[poise::command(slash_command, prefix_command, rename = "invite")]
+async fn party_invite(
+    ctx: Context<'_>,
+    #[description = "Invite player to party"] user: UserId,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if let Some(failure_message) = party_change_blocked(&ctx) {
         ctx.send(
             CreateReply::default()
                 .content(failure_message)
@@ -38,42 +212,46 @@ async fn party_invite(
         .await?;
         return Ok(());
     }
+    if guild_id.member(ctx, user).await.is_err() {
+        ctx.send(
+            CreateReply::default()
+                .content(format!("{} is not a member of this server", user.mention()))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
 
-    let party = {
-        let mut user_data = ctx.data().global_player_data.lock().unwrap();
-        let user_data = user_data.entry(ctx.author().id).or_default();
-        if user_data.party.is_none() {
-            user_data.party = Some(GroupUuid::new());
-        }
-        user_data.party.unwrap()
-    };
+    let party = get_or_create_party(&ctx);
+    let max_party_size = max_party_size(ctx.data(), guild_id);
     let user_party = {
         let mut group_data = ctx.data().group_data.lock().unwrap();
-        let user_party = group_data.entry(party).or_insert(QueueGroup {
-            players: HashSet::from([ctx.author().id]),
-            pending_invites: HashSet::new(),
-        });
-        user_party.pending_invites.insert(user);
-        user_party.clone()
+        let user_party = group_data.get_mut(&party).unwrap();
+        let party_size = user_party.players.len() + user_party.pending_invites.len() + 1;
+        if max_party_size.is_some_and(|max_party_size| party_size > max_party_size)
+            && !user_party.pending_invites.contains(&user)
+        {
+            None
+        } else {
+            user_party.pending_invites.insert(user);
+            user_party.last_active = Utc::now();
+            Some(user_party.clone())
+        }
     };
-    let Ok(_) = user
-        .direct_message(
-            ctx,
-            CreateMessage::default()
+    let Some(user_party) = user_party else {
+        ctx.send(
+            CreateReply::default()
                 .content(format!(
-                    "{} invited you to their group.\nCurrent members: {}",
-                    ctx.author().mention(),
-                    user_party
-                        .players
-                        .iter()
-                        .map(|p| format!("{}", p.mention()))
-                        .join(", ")
+                    "Cannot invite {} as parties can have at most {} players (including pending invites)",
+                    user.mention(),
+                    max_party_size.unwrap()
                 ))
-                .button(ButtonData::JoinParty(party).get_button())
-                .button(ButtonData::RejectParty(party).get_button()),
+                .ephemeral(true),
         )
-        .await
-    else {
+        .await?;
+        return Ok(());
+    };
+    let Ok(_) = send_party_invite(&ctx, party, &user_party, user).await else {
         ctx.send(
             CreateReply::default()
                 .content(format!(
@@ -94,6 +272,216 @@ async fn party_invite(
     Ok(())
 }
 
+/// DMs `user` an invite to the author's party
+async fn send_party_invite(
+    ctx: &Context<'_>,
+    party: GroupUuid,
+    user_party: &QueueGroup,
+    user: UserId,
+) -> Result<serenity::Message, serenity::Error> {
+    user.direct_message(
+        ctx,
+        CreateMessage::default()
+            .content(format!(
+                "{} invited you to their group.\nCurrent members: {}",
+                ctx.author().mention(),
+                user_party
+                    .players
+                    .iter()
+                    .map(|p| format!("{}", p.mention()))
+                    .join(", ")
+            ))
+            .button(ButtonData::JoinParty(party).get_button())
+            .button(ButtonData::RejectParty(party).get_button()),
+    )
+    .await
+}
+
+/// Invites your team, or the whole lobby, from your current or most recent match to your party
+#[poise::command(slash_command, prefix_command)]
+pub async fn queue_as_premade(
+    ctx: Context<'_>,
+    #[description = "Invite the other teams too"] whole_lobby: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let author = ctx.author().id;
+    let match_number = {
+        let match_channels = ctx.data().match_channels.lock().unwrap();
+        match_channels.get(&ctx.channel_id()).cloned()
+    };
+    // Outside a match channel, or once it's resolved, the author's last match is used
+    let members = match_number
+        .and_then(|match_number| {
+            let match_data = ctx.data().match_data.lock().unwrap();
+            match_data
+                .get(&match_number)
+                .map(|match_data| match_data.members.clone())
+        })
+        .filter(|members| members.iter().flatten().contains(&author))
+        .or_else(|| {
+            let queues = ctx
+                .data()
+                .guild_data
+                .lock()
+                .unwrap()
+                .get(&guild_id)
+                .map(|guild_data| guild_data.queues.clone())
+                .unwrap_or_default();
+            let last_matches = queues
+                .iter()
+                .filter_map(|queue| {
+                    let player_data = ctx.data().player_data.get(queue)?;
+                    player_data.get(&author)?.game_history.last().cloned()
+                })
+                .collect_vec();
+            let historical_match_data = ctx.data().historical_match_data.lock().unwrap();
+            last_matches
+                .iter()
+                .filter_map(|match_id| historical_match_data.get(match_id))
+                .max_by_key(|match_data| match_data.match_end_time)
+                .map(|match_data| match_data.members.clone())
+        });
+    let Some(members) = members else {
+        ctx.send(
+            CreateReply::default()
+                .content("You haven't played a match to form a party from")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let invitees = if whole_lobby.unwrap_or(false) {
+        members.into_iter().flatten().collect_vec()
+    } else {
+        members
+            .into_iter()
+            .find(|team| team.contains(&author))
+            .unwrap_or_default()
+    };
+    let party = get_or_create_party(&ctx);
+    let max_party_size = max_party_size(ctx.data(), guild_id);
+    let user_party = {
+        let mut group_data = ctx.data().group_data.lock().unwrap();
+        let user_party = group_data.get_mut(&party).unwrap();
+        let new_invites = invitees
+            .iter()
+            .filter(|player| {
+                !user_party.players.contains(player) && !user_party.pending_invites.contains(player)
+            })
+            .cloned()
+            .collect_vec();
+        let party_size = user_party.players.len() + user_party.pending_invites.len();
+        if max_party_size
+            .is_some_and(|max_party_size| party_size + new_invites.len() > max_party_size)
+        {
+            Err(max_party_size.unwrap())
+        } else {
+            user_party
+                .pending_invites
+                .extend(new_invites.iter().cloned());
+            user_party.last_active = Utc::now();
+            Ok((user_party.clone(), new_invites))
+        }
+    };
+    let (user_party, new_invites) = match user_party {
+        Ok(user_party) => user_party,
+        Err(max_party_size) => {
+            ctx.send(
+                CreateReply::default()
+                    .content(format!(
+                        "Parties can have at most {} players (including pending invites)",
+                        max_party_size
+                    ))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let mut sent_invites = vec![];
+    let mut failed_invites = vec![];
+    for player in new_invites {
+        match send_party_invite(&ctx, party, &user_party, player).await {
+            Ok(_) => sent_invites.push(player),
+            Err(_) => failed_invites.push(player),
+        }
+    }
+    let mut content = if sent_invites.is_empty() && failed_invites.is_empty() {
+        "Everyone is already in or invited to your party".to_string()
+    } else {
+        format!(
+            "Invited {} to your party. They can accept once they're out of their match.",
+            sent_invites
+                .iter()
+                .map(|player| player.mention())
+                .join(", ")
+        )
+    };
+    if !failed_invites.is_empty() {
+        if let Some(user_party) = ctx.data().group_data.lock().unwrap().get_mut(&party) {
+            for player in failed_invites.iter() {
+                user_party.pending_invites.remove(player);
+            }
+        }
+        content += format!(
+            "\nCould not DM {}, maybe they don't have dms open?",
+            failed_invites
+                .iter()
+                .map(|player| player.mention())
+                .join(", ")
+        )
+        .as_str();
+    }
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Posts a button anyone can use to join your party until it expires
+#[poise::command(slash_command, prefix_command, rename = "invite_link")]
+async fn party_invite_link(
+    ctx: Context<'_>,
+    #[description = "Minutes until the link expires"]
+    #[min = 1]
+    expire_minutes: Option<u32>,
+) -> Result<(), Error> {
+    if let Some(failure_message) = party_change_blocked(&ctx) {
+        ctx.send(
+            CreateReply::default()
+                .content(failure_message)
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let party = get_or_create_party(&ctx);
+    let invite_link = PartyInviteLink {
+        nonce: rand::random(),
+        expires: chrono::offset::Utc::now()
+            + chrono::Duration::minutes(expire_minutes.unwrap_or(15) as i64),
+    };
+    {
+        let mut group_data = ctx.data().group_data.lock().unwrap();
+        let user_party = group_data.get_mut(&party).unwrap();
+        user_party.invite_link = Some(invite_link);
+        user_party.last_active = Utc::now();
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "{} is looking for party members! This invite expires <t:{}:R>.",
+                ctx.author().mention(),
+                invite_link.expires.timestamp()
+            ))
+            .components(vec![CreateActionRow::Buttons(vec![
+                ButtonData::JoinPartyLink(party, invite_link.nonce).get_button(),
+            ])])
+            .ephemeral(false),
+    )
+    .await?;
+    Ok(())
+}
+
 pub async fn leave_party(
     data: Arc<Data>,
     user: &UserId,
@@ -122,12 +510,237 @@ pub async fn leave_party(
     Ok(())
 }
 
+pub async fn join_party(
+    message_component: &ComponentInteraction,
+    data: Arc<Data>,
+    ctx: &serenity::Context,
+    party_uuid: GroupUuid,
+    link_nonce: Option<u64>,
+) -> Result<(), Error> {
+    let guild_id = data
+        .group_data
+        .lock()
+        .unwrap()
+        .get(&party_uuid)
+        .map(|party| party.guild);
+    let Some(guild_id) = guild_id else {
+        message_component
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Party no longer exists.")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    };
+    if let Err(e) = {
+        let mut player_data = data.global_player_data.lock().unwrap();
+        let player_data = player_data
+            .entry(guild_id)
+            .or_default()
+            .entry(message_component.user.id)
+            .or_default();
+        match player_data.queue_state {
+            QueueState::None => Ok(()),
+            QueueState::Queued(..) => Err("Cannot join party while queued!"),
+            QueueState::InGame(_) => Err("Cannot join party while in game!"),
+        }
+    } {
+        message_component
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(e)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+    let max_party_size = max_party_size(&data, guild_id);
+    let group_members = 'group_members: {
+        let global_player_data = data.global_player_data.lock().unwrap();
+        let mut group_data = data.group_data.lock().unwrap();
+        let party = group_data.get_mut(&party_uuid);
+        let Some(party) = party else {
+            break 'group_members Err("Party no longer exists.");
+        };
+        let user = message_component.user.id;
+        if let Some(link_nonce) = link_nonce {
+            let link_valid = party.invite_link.is_some_and(|invite_link| {
+                invite_link.nonce == link_nonce && invite_link.expires > chrono::offset::Utc::now()
+            });
+            if !link_valid {
+                break 'group_members Err("Party invite no longer valid.");
+            }
+            if party.players.contains(&user) {
+                break 'group_members Err("You are already in this party.");
+            }
+        } else if !party.pending_invites.contains(&user) {
+            break 'group_members Err("Party invite no longer valid.");
+        }
+        // Joining a queued party would leave the new member out of its match
+        let party_busy = party.players.iter().any(|member| {
+            global_player_data
+                .get(&guild_id)
+                .and_then(|players| players.get(member))
+                .is_some_and(|member| !matches!(member.queue_state, QueueState::None))
+        });
+        if party_busy {
+            break 'group_members Err("Cannot join party while it is queued or in game!");
+        }
+        // Other outstanding invites keep their spot in the party
+        let other_invites = party
+            .pending_invites
+            .iter()
+            .filter(|invited| **invited != user)
+            .count();
+        if max_party_size
+            .is_some_and(|max_party_size| party.players.len() + other_invites >= max_party_size)
+        {
+            break 'group_members Err("Party is full.");
+        }
+        party.pending_invites.remove(&user);
+        party.players.insert(user);
+        party.last_active = Utc::now();
+        Ok(party.players.clone())
+    };
+    let group_members = match group_members {
+        Ok(group_members) => group_members,
+        Err(e) => {
+            message_component
+                .create_response(
+                    ctx,
+                    serenity::CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(e)
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    let old_party = {
+        let mut player_data = data.global_player_data.lock().unwrap();
+        let player_data = player_data
+            .entry(guild_id)
+            .or_default()
+            .entry(message_component.user.id)
+            .or_default();
+        let old_party = player_data.party;
+        player_data.party = Some(party_uuid);
+        old_party
+    };
+    if let Some(old_party) = old_party {
+        if old_party != party_uuid {
+            leave_party(
+                data,
+                &message_component.user.id,
+                Arc::new(ctx.http()),
+                old_party,
+            )
+            .await?;
+        }
+    }
+
+    for group_member in group_members {
+        if group_member == message_component.user.id {
+            continue;
+        }
+        group_member
+            .direct_message(
+                ctx,
+                CreateMessage::new().content(format!(
+                    "{} joined your party!",
+                    message_component.user.id.mention()
+                )),
+            )
+            .await?;
+    }
+    if link_nonce.is_none() {
+        message_component.message.delete(ctx).await?;
+    }
+    message_component
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("Joined party!")
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn reject_party(
+    message_component: &ComponentInteraction,
+    data: Arc<Data>,
+    ctx: &serenity::Context,
+    party_uuid: GroupUuid,
+) -> Result<(), Error> {
+    let group_members = {
+        let mut group_data = data.group_data.lock().unwrap();
+        let party = group_data.get_mut(&party_uuid);
+        if let Some(party) = party {
+            party.pending_invites.remove(&message_component.user.id);
+            Some(party.players.clone())
+        } else {
+            None
+        }
+    };
+    let Some(group_members) = group_members else {
+        message_component
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content("Party no longer exists."),
+                ),
+            )
+            .await?;
+        return Ok(());
+    };
+    for group_member in group_members {
+        if group_member == message_component.user.id {
+            continue;
+        }
+        group_member
+            .direct_message(
+                ctx,
+                CreateMessage::new().content(format!(
+                    "{} rejected your party invite",
+                    message_component.user.id.mention()
+                )),
+            )
+            .await?;
+    }
+    message_component.message.delete(ctx).await?;
+    message_component
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("Rejected party invite.")
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
 /// Leave party
 #[poise::command(slash_command, prefix_command, rename = "leave")]
 async fn party_leave(ctx: Context<'_>) -> Result<(), Error> {
     let old_party = {
         let mut user_data = ctx.data().global_player_data.lock().unwrap();
         let user_data = user_data
+            .entry(ctx.guild_id().unwrap())
+            .or_default()
             .entry(ctx.author().id)
             .or_insert(GlobalPlayerData::default());
         let old_party = user_data.party.clone();
@@ -165,6 +778,8 @@ async fn party_list(ctx: Context<'_>) -> Result<(), Error> {
     let party = {
         let mut user_data = ctx.data().global_player_data.lock().unwrap();
         let user_data = user_data
+            .entry(ctx.guild_id().unwrap())
+            .or_default()
             .entry(ctx.author().id)
             .or_insert(GlobalPlayerData::default());
         user_data.party.clone()
@@ -206,7 +821,16 @@ async fn party_list(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(slash_command, prefix_command)]
 pub async fn list_parties(ctx: Context<'_>) -> Result<(), Error> {
     let response = {
-        let groups = ctx.data().group_data.lock().unwrap().clone();
+        let guild_id = ctx.guild_id().unwrap();
+        let groups: HashMap<GroupUuid, QueueGroup> = ctx
+            .data()
+            .group_data
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, group)| group.guild == guild_id)
+            .map(|(id, group)| (*id, group.clone()))
+            .collect();
         format!("Groups: {}", serde_json::to_string(&groups).unwrap())
     };
     ctx.send(CreateReply::default().content(response).ephemeral(true))
@@ -218,7 +842,7 @@ pub async fn list_parties(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(
     slash_command,
     prefix_command,
-    subcommands("party_invite", "party_leave", "party_list")
+    subcommands("party_invite", "party_invite_link", "party_leave", "party_list")
 )]
 pub async fn party(_: Context<'_>) -> Result<(), Error> {
     Ok(())
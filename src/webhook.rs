@@ -0,0 +1,108 @@
+//! Outgoing webhooks notifying external tools when matches are created and resolved
+
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use poise::serenity_prelude::UserId;
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::{
+    format_timestamp, format_unix_timestamp, Data, MatchData, MatchResult, MatchUuid, QueueUuid,
+};
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Failed to build webhook client")
+});
+
+#[derive(Serialize)]
+struct MatchWebhookPayload {
+    event: &'static str,
+    match_id: MatchUuid,
+    name: String,
+    queue: QueueUuid,
+    teams: Vec<Vec<UserId>>,
+    map: Option<String>,
+    result: Option<MatchResult>,
+    ratings: HashMap<UserId, Option<f64>>,
+    started_at: Option<String>,
+    timestamp: String,
+}
+
+/// Posts the match to the queue's webhook, if one is configured. Pass the result once the match
+/// has been resolved.
+pub fn send_match_webhook(
+    data: &Data,
+    match_id: MatchUuid,
+    match_data: &MatchData,
+    result: Option<&MatchResult>,
+) {
+    let (url, secret) = {
+        let config = data.configuration.get(&match_data.queue).unwrap();
+        let Some(url) = config.webhook_url.clone() else {
+            return;
+        };
+        (url, config.webhook_secret.clone())
+    };
+    let ratings = {
+        let player_data = data.player_data.get(&match_data.queue).unwrap();
+        match_data
+            .members
+            .iter()
+            .flatten()
+            .map(|player| {
+                (
+                    *player,
+                    player_data
+                        .get(player)
+                        .and_then(|player| player.rating)
+                        .map(|rating| rating.rating),
+                )
+            })
+            .collect()
+    };
+    let payload = MatchWebhookPayload {
+        event: if result.is_some() {
+            "match_resolved"
+        } else {
+            "match_created"
+        },
+        match_id,
+        name: match_data.name.clone(),
+        queue: match_data.queue,
+        teams: match_data.members.clone(),
+        map: match_data.map.clone(),
+        result: result.cloned(),
+        ratings,
+        started_at: match_data.match_start_time.and_then(format_unix_timestamp),
+        timestamp: format_timestamp(Utc::now()),
+    };
+    let body = match serde_json::to_string(&payload) {
+        Ok(body) => body,
+        Err(err) => {
+            eprintln!("Could not serialize webhook payload: {}", err);
+            return;
+        }
+    };
+    let mut request = CLIENT.post(url).header("Content-Type", "application/json");
+    if let Some(secret) = secret {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body.as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+        request = request.header("X-Signature-256", format!("sha256={}", signature));
+    }
+    tokio::spawn(async move {
+        match request.body(body).send().await {
+            Ok(response) if !response.status().is_success() => {
+                eprintln!("Webhook returned status {}", response.status());
+            }
+            Ok(_) => {}
+            Err(err) => eprintln!("Webhook failed: {}", err),
+        }
+    });
+}